@@ -0,0 +1,57 @@
+extern crate debugger;
+#[macro_use]
+extern crate js;
+
+use debugger::{DebugContext, Object};
+use js::jsapi;
+use js::jsapi::JSAutoCompartment;
+use js::rust::Runtime;
+use std::ptr;
+use std::time::{Duration, Instant};
+
+const HANDLES: usize = 100000;
+const COLLECTIONS: u32 = 10;
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs() as f64 * 1000.0 + duration.subsec_nanos() as f64 / 1000000.0
+}
+
+fn main() {
+    let runtime = Runtime::new();
+    DebugContext::with(&runtime, |context| {
+        let cx = context.as_raw();
+        rooted!(in (cx) let mut global = ptr::null_mut());
+        unsafe {
+            assert!(debugger::new_global_object(cx, global.handle_mut()));
+        }
+        let _ac = JSAutoCompartment::new(cx, global.get());
+
+        let start = Instant::now();
+        let objects: Vec<Object> = (0..HANDLES).map(|_| {
+            Object::new(context, unsafe { jsapi::JS_NewPlainObject(cx) })
+        }).collect();
+        println!("create {} handles: {:.2} ms", HANDLES, millis(start.elapsed()));
+
+        let start = Instant::now();
+        for _ in 0..COLLECTIONS {
+            unsafe {
+                jsapi::JS_GC(jsapi::JS_GetRuntime(cx));
+            }
+        }
+        println!(
+            "gc with {} live handles: {:.2} ms",
+            HANDLES,
+            millis(start.elapsed()) / COLLECTIONS as f64
+        );
+
+        let start = Instant::now();
+        drop(objects);
+        println!("drop {} handles: {:.2} ms", HANDLES, millis(start.elapsed()));
+
+        let start = Instant::now();
+        unsafe {
+            jsapi::JS_GC(jsapi::JS_GetRuntime(cx));
+        }
+        println!("gc with no live handles: {:.2} ms", millis(start.elapsed()));
+    });
+}
@@ -0,0 +1,92 @@
+extern crate debugger;
+#[macro_use]
+extern crate js;
+
+use debugger::{DebugContext, Debugger, Source};
+use debugger::debugger::ScriptQuery;
+use js::jsval::UndefinedValue;
+use js::rust::Runtime;
+use std::ptr;
+use std::time::{Duration, Instant};
+
+const SOURCE_LINES: usize = 100000;
+const PROPERTIES: usize = 100000;
+const ITERATIONS: u32 = 10;
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs() as f64 * 1000.0 + duration.subsec_nanos() as f64 / 1000000.0
+}
+
+fn find_source<'rt>(cx: DebugContext<'rt>, debugger: &Debugger<'rt>, url: &str) -> Source<'rt> {
+    let query = ScriptQuery {
+        url: Some(url.to_string()),
+        line: None
+    };
+    let scripts = debugger.find_scripts(cx, &query).ok().expect("findScripts failed");
+    scripts[0].get_source(cx).ok().expect("source failed")
+}
+
+fn bench_source_text<'rt>(cx: DebugContext<'rt>, debugger: &Debugger<'rt>, url: &str) {
+    let source = find_source(cx, debugger, url);
+    let start = Instant::now();
+    let mut length = 0;
+    for _ in 0..ITERATIONS {
+        length = source.get_text(cx).ok().expect("text failed").len();
+    }
+    println!(
+        "get_text {} ({} bytes): {:.2} ms",
+        url,
+        length,
+        millis(start.elapsed()) / ITERATIONS as f64
+    );
+}
+
+fn main() {
+    let runtime = Runtime::new();
+    DebugContext::with(&runtime, |cx| {
+        rooted!(in (runtime.cx()) let mut global = ptr::null_mut());
+        unsafe {
+            assert!(debugger::new_global_object(runtime.cx(), global.handle_mut()));
+        }
+        let debugger = Debugger::new(cx);
+        let global_object = debugger.add_debuggee(cx, global.handle())
+            .ok()
+            .expect("addDebuggee failed");
+
+        let sources = [
+            ("ascii.js", "var ascii = 'plain text';\n"),
+            ("latin1.js", "var latin1 = 'caf\u{e9} cr\u{e8}me';\n"),
+            ("utf16.js", "var utf16 = '\u{20ac} \u{3b1}\u{3b2}\u{3b3}';\n")
+        ];
+        for &(url, line) in sources.iter() {
+            let text: String = (0..SOURCE_LINES).map(|_| line).collect();
+            rooted!(in (runtime.cx()) let mut rval = UndefinedValue());
+            runtime.evaluate_script(global.handle(), &text, url, 1, rval.handle_mut()).unwrap();
+            bench_source_text(cx, &debugger, url);
+        }
+
+        let code = format!("for (var i = 0; i < {}; i++) this['property' + i] = i;", PROPERTIES);
+        global_object.execute_in_global(cx, &code).ok().expect("executeInGlobal failed");
+        let start = Instant::now();
+        let mut count = 0;
+        for _ in 0..ITERATIONS {
+            count = global_object.get_own_property_names(cx).ok().expect("names failed").len();
+        }
+        println!(
+            "get_own_property_names ({} names): {:.2} ms",
+            count,
+            millis(start.elapsed()) / ITERATIONS as f64
+        );
+
+        let code: String = (0..SOURCE_LINES).map(|_| "0;\n").collect();
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            global_object.execute_in_global(cx, &code).ok().expect("executeInGlobal failed");
+        }
+        println!(
+            "execute_in_global ({} bytes): {:.2} ms",
+            code.len(),
+            millis(start.elapsed()) / ITERATIONS as f64
+        );
+    });
+}
@@ -0,0 +1,523 @@
+#[macro_use]
+extern crate js;
+extern crate debugger;
+
+use debugger::{
+    CompletionValue,
+    DebugContext,
+    Debugger,
+    EventLoop,
+    Frame,
+    OnDebuggerStatement,
+    OnNewScript,
+    OnStepComplete,
+    PauseController,
+    ResumptionValue,
+    Result,
+    Script,
+    StepKind,
+    Stepper,
+    Value
+};
+use debugger::script::OnHit;
+use debugger::session::{BreakpointSpec, Breakpoints};
+use js::jsval::UndefinedValue;
+use js::rust::Runtime;
+use std::cell::{Cell, RefCell};
+use std::env;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, Read, Write};
+use std::process;
+use std::ptr;
+use std::rc::{Rc, Weak};
+use std::result;
+
+enum Command {
+    Break(String, usize),
+    Run,
+    Backtrace,
+    Frame(usize),
+    Print(String),
+    Locals,
+    Step,
+    Next,
+    Finish,
+    Continue,
+    Watch(String),
+    Delete(u64),
+    Help,
+    Quit,
+    Empty
+}
+
+fn parse_command(line: &str) -> result::Result<Command, String> {
+    let line = line.trim();
+    let (name, argument) = match line.find(char::is_whitespace) {
+        Some(index) => (&line[..index], line[index..].trim()),
+        None => (line, "")
+    };
+    match name {
+        "" => Ok(Command::Empty),
+        "b" | "break" => {
+            let index = try!(argument.rfind(':').ok_or("usage: break file:line".to_string()));
+            let line = try!(argument[index + 1..].parse::<usize>().map_err(|_| {
+                format!("invalid line number '{}'", &argument[index + 1..])
+            }));
+            Ok(Command::Break(argument[..index].to_string(), line))
+        }
+        "r" | "run" => Ok(Command::Run),
+        "bt" | "backtrace" => Ok(Command::Backtrace),
+        "f" | "frame" => argument.parse().map(Command::Frame).map_err(|_| {
+            "usage: frame n".to_string()
+        }),
+        "p" | "print" if !argument.is_empty() => Ok(Command::Print(argument.to_string())),
+        "locals" => Ok(Command::Locals),
+        "s" | "step" => Ok(Command::Step),
+        "n" | "next" => Ok(Command::Next),
+        "finish" => Ok(Command::Finish),
+        "c" | "continue" => Ok(Command::Continue),
+        "watch" | "display" if !argument.is_empty() => {
+            Ok(Command::Watch(argument.to_string()))
+        }
+        "d" | "delete" => argument.parse().map(Command::Delete).map_err(|_| {
+            "usage: delete n".to_string()
+        }),
+        "h" | "help" => Ok(Command::Help),
+        "q" | "quit" => Ok(Command::Quit),
+        _ => Err(format!("unknown command '{}', try 'help'", line))
+    }
+}
+
+const HELP: &'static str = "\
+break file:line  set a breakpoint
+delete n         delete breakpoint n
+run              start the program
+bt               print a backtrace
+frame n          select frame n
+print expr       evaluate expr in the selected frame
+locals           print the variables of the selected frame
+step             step into the next statement
+next             step over the next statement
+finish           run until the selected frame returns
+continue         resume execution
+watch expr       print expr every time execution stops
+quit             exit jsdb";
+
+struct Watch {
+    expression: String,
+    last: Option<String>
+}
+
+struct Jsdb<'rt> {
+    this: RefCell<Weak<Jsdb<'rt>>>,
+    debugger: Rc<Debugger<'rt>>,
+    controller: RefCell<Option<Rc<PauseController<'rt>>>>,
+    stepper: Stepper<'rt>,
+    breakpoints: Breakpoints<'rt>,
+    watches: RefCell<Vec<Watch>>,
+    selected: Cell<usize>,
+    running: Cell<bool>,
+    run_requested: Cell<bool>,
+    quit_requested: Cell<bool>
+}
+
+struct Prompt<'rt>(Weak<Jsdb<'rt>>);
+
+impl<'rt> EventLoop<'rt> for Prompt<'rt> {
+    fn process_events(
+        &self,
+        cx: DebugContext<'rt>,
+        controller: &PauseController<'rt>
+    ) -> Result<'rt, ()> {
+        match self.0.upgrade() {
+            Some(ref jsdb) if !jsdb.quit_requested.get() => jsdb.prompt(cx, Some(controller)),
+            Some(_) => {
+                controller.resume(Some(CompletionValue::Terminate));
+                Ok(())
+            }
+            None => {
+                controller.resume(None);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<'rt> Jsdb<'rt> {
+    fn new(debugger: Rc<Debugger<'rt>>) -> Rc<Jsdb<'rt>> {
+        let jsdb = Rc::new(Jsdb {
+            this: RefCell::new(Weak::new()),
+            debugger: debugger.clone(),
+            controller: RefCell::new(None),
+            stepper: Stepper::new(debugger.clone()),
+            breakpoints: Breakpoints::new(debugger),
+            watches: RefCell::new(Vec::new()),
+            selected: Cell::new(0),
+            running: Cell::new(false),
+            run_requested: Cell::new(false),
+            quit_requested: Cell::new(false)
+        });
+        *jsdb.this.borrow_mut() = Rc::downgrade(&jsdb);
+        let prompt = Rc::new(Prompt(Rc::downgrade(&jsdb)));
+        *jsdb.controller.borrow_mut() = Some(Rc::new(PauseController::new(prompt)));
+        jsdb
+    }
+
+    fn this(&self) -> Rc<Jsdb<'rt>> {
+        self.this.borrow().upgrade().unwrap()
+    }
+
+    fn controller(&self) -> Rc<PauseController<'rt>> {
+        self.controller.borrow().as_ref().unwrap().clone()
+    }
+
+    fn prompt(
+        &self,
+        cx: DebugContext<'rt>,
+        controller: Option<&PauseController<'rt>>
+    ) -> Result<'rt, ()> {
+        print!("(jsdb) ");
+        let _ = io::stdout().flush();
+        let mut line = String::new();
+        let stdin = io::stdin();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            println!();
+            self.quit(controller);
+            return Ok(());
+        }
+        let command = match parse_command(&line) {
+            Ok(command) => command,
+            Err(message) => {
+                println!("{}", message);
+                return Ok(());
+            }
+        };
+        match command {
+            Command::Empty => {}
+            Command::Help => println!("{}", HELP),
+            Command::Quit => self.quit(controller),
+            Command::Break(file, line) => try!(self.add_breakpoint(cx, file, line)),
+            Command::Run => {
+                if self.running.get() {
+                    println!("The program is already running.");
+                } else {
+                    self.run_requested.set(true);
+                }
+            }
+            Command::Delete(number) => try!(self.delete_breakpoint(cx, number)),
+            Command::Watch(expression) => {
+                println!("Watching {}", expression);
+                self.watches.borrow_mut().push(Watch {
+                    expression: expression,
+                    last: None
+                });
+            }
+            Command::Print(expression) => {
+                let completion = match self.selected_frame(cx, controller) {
+                    Some(frame) => try!(frame.eval(cx, &expression)),
+                    None => match try!(self.debugger.get_debuggees(cx)).into_iter().next() {
+                        Some(global) => try!(global.execute_in_global(cx, &expression)),
+                        None => return Ok(())
+                    }
+                };
+                println!("{}", try!(describe_completion(cx, completion)));
+            }
+            Command::Continue => match controller {
+                Some(controller) => {
+                    controller.resume(None);
+                }
+                None => println!("The program is not being run.")
+            },
+            Command::Step => try!(self.step(cx, controller, StepKind::In)),
+            Command::Next => try!(self.step(cx, controller, StepKind::Over)),
+            Command::Finish => try!(self.step(cx, controller, StepKind::Out)),
+            Command::Backtrace => {
+                let mut current = controller.and_then(|controller| {
+                    controller.get_paused_frame(cx)
+                });
+                if current.is_none() {
+                    println!("No stack.");
+                }
+                let mut index = 0;
+                while let Some(frame) = current {
+                    let marker = if index == self.selected.get() { "*" } else { " " };
+                    println!("{}#{} {}", marker, index, try!(describe_frame(cx, &frame)));
+                    current = try!(frame.get_older(cx));
+                    index += 1;
+                }
+            }
+            Command::Frame(index) => {
+                let previous = self.selected.get();
+                self.selected.set(index);
+                match self.selected_frame(cx, controller) {
+                    Some(frame) => println!("#{} {}", index, try!(describe_frame(cx, &frame))),
+                    None => {
+                        self.selected.set(previous);
+                        println!("No frame #{}.", index);
+                    }
+                }
+            }
+            Command::Locals => {
+                let environment = match self.selected_frame(cx, controller) {
+                    Some(frame) => try!(frame.get_environment(cx)),
+                    None => None
+                };
+                match environment {
+                    Some(environment) => {
+                        for name in try!(environment.names(cx)) {
+                            match try!(environment.get_variable(cx, &name)) {
+                                Some(value) => {
+                                    println!("{} = {}", name, try!(describe(cx, value)))
+                                }
+                                None => println!("{} = <optimized out>", name)
+                            }
+                        }
+                    }
+                    None => println!("No frame selected.")
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn selected_frame(
+        &self,
+        cx: DebugContext<'rt>,
+        controller: Option<&PauseController<'rt>>
+    ) -> Option<Frame<'rt>> {
+        let mut frame = match controller.and_then(|controller| controller.get_paused_frame(cx)) {
+            Some(frame) => frame,
+            None => return None
+        };
+        for _ in 0..self.selected.get() {
+            frame = match frame.get_older(cx) {
+                Ok(Some(older)) => older,
+                _ => return None
+            };
+        }
+        Some(frame)
+    }
+
+    fn step(
+        &self,
+        cx: DebugContext<'rt>,
+        controller: Option<&PauseController<'rt>>,
+        kind: StepKind
+    ) -> Result<'rt, ()> {
+        let frame = match self.selected_frame(cx, controller) {
+            Some(frame) => frame,
+            None => {
+                println!("The program is not being run.");
+                return Ok(());
+            }
+        };
+        try!(self.stepper.step(cx, kind, &frame, self.this() as Rc<OnStepComplete<'rt> + 'rt>));
+        controller.unwrap().resume(None);
+        Ok(())
+    }
+
+    fn quit(&self, controller: Option<&PauseController<'rt>>) {
+        self.quit_requested.set(true);
+        if let Some(controller) = controller {
+            controller.resume(Some(CompletionValue::Terminate));
+        }
+    }
+
+    fn add_breakpoint(&self, cx: DebugContext<'rt>, file: String, line: usize) -> Result<'rt, ()> {
+        let spec = BreakpointSpec {
+            url: file.clone(),
+            line: line,
+            ..Default::default()
+        };
+        let on_hit = self.this() as Rc<OnHit<'rt> + 'rt>;
+        let number = try!(self.breakpoints.add(cx, spec, &on_hit, None));
+        if self.breakpoints.locations(cx, number).is_empty() {
+            println!("Breakpoint {} at {}:{} (pending)", number, file, line);
+        } else {
+            println!("Breakpoint {} at {}:{}", number, file, line);
+        }
+        Ok(())
+    }
+
+    fn delete_breakpoint(&self, cx: DebugContext<'rt>, number: u64) -> Result<'rt, ()> {
+        if try!(self.breakpoints.remove(cx, number)) {
+            println!("Deleted breakpoint {}", number);
+        } else {
+            println!("No breakpoint number {}.", number);
+        }
+        Ok(())
+    }
+
+    fn stopped(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>,
+        reason: &str
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        self.selected.set(0);
+        println!("{} at {}", reason, try!(describe_frame(cx, frame)));
+        try!(self.print_source_line(cx, frame));
+        try!(self.print_watches(cx, frame));
+        self.controller().pause(cx, frame)
+    }
+
+    fn print_source_line(&self, cx: DebugContext<'rt>, frame: &Frame<'rt>) -> Result<'rt, ()> {
+        if let Some((line_number, line)) = try!(frame.get_source_line(cx)) {
+            println!("{}\t{}", line_number, line);
+        }
+        Ok(())
+    }
+
+    fn print_watches(&self, cx: DebugContext<'rt>, frame: &Frame<'rt>) -> Result<'rt, ()> {
+        for watch in self.watches.borrow_mut().iter_mut() {
+            let completion = try!(frame.eval(cx, &watch.expression));
+            let current = try!(describe_completion(cx, completion));
+            match watch.last {
+                Some(ref last) if *last != current => {
+                    println!("{}: {} (was {})", watch.expression, current, last);
+                }
+                _ => println!("{}: {}", watch.expression, current)
+            }
+            watch.last = Some(current);
+        }
+        Ok(())
+    }
+}
+
+impl<'rt> OnNewScript<'rt> for Jsdb<'rt> {
+    fn on_new_script(&self, cx: DebugContext<'rt>, script: &Script<'rt>) -> Result<'rt, ()> {
+        self.breakpoints.resolve(cx, script)
+    }
+}
+
+impl<'rt> OnDebuggerStatement<'rt> for Jsdb<'rt> {
+    fn on_debugger_statement(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        self.stopped(cx, frame, "Paused on debugger statement")
+    }
+}
+
+impl<'rt> OnHit<'rt> for Jsdb<'rt> {
+    fn on_hit(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        self.stopped(cx, frame, "Breakpoint hit")
+    }
+}
+
+impl<'rt> OnStepComplete<'rt> for Jsdb<'rt> {
+    fn on_step_complete(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        self.stopped(cx, frame, "Stepped")
+    }
+}
+
+fn describe<'rt>(cx: DebugContext<'rt>, value: Value<'rt>) -> Result<'rt, String> {
+    Ok(match value {
+        Value::Undefined => "undefined".to_string(),
+        Value::Null => "null".to_string(),
+        Value::Boolean(b) => b.to_string(),
+        Value::Int32(i) => i.to_string(),
+        Value::Double(d) => d.to_string(),
+        Value::String(s) => format!("{:?}", s),
+        Value::Object(object) => {
+            if try!(object.get_is_callable(cx)) {
+                format!("[Function {}]", try!(object.get_name(cx)).unwrap_or(String::new()))
+            } else {
+                format!("[object {}]", try!(object.get_class(cx)))
+            }
+        }
+    })
+}
+
+fn describe_completion<'rt>(
+    cx: DebugContext<'rt>,
+    completion: CompletionValue<'rt>
+) -> Result<'rt, String> {
+    match completion {
+        CompletionValue::Return(value) => describe(cx, value),
+        CompletionValue::Throw(value) => describe(cx, value).map(|value| {
+            format!("Uncaught {}", value)
+        }),
+        CompletionValue::Terminate => Ok("<terminated>".to_string())
+    }
+}
+
+fn describe_frame<'rt>(cx: DebugContext<'rt>, frame: &Frame<'rt>) -> Result<'rt, String> {
+    let name = match try!(frame.get_callee(cx)) {
+        Some(callee) => try!(callee.get_name(cx)).unwrap_or("<anonymous>".to_string()),
+        None => "<global>".to_string()
+    };
+    match try!(frame.get_script(cx)) {
+        Some(script) => {
+            let location = try!(script.get_offset_location(cx, try!(frame.get_offset(cx))));
+            Ok(format!("{} ({}:{})", name, try!(script.get_url(cx)), location.line_number))
+        }
+        None => Ok(name)
+    }
+}
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            println!("usage: jsdb file.js");
+            process::exit(1);
+        }
+    };
+    let mut source = String::new();
+    if let Err(error) = File::open(&path).and_then(|mut file| file.read_to_string(&mut source)) {
+        println!("jsdb: {}: {}", path, error);
+        process::exit(1);
+    }
+
+    let runtime = Runtime::new();
+    DebugContext::with(&runtime, |cx| run(cx, &runtime, &path, &source));
+}
+
+fn run<'rt>(cx: DebugContext<'rt>, runtime: &Runtime, path: &str, source: &str) {
+    rooted!(in (cx.as_raw()) let mut global = ptr::null_mut());
+    unsafe {
+        assert!(debugger::new_global_object(cx.as_raw(), global.handle_mut()));
+    }
+    let debugger = Rc::new(Debugger::new(cx));
+    debugger.add_debuggee(cx, global.handle()).ok().expect("failed to add debuggee");
+    let jsdb = Jsdb::new(debugger.clone());
+    debugger.set_on_new_script(cx, Some(jsdb.clone() as Rc<OnNewScript<'rt> + 'rt>)).ok().unwrap();
+    debugger.set_on_debugger_statement(
+        cx,
+        Some(jsdb.clone() as Rc<OnDebuggerStatement<'rt> + 'rt>)
+    ).ok().unwrap();
+
+    println!("Reading {}. Type 'help' for a list of commands.", path);
+    loop {
+        while !jsdb.run_requested.get() {
+            if jsdb.quit_requested.get() {
+                return;
+            }
+            jsdb.prompt(cx, None).ok().expect("debugger error");
+        }
+        jsdb.run_requested.set(false);
+        jsdb.running.set(true);
+        rooted!(in (cx.as_raw()) let mut rval = UndefinedValue());
+        let result = runtime.evaluate_script(global.handle(), source, path, 1, rval.handle_mut());
+        jsdb.running.set(false);
+        jsdb.stepper.cancel(cx).ok().expect("debugger error");
+        if jsdb.quit_requested.get() {
+            return;
+        }
+        match result {
+            Ok(()) => println!("Program finished."),
+            Err(()) => println!("Program terminated with an uncaught exception.")
+        }
+    }
+}
@@ -0,0 +1,384 @@
+use context::DebugContext;
+use exception::Result;
+use frame::Frame;
+use script::OnHit;
+use std::cell::Cell;
+use std::rc::Rc;
+use value::{CompletionValue, ResumptionValue, Value};
+
+pub trait OnLog<'rt> {
+    fn on_log(&self, cx: DebugContext<'rt>, frame: &Frame<'rt>, message: &str) -> Result<'rt, ()>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HitCount {
+    Equal(usize),
+    AtLeast(usize),
+    Multiple(usize)
+}
+
+impl HitCount {
+    pub fn matches(&self, hits: usize) -> bool {
+        match *self {
+            HitCount::Equal(n) => hits == n,
+            HitCount::AtLeast(n) => hits >= n,
+            HitCount::Multiple(n) => n != 0 && hits % n == 0
+        }
+    }
+}
+
+pub struct LogMessage<'rt> {
+    pub template: String,
+    pub on_log: Rc<OnLog<'rt> + 'rt>
+}
+
+impl<'rt> LogMessage<'rt> {
+    pub fn new(template: &str, on_log: Rc<OnLog<'rt> + 'rt>) -> LogMessage<'rt> {
+        LogMessage {
+            template: template.to_string(),
+            on_log: on_log
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct BreakpointOptions<'rt> {
+    pub condition: Option<String>,
+    pub hit_count: Option<HitCount>,
+    pub log_message: Option<LogMessage<'rt>>
+}
+
+pub struct ConditionalOnHit<'rt> {
+    options: BreakpointOptions<'rt>,
+    on_hit: Rc<OnHit<'rt> + 'rt>,
+    hits: Cell<usize>
+}
+
+impl<'rt> ConditionalOnHit<'rt> {
+    pub fn new(
+        options: BreakpointOptions<'rt>,
+        on_hit: Rc<OnHit<'rt> + 'rt>
+    ) -> ConditionalOnHit<'rt> {
+        ConditionalOnHit {
+            options: options,
+            on_hit: on_hit,
+            hits: Cell::new(0)
+        }
+    }
+
+    pub fn get_hits(&self) -> usize {
+        self.hits.get()
+    }
+
+    fn test_condition(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, Condition> {
+        let condition = match self.options.condition {
+            Some(ref condition) => condition,
+            None => return Ok(Condition::True)
+        };
+        match try!(frame.eval(cx, &format!("!!({})", condition))) {
+            CompletionValue::Return(Value::Boolean(true)) => Ok(Condition::True),
+            CompletionValue::Throw(exception) => {
+                describe_exception(cx, &exception).map(Condition::Threw)
+            }
+            _ => Ok(Condition::False)
+        }
+    }
+
+    fn format_message(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>,
+        template: &str
+    ) -> Result<'rt, String> {
+        let mut message = String::new();
+        for segment in parse_template(template) {
+            match segment {
+                Segment::Text(text) => message.push_str(text),
+                Segment::Expression(expression) => {
+                    match try!(frame.eval(cx, &format!("String({})", expression))) {
+                        CompletionValue::Return(Value::String(s)) => message.push_str(&s),
+                        CompletionValue::Throw(exception) => {
+                            let exception = try!(describe_exception(cx, &exception));
+                            message.push_str(&format!("<exception: {}>", exception));
+                        }
+                        _ => message.push_str("<unavailable>")
+                    }
+                }
+            }
+        }
+        Ok(message)
+    }
+}
+
+impl<'rt> OnHit<'rt> for ConditionalOnHit<'rt> {
+    fn on_hit(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        match try!(self.test_condition(cx, frame)) {
+            Condition::True => {}
+            Condition::False => return Ok(None),
+            Condition::Threw(exception) => {
+                // A broken condition is reported through the logpoint, or pauses so it can be
+                // inspected, rather than silently never matching.
+                return match self.options.log_message {
+                    Some(ref log_message) => {
+                        let message = format!("<condition exception: {}>", exception);
+                        try!(log_message.on_log.on_log(cx, frame, &message));
+                        Ok(None)
+                    }
+                    None => self.on_hit.on_hit(cx, frame)
+                };
+            }
+        }
+        let hits = self.hits.get() + 1;
+        self.hits.set(hits);
+        if let Some(hit_count) = self.options.hit_count {
+            if !hit_count.matches(hits) {
+                return Ok(None);
+            }
+        }
+        match self.options.log_message {
+            Some(ref log_message) => {
+                let message = try!(self.format_message(cx, frame, &log_message.template));
+                try!(log_message.on_log.on_log(cx, frame, &message));
+                Ok(None)
+            }
+            None => self.on_hit.on_hit(cx, frame)
+        }
+    }
+}
+
+enum Condition {
+    True,
+    False,
+    Threw(String)
+}
+
+fn describe_exception<'rt>(cx: DebugContext<'rt>, exception: &Value<'rt>) -> Result<'rt, String> {
+    Ok(match *exception {
+        Value::Undefined => "undefined".to_string(),
+        Value::Boolean(b) => b.to_string(),
+        Value::Int32(i) => i.to_string(),
+        Value::Double(d) => d.to_string(),
+        Value::String(ref s) => s.clone(),
+        Value::Object(ref object) => {
+            match try!(object.get_own_property_descriptor(cx, "message")).value {
+                Some(Value::String(message)) => message,
+                _ => try!(object.get_class(cx))
+            }
+        }
+        Value::Null => "null".to_string()
+    })
+}
+
+#[derive(Debug, PartialEq)]
+enum Segment<'a> {
+    Text(&'a str),
+    Expression(&'a str)
+}
+
+// `{{` and `}}` outside an expression stand for literal braces. Braces inside string and
+// template literals within an expression do not count towards its nesting.
+fn parse_template(template: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut depth = 0;
+    let mut open = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    let mut chars = template.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        if depth == 0 {
+            match c {
+                '{' | '}' if chars.peek().map(|&(_, next)| next) == Some(c) => {
+                    segments.push(Segment::Text(&template[start..index + 1]));
+                    chars.next();
+                    start = index + 2;
+                }
+                '{' => {
+                    if start < index {
+                        segments.push(Segment::Text(&template[start..index]));
+                    }
+                    open = index;
+                    depth = 1;
+                }
+                _ => {}
+            }
+            continue;
+        }
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '\'' | '"' | '`' => quote = Some(c),
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    segments.push(Segment::Expression(&template[open + 1..index]));
+                    start = index + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    if depth > 0 {
+        start = open;
+    }
+    if start < template.len() {
+        segments.push(Segment::Text(&template[start..]));
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BreakpointOptions, HitCount, LogMessage, OnLog, Segment, parse_template};
+    use context::DebugContext;
+    use debugger::ScriptQuery;
+    use exception::Result;
+    use frame::Frame;
+    use script::OnHit;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use utils;
+    use value::ResumptionValue;
+
+    #[test]
+    fn hit_count() {
+        assert!(HitCount::Equal(3).matches(3));
+        assert!(!HitCount::Equal(3).matches(4));
+        assert!(HitCount::AtLeast(2).matches(5));
+        assert!(!HitCount::AtLeast(2).matches(1));
+        assert!(HitCount::Multiple(3).matches(6));
+        assert!(!HitCount::Multiple(3).matches(7));
+        assert!(!HitCount::Multiple(0).matches(0));
+    }
+
+    #[test]
+    fn template() {
+        assert_eq!(parse_template("x = {x}, y = {f({a: 1})}!"), vec![
+            Segment::Text("x = "),
+            Segment::Expression("x"),
+            Segment::Text(", y = "),
+            Segment::Expression("f({a: 1})"),
+            Segment::Text("!")
+        ]);
+        assert_eq!(parse_template("unterminated {x"), vec![
+            Segment::Text("unterminated "),
+            Segment::Text("{x")
+        ]);
+        assert_eq!(parse_template("{{x}} = {x}"), vec![
+            Segment::Text("{"),
+            Segment::Text("x}"),
+            Segment::Text(" = "),
+            Segment::Expression("x")
+        ]);
+        assert_eq!(parse_template("{s + \"}\"} {'\\'{'}"), vec![
+            Segment::Expression("s + \"}\""),
+            Segment::Text(" "),
+            Segment::Expression("'\\'{'")
+        ]);
+    }
+
+    struct Recorder(RefCell<Vec<String>>);
+
+    impl<'rt> OnHit<'rt> for Recorder {
+        fn on_hit(
+            &self,
+            _cx: DebugContext<'rt>,
+            _frame: &Frame<'rt>
+        ) -> Result<'rt, ResumptionValue<'rt>> {
+            self.0.borrow_mut().push("hit".to_string());
+            Ok(None)
+        }
+    }
+
+    impl<'rt> OnLog<'rt> for Recorder {
+        fn on_log(
+            &self,
+            _cx: DebugContext<'rt>,
+            _frame: &Frame<'rt>,
+            message: &str
+        ) -> Result<'rt, ()> {
+            self.0.borrow_mut().push(message.to_string());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn conditional_breakpoints() {
+        utils::with_debuggee(|cx, debugger, evaluate| {
+            evaluate("function f(x) {\n    return x;\n}\n");
+            let query = ScriptQuery {
+                url: Some("test".to_string()),
+                line: Some(2)
+            };
+            let (script, offset) = debugger.find_scripts(cx, &query).unwrap().into_iter()
+                .filter_map(|script| {
+                    let offsets = script.get_offsets_for_line(cx, 2).unwrap();
+                    offsets.first().cloned().map(|offset| (script, offset))
+                })
+                .next()
+                .unwrap();
+            let recorder = Rc::new(Recorder(RefCell::new(Vec::new())));
+            let check = |options, expected: &[&str]| {
+                script.add_breakpoint_with_options(cx, offset, options, recorder.clone()).unwrap();
+                evaluate("f(1); f(2); f(3);");
+                script.clear_breakpoints(cx, offset).unwrap();
+                assert_eq!(*recorder.0.borrow(), expected);
+                recorder.0.borrow_mut().clear();
+            };
+
+            check(BreakpointOptions {
+                condition: Some("x > 1".to_string()),
+                hit_count: Some(HitCount::Equal(2)),
+                log_message: None
+            }, &["hit"]);
+            check(BreakpointOptions {
+                condition: Some("x != 2".to_string()),
+                hit_count: None,
+                log_message: Some(LogMessage::new("{{x}} = {x}", recorder.clone()))
+            }, &["{x} = 1", "{x} = 3"]);
+            check(BreakpointOptions {
+                condition: Some("x.y.z".to_string()),
+                hit_count: None,
+                log_message: None
+            }, &["hit", "hit", "hit"]);
+            check(BreakpointOptions {
+                condition: Some("x ==".to_string()),
+                hit_count: None,
+                log_message: None
+            }, &["hit", "hit", "hit"]);
+            check(BreakpointOptions {
+                condition: Some("x == 2 && (function () { throw 'bad'; })()".to_string()),
+                hit_count: None,
+                log_message: Some(LogMessage::new("{x}", recorder.clone()))
+            }, &["<condition exception: bad>"]);
+            check(BreakpointOptions {
+                condition: Some("x == 1".to_string()),
+                hit_count: None,
+                log_message: Some(LogMessage::new(
+                    concat!(
+                        "{(function () { throw 'boom'; })()} ",
+                        "{(function () { throw new Error('bad'); })()}"
+                    ),
+                    recorder.clone()
+                ))
+            }, &["<exception: boom> <exception: bad>"]);
+        });
+    }
+}
@@ -12,58 +12,115 @@ use js::jsapi::{
     MutableHandleValue,
     Value
 };
+use js::jsval;
+use std::any::TypeId;
+use std::cell::Cell;
 use std::os::raw::{c_char, c_uint};
+use std::ptr;
 use std::rc::Rc;
+use trace::{self, Teardown};
 
 const JSCLASS_HAS_PRIVATE: c_uint = 1 << 0;
 
 pub trait Call {
+    fn hook_type() -> TypeId;
     unsafe fn call(&self, cx: *mut JSContext, argc: u32, vp: *mut Value) -> bool;
 }
 
 impl<T: ?Sized + Call> Call for Rc<T> {
+    fn hook_type() -> TypeId {
+        T::hook_type()
+    }
+
     unsafe fn call(&self, cx: *mut JSContext, argc: u32, vp: *mut Value) -> bool {
         (**self).call(cx, argc, vp)
     }
 }
 
-impl FromJSValue for *mut Box<Call> {
-    unsafe fn from_js_value(cx: *mut JSContext, v: HandleValue) -> Result<Self> {
+struct CallSlot {
+    hook_type: TypeId,
+    data: Cell<*mut ()>,
+    call: unsafe fn(*mut (), *mut JSContext, u32, *mut Value) -> bool,
+    drop: unsafe fn(*mut ())
+}
+
+impl CallSlot {
+    fn new<T: Call>(call: T) -> CallSlot {
+        CallSlot {
+            hook_type: T::hook_type(),
+            data: Cell::new(Box::into_raw(Box::new(call)) as *mut ()),
+            call: call_data::<T>,
+            drop: drop_data::<T>
+        }
+    }
+
+    unsafe fn get<T: Call + Clone>(&self) -> T {
+        assert!(self.hook_type == T::hook_type(), "hook read back as a different hook type");
+        let data = self.data.get();
+        assert!(!data.is_null(), "hook outlived its debugging scope");
+        (*(data as *const T)).clone()
+    }
+}
+
+impl Teardown for CallSlot {
+    fn teardown(&self) {
+        let data = self.data.replace(ptr::null_mut());
+        if !data.is_null() {
+            unsafe { (self.drop)(data) };
+        }
+    }
+}
+
+impl Drop for CallSlot {
+    fn drop(&mut self) {
+        self.teardown();
+    }
+}
+
+unsafe fn call_data<T: Call>(data: *mut (), cx: *mut JSContext, argc: u32, vp: *mut Value) -> bool {
+    (*(data as *const T)).call(cx, argc, vp)
+}
+
+unsafe fn drop_data<T: Call>(data: *mut ()) {
+    drop(Box::from_raw(data as *mut T));
+}
+
+impl FromJSValue for Rc<CallSlot> {
+    unsafe fn from_js_value<'rt>(cx: *mut JSContext, v: HandleValue) -> Result<'rt, Self> {
         rooted!(in (cx) let obj = v.to_object());
         assert!(jsapi::JS_GetClass(obj.get()) == &CLASS);
-        Ok(jsapi::JS_GetPrivate(obj.get()) as *mut Box<Call>)
+        Ok((*(jsapi::JS_GetPrivate(obj.get()) as *const Rc<CallSlot>)).clone())
     }
 }
 
-impl ToJSValue for *mut Box<Call> {
+impl ToJSValue for Rc<CallSlot> {
     unsafe fn to_js_value(&self, cx: *mut JSContext, rval: MutableHandleValue) -> bool {
         rooted!(in (cx) let obj = try_jsapi!(jsapi::JS_NewObject(cx, &CLASS)));
-        jsapi::JS_SetPrivate(obj.get(), *self as _);
+        trace::on_teardown(cx, Rc::downgrade(self) as _);
+        jsapi::JS_SetPrivate(obj.get(), Box::into_raw(Box::new(self.clone())) as _);
         obj.to_js_value(cx, rval)
     }
 }
 
-impl<T: 'static + Call + Clone> FromJSValue for T {
-    unsafe fn from_js_value(cx: *mut JSContext, v: HandleValue) -> Result<Self> {
-        <*mut Box<Call>>::from_js_value(cx, v).map(|call| {
-            (**(call as *mut Box<T>)).clone()
-        })
+impl<T: Call + Clone> FromJSValue for T {
+    unsafe fn from_js_value<'rt>(cx: *mut JSContext, v: HandleValue) -> Result<'rt, Self> {
+        <Rc<CallSlot>>::from_js_value(cx, v).map(|slot| slot.get())
     }
 }
 
-impl<T: 'static + Call + Clone> ToJSValue for T {
+impl<T: Call + Clone> ToJSValue for T {
     unsafe fn to_js_value(&self, cx: *mut JSContext, rval: MutableHandleValue) -> bool {
-        Box::into_raw(Box::new(Box::new(self.clone()) as Box<Call>)).to_js_value(cx, rval)
+        Rc::new(CallSlot::new(self.clone())).to_js_value(cx, rval)
     }
 }
 
-impl<T: 'static + Call + Clone> FromJSValue for Option<T> {
-    unsafe fn from_js_value(cx: *mut JSContext, v: HandleValue) -> Result<Self> {
+impl<T: Call + Clone> FromJSValue for Option<T> {
+    unsafe fn from_js_value<'rt>(cx: *mut JSContext, v: HandleValue) -> Result<'rt, Self> {
         UndefinedOr::<T>::from_js_value(cx, v).map(|call| call.into_option())
     }
 }
 
-impl<T: 'static + Call + Clone> ToJSValue for Option<T> {
+impl<T: Call + Clone> ToJSValue for Option<T> {
     unsafe fn to_js_value(&self, cx: *mut JSContext, rval: MutableHandleValue) -> bool {
         match self {
             &Some(ref v) => v.to_js_value(cx, rval),
@@ -97,9 +154,15 @@ static CLASS_OPS: JSClassOps = JSClassOps {
 unsafe extern "C" fn call(cx: *mut JSContext, argc: u32, vp: *mut Value) -> bool {
     let args = CallArgs::from_vp(vp, argc);
     rooted!(in (cx) let callee = args.callee());
-    (&*(jsapi::JS_GetPrivate(callee.get()) as *mut Box<Call>)).call(cx, argc, vp)
+    let slot = (*(jsapi::JS_GetPrivate(callee.get()) as *const Rc<CallSlot>)).clone();
+    let data = slot.data.get();
+    if data.is_null() {
+        args.rval().set(jsval::UndefinedValue());
+        return true;
+    }
+    (slot.call)(data, cx, argc, vp)
 }
 
 unsafe extern "C" fn finalize(_fop: *mut JSFreeOp, obj: *mut JSObject) {
-    let _call = Box::from_raw(jsapi::JS_GetPrivate(obj) as *mut Box<Call>);
+    let _slot = Box::from_raw(jsapi::JS_GetPrivate(obj) as *mut Rc<CallSlot>);
 }
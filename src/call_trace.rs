@@ -0,0 +1,221 @@
+use context::DebugContext;
+use debugger::{Debugger, OnEnterFrame};
+use exception::Result;
+use frame::{Frame, OnPop};
+use rooted::Rooted;
+use serde_json;
+use serde_json::Value as Json;
+use std::cell::{Cell, RefCell};
+use std::io;
+use std::io::Write;
+use std::rc::{Rc, Weak};
+use std::time::Instant;
+use value::{CompletionValue, ResumptionValue};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    Begin,
+    End
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceEvent {
+    pub phase: Phase,
+    pub name: String,
+    pub timestamp: u64,
+    pub url: Option<String>,
+    pub line: Option<usize>,
+    pub column: Option<usize>
+}
+
+impl TraceEvent {
+    pub fn to_json(&self) -> Json {
+        let mut event = json!({
+            "name": self.name,
+            "cat": "js",
+            "ph": match self.phase {
+                Phase::Begin => "B",
+                Phase::End => "E"
+            },
+            "ts": self.timestamp,
+            "pid": 1,
+            "tid": 1
+        });
+        if self.phase == Phase::Begin {
+            event["args"] = json!({
+                "url": self.url,
+                "line": self.line,
+                "column": self.column
+            });
+        }
+        event
+    }
+}
+
+pub struct CallTracer<'rt> {
+    debugger: Rc<Debugger<'rt>>,
+    this: RefCell<Weak<CallTracer<'rt>>>,
+    start: Instant,
+    tracing: Cell<bool>,
+    frames: RefCell<Vec<(Frame<'rt>, String)>>,
+    events: RefCell<Vec<TraceEvent>>
+}
+
+impl<'rt> CallTracer<'rt> {
+    pub fn new(debugger: Rc<Debugger<'rt>>) -> Rc<CallTracer<'rt>> {
+        let tracer = Rc::new(CallTracer {
+            debugger: debugger,
+            this: RefCell::new(Weak::new()),
+            start: Instant::now(),
+            tracing: Cell::new(false),
+            frames: RefCell::new(Vec::new()),
+            events: RefCell::new(Vec::new())
+        });
+        *tracer.this.borrow_mut() = Rc::downgrade(&tracer);
+        tracer
+    }
+
+    fn this(&self) -> Rc<CallTracer<'rt>> {
+        self.this.borrow().upgrade().unwrap()
+    }
+
+    pub fn is_tracing(&self) -> bool {
+        self.tracing.get()
+    }
+
+    pub fn start(&self, cx: DebugContext<'rt>) -> Result<'rt, ()> {
+        if self.tracing.get() {
+            return Ok(());
+        }
+        try!(self.debugger.add_on_enter_frame(cx, self.this() as Rc<OnEnterFrame<'rt> + 'rt>));
+        self.tracing.set(true);
+        Ok(())
+    }
+
+    pub fn stop(&self, cx: DebugContext<'rt>) -> Result<'rt, ()> {
+        if !self.tracing.get() {
+            return Ok(());
+        }
+        self.tracing.set(false);
+        let on_enter_frame = self.this() as Rc<OnEnterFrame<'rt> + 'rt>;
+        try!(self.debugger.remove_on_enter_frame(cx, &on_enter_frame));
+        let on_pop = self.this() as Rc<OnPop<'rt> + 'rt>;
+        let frames: Vec<(Frame<'rt>, String)> = self.frames.borrow_mut().drain(..).collect();
+        for (frame, name) in frames.into_iter().rev() {
+            try!(self.debugger.remove_on_pop(cx, &frame, &on_pop));
+            self.record(Phase::End, name, None);
+        }
+        Ok(())
+    }
+
+    pub fn get_events(&self) -> Vec<TraceEvent> {
+        self.events.borrow().clone()
+    }
+
+    pub fn clear(&self) {
+        self.events.borrow_mut().clear();
+    }
+
+    pub fn to_json(&self) -> Json {
+        let events: Vec<Json> = self.events.borrow().iter().map(|event| event.to_json()).collect();
+        json!({
+            "traceEvents": events,
+            "displayTimeUnit": "ms"
+        })
+    }
+
+    pub fn write_json<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        try!(serde_json::to_writer(&mut *writer, &self.to_json()));
+        writer.write_all(b"\n")
+    }
+
+    fn record(&self, phase: Phase, name: String, location: Option<(String, usize, usize)>) {
+        let elapsed = self.start.elapsed();
+        let timestamp = elapsed.as_secs() * 1000000 + elapsed.subsec_nanos() as u64 / 1000;
+        let (url, line, column) = match location {
+            Some((url, line, column)) => (Some(url), Some(line), Some(column)),
+            None => (None, None, None)
+        };
+        self.events.borrow_mut().push(TraceEvent {
+            phase: phase,
+            name: name,
+            timestamp: timestamp,
+            url: url,
+            line: line,
+            column: column
+        });
+    }
+}
+
+impl<'rt> OnEnterFrame<'rt> for CallTracer<'rt> {
+    fn on_enter_frame(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        if !self.tracing.get() {
+            return Ok(None);
+        }
+        let name = try!(frame.get_function_name(cx));
+        let location = match try!(frame.get_script(cx)) {
+            Some(script) => {
+                let offset = try!(frame.get_offset(cx));
+                let location = try!(script.get_offset_location(cx, offset));
+                Some((try!(script.get_url(cx)), location.line_number, location.column_number))
+            }
+            None => None
+        };
+        try!(self.debugger.add_on_pop(cx, frame, self.this() as Rc<OnPop<'rt> + 'rt>));
+        self.frames.borrow_mut().push((Frame::new(cx, frame.get()), name.clone()));
+        self.record(Phase::Begin, name, location);
+        Ok(None)
+    }
+}
+
+impl<'rt> OnPop<'rt> for CallTracer<'rt> {
+    fn on_pop(
+        &self,
+        _cx: DebugContext<'rt>,
+        frame: &Frame<'rt>,
+        _value: &CompletionValue<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        let position = self.frames.borrow().iter().rposition(|entry| entry.0.get() == frame.get());
+        if let Some(position) = position {
+            let (_, name) = self.frames.borrow_mut().remove(position);
+            self.record(Phase::End, name, None);
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Phase, TraceEvent};
+
+    #[test]
+    fn trace_event() {
+        let event = TraceEvent {
+            phase: Phase::Begin,
+            name: "f".to_string(),
+            timestamp: 42,
+            url: Some("a.js".to_string()),
+            line: Some(3),
+            column: Some(4)
+        };
+        let json = event.to_json();
+        assert_eq!(json["ph"], "B");
+        assert_eq!(json["ts"], 42);
+        assert_eq!(json["args"]["url"], "a.js");
+        assert_eq!(json["args"]["line"], 3);
+        let event = TraceEvent {
+            phase: Phase::End,
+            url: None,
+            line: None,
+            column: None,
+            ..event
+        };
+        let json = event.to_json();
+        assert_eq!(json["ph"], "E");
+        assert!(json.get("args").is_none());
+    }
+}
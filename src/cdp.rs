@@ -0,0 +1,842 @@
+use context::DebugContext;
+use debugger::{Debugger, OnDebuggerStatement, OnNewScript, ScriptQuery};
+use exception::{Exception, Result};
+use frame::Frame;
+use pause::PauseController;
+use registry::{Handle, HandleRegistry, Lifetime};
+use rooted::Rooted;
+use script::{OnHit, Script};
+use serde_json;
+use serde_json::Value as Json;
+use session::{
+    BreakpointSpec,
+    Breakpoints,
+    Connection,
+    Dispatcher,
+    OnPauseRequest,
+    PauseRequest
+};
+use source::{Source, SourceText};
+use std::cell::{Cell, RefCell};
+use std::io;
+use std::net::{TcpListener, TcpStream};
+use std::rc::{Rc, Weak};
+use std::result;
+use stepper::{OnStepComplete, StepKind, Stepper};
+use tungstenite;
+use tungstenite::{Message, WebSocket};
+use value::{CompletionValue, ResumptionValue, Value};
+
+enum Error<'rt> {
+    Exception(Exception<'rt>),
+    InvalidParams(&'static str),
+    NotFound(&'static str),
+    NotPaused,
+    MethodNotFound(String)
+}
+
+impl<'rt> From<Exception<'rt>> for Error<'rt> {
+    fn from(exception: Exception<'rt>) -> Error<'rt> {
+        Error::Exception(exception)
+    }
+}
+
+impl<'rt> Error<'rt> {
+    fn to_json(&self) -> Json {
+        let (code, message) = match *self {
+            Error::Exception(_) => (-32000, "Uncaught exception in debugger".to_string()),
+            Error::InvalidParams(name) => (-32602, format!("Invalid parameter: {}", name)),
+            Error::NotFound(what) => (-32000, format!("No {} with given id", what)),
+            Error::NotPaused => (-32000, "Can only perform operation while paused".to_string()),
+            Error::MethodNotFound(ref method) => {
+                (-32601, format!("'{}' wasn't found", method))
+            }
+        };
+        json!({ "code": code, "message": message })
+    }
+}
+
+type CommandResult<'rt> = result::Result<Json, Error<'rt>>;
+
+struct SourceEntry<'rt> {
+    id: String,
+    url: String,
+    source: Source<'rt>
+}
+
+pub struct Server<'rt>(Rc<Session<'rt>>);
+
+impl<'rt> Server<'rt> {
+    pub fn accept(
+        cx: DebugContext<'rt>,
+        debugger: Rc<Debugger<'rt>>,
+        listener: &TcpListener
+    ) -> io::Result<Server<'rt>> {
+        let (stream, _) = try!(listener.accept());
+        let socket = match tungstenite::accept(stream) {
+            Ok(socket) => socket,
+            Err(_) => {
+                return Err(io::Error::new(io::ErrorKind::Other, "WebSocket handshake failed"));
+            }
+        };
+        let session = Session::new(debugger, socket);
+        try!(session.install(cx).map_err(|_| {
+            io::Error::new(io::ErrorKind::Other, "Failed to install debugger hooks")
+        }));
+        Ok(Server(session))
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.0.connected.get()
+    }
+
+    pub fn poll(&self, cx: DebugContext<'rt>) -> Result<'rt, bool> {
+        while self.0.connected.get() {
+            if !try!(self.0.process_message(cx, None, false)) {
+                break;
+            }
+        }
+        Ok(self.0.connected.get())
+    }
+}
+
+struct Session<'rt> {
+    this: RefCell<Weak<Session<'rt>>>,
+    debugger: Rc<Debugger<'rt>>,
+    socket: RefCell<WebSocket<TcpStream>>,
+    controller: RefCell<Option<Rc<PauseController<'rt>>>>,
+    pause_request: RefCell<Option<Rc<PauseRequest<'rt>>>>,
+    stepper: Stepper<'rt>,
+    sources: RefCell<Vec<SourceEntry<'rt>>>,
+    breakpoints: Breakpoints<'rt>,
+    handles: HandleRegistry<'rt>,
+    next_id: Cell<usize>,
+    enabled: Cell<bool>,
+    connected: Cell<bool>
+}
+
+impl<'rt> Session<'rt> {
+    fn new(debugger: Rc<Debugger<'rt>>, socket: WebSocket<TcpStream>) -> Rc<Session<'rt>> {
+        let session = Rc::new(Session {
+            this: RefCell::new(Weak::new()),
+            debugger: debugger.clone(),
+            socket: RefCell::new(socket),
+            controller: RefCell::new(None),
+            pause_request: RefCell::new(None),
+            stepper: Stepper::new(debugger.clone()),
+            sources: RefCell::new(Vec::new()),
+            breakpoints: Breakpoints::new(debugger.clone()),
+            handles: HandleRegistry::new(),
+            next_id: Cell::new(1),
+            enabled: Cell::new(false),
+            connected: Cell::new(true)
+        });
+        *session.this.borrow_mut() = Rc::downgrade(&session);
+        let dispatcher = Rc::new(Dispatcher(Rc::downgrade(&session)));
+        *session.controller.borrow_mut() = Some(Rc::new(PauseController::new(dispatcher)));
+        let target = Rc::downgrade(&session) as Weak<OnPauseRequest<'rt> + 'rt>;
+        *session.pause_request.borrow_mut() = Some(PauseRequest::new(debugger, target));
+        session
+    }
+
+    fn this(&self) -> Rc<Session<'rt>> {
+        self.this.borrow().upgrade().unwrap()
+    }
+
+    fn controller(&self) -> Rc<PauseController<'rt>> {
+        self.controller.borrow().as_ref().unwrap().clone()
+    }
+
+    fn pause_request(&self) -> Rc<PauseRequest<'rt>> {
+        self.pause_request.borrow().as_ref().unwrap().clone()
+    }
+
+    fn install(&self, cx: DebugContext<'rt>) -> Result<'rt, ()> {
+        try!(self.debugger.set_on_new_script(cx, Some(self.this() as Rc<OnNewScript<'rt> + 'rt>)));
+        try!(self.debugger.set_on_debugger_statement(
+            cx,
+            Some(self.this() as Rc<OnDebuggerStatement<'rt> + 'rt>)
+        ));
+        Ok(())
+    }
+
+    fn generate_id(&self) -> String {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        id.to_string()
+    }
+
+    fn send(&self, message: Json) {
+        if let Err(_) = self.socket.borrow_mut().write_message(Message::Text(message.to_string())) {
+            self.connected.set(false);
+        }
+    }
+
+    fn send_event(&self, method: &str, params: Json) {
+        self.send(json!({ "method": method, "params": params }));
+    }
+
+    fn process_message(
+        &self,
+        cx: DebugContext<'rt>,
+        controller: Option<&PauseController<'rt>>,
+        blocking: bool
+    ) -> Result<'rt, bool> {
+        if let Err(_) = self.socket.borrow_mut().get_mut().set_nonblocking(!blocking) {
+            self.disconnect(controller);
+            return Ok(false);
+        }
+        let message = self.socket.borrow_mut().read_message();
+        let text = match message {
+            Ok(Message::Text(text)) => text,
+            Ok(Message::Close(_)) => {
+                self.disconnect(controller);
+                return Ok(false);
+            }
+            Ok(_) => return Ok(true),
+            Err(tungstenite::Error::Io(ref error)) if error.kind() == io::ErrorKind::WouldBlock => {
+                return Ok(false);
+            }
+            Err(_) => {
+                self.disconnect(controller);
+                return Ok(false);
+            }
+        };
+        let request: Json = match serde_json::from_str(&text) {
+            Ok(request) => request,
+            Err(_) => return Ok(true)
+        };
+        let id = request["id"].clone();
+        let method = request["method"].as_str().unwrap_or("").to_string();
+        let response = match self.dispatch(cx, controller, &method, &request["params"]) {
+            Ok(result) => json!({ "id": id, "result": result }),
+            Err(error) => json!({ "id": id, "error": error.to_json() })
+        };
+        self.send(response);
+        if method == "Debugger.enable" {
+            try!(self.report_sources(cx));
+        }
+        Ok(true)
+    }
+
+    fn disconnect(&self, controller: Option<&PauseController<'rt>>) {
+        self.connected.set(false);
+        if let Some(controller) = controller {
+            controller.resume(None);
+        }
+    }
+
+    fn dispatch(
+        &self,
+        cx: DebugContext<'rt>,
+        controller: Option<&PauseController<'rt>>,
+        method: &str,
+        params: &Json
+    ) -> CommandResult<'rt> {
+        match method {
+            "Debugger.enable" => {
+                self.enabled.set(true);
+                Ok(json!({ "debuggerId": "debugger" }))
+            }
+            "Debugger.disable" => {
+                self.enabled.set(false);
+                if let Some(controller) = controller {
+                    controller.resume(None);
+                }
+                Ok(json!({}))
+            }
+            "Debugger.setBreakpointByUrl" => self.set_breakpoint_by_url(cx, params),
+            "Debugger.removeBreakpoint" => self.remove_breakpoint(cx, params),
+            "Debugger.getScriptSource" => self.get_script_source(cx, params),
+            "Debugger.pause" => {
+                try!(self.pause_request().request(cx));
+                Ok(json!({}))
+            }
+            "Debugger.resume" => {
+                match controller {
+                    Some(controller) => {
+                        controller.resume(None);
+                        Ok(json!({}))
+                    }
+                    None => Err(Error::NotPaused)
+                }
+            }
+            "Debugger.stepOver" => self.step(cx, controller, StepKind::Over),
+            "Debugger.stepInto" => self.step(cx, controller, StepKind::In),
+            "Debugger.stepOut" => self.step(cx, controller, StepKind::Out),
+            "Debugger.evaluateOnCallFrame" => self.evaluate_on_call_frame(cx, params),
+            "Runtime.enable" | "Runtime.runIfWaitingForDebugger" => Ok(json!({})),
+            "Runtime.evaluate" => self.evaluate(cx, params),
+            "Runtime.getProperties" => self.get_properties(cx, params),
+            "Runtime.releaseObject" => self.release_object(params),
+            "Runtime.releaseObjectGroup" => Ok(json!({})),
+            _ => Err(Error::MethodNotFound(method.to_string()))
+        }
+    }
+
+    fn set_breakpoint_by_url(&self, cx: DebugContext<'rt>, params: &Json) -> CommandResult<'rt> {
+        let url = try!(params["url"].as_str().ok_or(Error::InvalidParams("url")));
+        let line = try!(params["lineNumber"].as_u64().ok_or(Error::InvalidParams("lineNumber")));
+        let spec = BreakpointSpec {
+            url: url.to_string(),
+            line: line as usize + 1,
+            condition: params["condition"].as_str().and_then(|condition| {
+                if condition.is_empty() { None } else { Some(condition.to_string()) }
+            }),
+            ..Default::default()
+        };
+        let id = try!(self.breakpoints.add(cx, spec, &(self.this() as Rc<OnHit<'rt> + 'rt>), None));
+        let mut locations = Vec::new();
+        for location in self.breakpoints.locations(cx, id) {
+            let source = try!(location.script.get_source(cx));
+            locations.push(json!({
+                "scriptId": try!(self.register_source(cx, source, url)),
+                "lineNumber": location.line - 1,
+                "columnNumber": location.column
+            }));
+        }
+        Ok(json!({ "breakpointId": breakpoint_id(id, url, line as usize), "locations": locations }))
+    }
+
+    fn remove_breakpoint(&self, cx: DebugContext<'rt>, params: &Json) -> CommandResult<'rt> {
+        let id = try!(params["breakpointId"].as_str().ok_or(Error::InvalidParams("breakpointId")));
+        let id = try!(id.split(':').next().and_then(|id| id.parse::<u64>().ok()).ok_or(
+            Error::NotFound("breakpoint")
+        ));
+        if !try!(self.breakpoints.remove(cx, id)) {
+            return Err(Error::NotFound("breakpoint"));
+        }
+        Ok(json!({}))
+    }
+
+    fn get_script_source(&self, cx: DebugContext<'rt>, params: &Json) -> CommandResult<'rt> {
+        let id = try!(params["scriptId"].as_str().ok_or(Error::InvalidParams("scriptId")));
+        let source = {
+            let sources = self.sources.borrow();
+            match sources.iter().find(|entry| entry.id == id) {
+                Some(entry) => Source::new(cx, entry.source.get()),
+                None => return Err(Error::NotFound("script"))
+            }
+        };
+        Ok(json!({ "scriptSource": try!(source.get_text(cx)) }))
+    }
+
+    fn step(
+        &self,
+        cx: DebugContext<'rt>,
+        controller: Option<&PauseController<'rt>>,
+        kind: StepKind
+    ) -> CommandResult<'rt> {
+        let controller = try!(controller.ok_or(Error::NotPaused));
+        let frame = try!(controller.get_paused_frame(cx).ok_or(Error::NotPaused));
+        try!(self.stepper.step(cx, kind, &frame, self.this() as Rc<OnStepComplete<'rt> + 'rt>));
+        controller.resume(None);
+        Ok(json!({}))
+    }
+
+    fn evaluate_on_call_frame(&self, cx: DebugContext<'rt>, params: &Json) -> CommandResult<'rt> {
+        let id = try!(params["callFrameId"].as_str().ok_or(Error::InvalidParams("callFrameId")));
+        let expression = try!(params["expression"].as_str().ok_or(
+            Error::InvalidParams("expression")
+        ));
+        let frame = match self.get_handle(cx, id) {
+            Some(Handle::Frame(frame)) => frame,
+            _ => return Err(Error::NotFound("call frame"))
+        };
+        let completion = try!(frame.eval(cx, expression));
+        self.completion_to_json(cx, completion)
+    }
+
+    fn evaluate(&self, cx: DebugContext<'rt>, params: &Json) -> CommandResult<'rt> {
+        let expression = try!(params["expression"].as_str().ok_or(
+            Error::InvalidParams("expression")
+        ));
+        let global = match try!(self.debugger.get_debuggees(cx)).into_iter().next() {
+            Some(global) => global,
+            None => return Err(Error::NotFound("execution context"))
+        };
+        let completion = try!(global.execute_in_global(cx, expression));
+        self.completion_to_json(cx, completion)
+    }
+
+    fn completion_to_json(
+        &self,
+        cx: DebugContext<'rt>,
+        completion: CompletionValue<'rt>
+    ) -> CommandResult<'rt> {
+        match completion {
+            CompletionValue::Return(value) => Ok(json!({
+                "result": try!(self.remote_object(cx, value))
+            })),
+            CompletionValue::Throw(value) => {
+                let exception = try!(self.remote_object(cx, value));
+                Ok(json!({
+                    "result": exception.clone(),
+                    "exceptionDetails": {
+                        "exceptionId": 1,
+                        "text": "Uncaught",
+                        "lineNumber": 0,
+                        "columnNumber": 0,
+                        "exception": exception
+                    }
+                }))
+            }
+            CompletionValue::Terminate => Ok(json!({
+                "result": { "type": "undefined" }
+            }))
+        }
+    }
+
+    fn get_properties(&self, cx: DebugContext<'rt>, params: &Json) -> CommandResult<'rt> {
+        let id = try!(params["objectId"].as_str().ok_or(Error::InvalidParams("objectId")));
+        let mut result = Vec::new();
+        match self.get_handle(cx, id) {
+            Some(Handle::Object(object)) => {
+                for name in try!(object.get_own_property_names(cx)) {
+                    let descriptor = try!(object.get_own_property_descriptor(cx, &name));
+                    let mut property = json!({
+                        "name": name,
+                        "configurable": descriptor.configurable.unwrap_or(false),
+                        "enumerable": descriptor.enumerable.unwrap_or(false),
+                        "isOwn": true
+                    });
+                    if let Some(writable) = descriptor.writable {
+                        property["writable"] = json!(writable);
+                    }
+                    if let Some(value) = descriptor.value {
+                        property["value"] = try!(self.remote_object(cx, value));
+                    }
+                    if let Some(get) = descriptor.get {
+                        property["get"] = try!(self.remote_object(cx, get));
+                    }
+                    if let Some(set) = descriptor.set {
+                        property["set"] = try!(self.remote_object(cx, set));
+                    }
+                    result.push(property);
+                }
+            }
+            Some(Handle::Environment(environment)) => {
+                for name in try!(environment.names(cx)) {
+                    let value = match try!(environment.get_variable(cx, &name)) {
+                        Some(value) => try!(self.remote_object(cx, value)),
+                        None => json!({ "type": "undefined", "description": "<optimized out>" })
+                    };
+                    result.push(json!({
+                        "name": name,
+                        "value": value,
+                        "configurable": false,
+                        "enumerable": true,
+                        "writable": true,
+                        "isOwn": true
+                    }));
+                }
+            }
+            _ => return Err(Error::NotFound("object"))
+        }
+        Ok(json!({ "result": result }))
+    }
+
+    fn register_handle(&self, cx: DebugContext<'rt>, handle: Handle<'rt>) -> Result<'rt, String> {
+        self.handles.register(cx, handle, Lifetime::Pause).map(|id| id.to_string())
+    }
+
+    fn get_handle(&self, cx: DebugContext<'rt>, id: &str) -> Option<Handle<'rt>> {
+        match id.parse::<u64>() {
+            Ok(id) => self.handles.get(cx, id),
+            Err(_) => None
+        }
+    }
+
+    fn release_object(&self, params: &Json) -> CommandResult<'rt> {
+        let id = try!(params["objectId"].as_str().ok_or(Error::InvalidParams("objectId")));
+        if let Ok(id) = id.parse::<u64>() {
+            self.handles.release(id);
+        }
+        Ok(json!({}))
+    }
+
+    fn remote_object(&self, cx: DebugContext<'rt>, value: Value<'rt>) -> CommandResult<'rt> {
+        Ok(match value {
+            Value::Undefined => json!({ "type": "undefined" }),
+            Value::Boolean(b) => json!({ "type": "boolean", "value": b }),
+            Value::Int32(i) => json!({
+                "type": "number",
+                "value": i,
+                "description": i.to_string()
+            }),
+            Value::Double(d) => {
+                let mut result = json!({ "type": "number", "description": d.to_string() });
+                if d.is_finite() {
+                    result["value"] = json!(d);
+                } else {
+                    result["unserializableValue"] = json!(d.to_string());
+                }
+                result
+            }
+            Value::String(s) => json!({ "type": "string", "value": s }),
+            Value::Null => json!({ "type": "object", "subtype": "null", "value": null }),
+            Value::Object(object) => {
+                let class_name = try!(object.get_class(cx));
+                if try!(object.get_is_callable(cx)) {
+                    let name = try!(object.get_name(cx)).unwrap_or(String::new());
+                    json!({
+                        "type": "function",
+                        "className": class_name,
+                        "description": format!("function {}()", name),
+                        "objectId": try!(self.register_handle(cx, Handle::Object(object)))
+                    })
+                } else {
+                    let mut result = json!({
+                        "type": "object",
+                        "className": class_name,
+                        "description": class_name
+                    });
+                    if class_name == "Array" {
+                        result["subtype"] = json!("array");
+                    }
+                    let id = try!(self.register_handle(cx, Handle::Object(object)));
+                    result["objectId"] = json!(id);
+                    result
+                }
+            }
+        })
+    }
+
+    fn register_source(
+        &self,
+        cx: DebugContext<'rt>,
+        source: Source<'rt>,
+        url: &str
+    ) -> Result<'rt, String> {
+        if let Some(entry) = self.sources.borrow().iter().find(|entry| {
+            entry.source.get() == source.get()
+        }) {
+            return Ok(entry.id.clone());
+        }
+        let id = self.generate_id();
+        if self.enabled.get() {
+            let text = try!(source.get_source_text(cx));
+            self.send_script_parsed(&id, url, &text);
+        }
+        self.sources.borrow_mut().push(SourceEntry {
+            id: id.clone(),
+            url: url.to_string(),
+            source: source
+        });
+        Ok(id)
+    }
+
+    fn send_script_parsed(&self, id: &str, url: &str, text: &SourceText) {
+        let end_line = text.line_count() - 1;
+        let end_column = text.line(text.line_count()).map(|line| {
+            line.encode_utf16().count()
+        }).unwrap_or(0);
+        self.send_event("Debugger.scriptParsed", json!({
+            "scriptId": id,
+            "url": url,
+            "startLine": 0,
+            "startColumn": 0,
+            "endLine": end_line,
+            "endColumn": end_column,
+            "executionContextId": 1,
+            "hash": ""
+        }));
+    }
+
+    fn report_sources(&self, cx: DebugContext<'rt>) -> Result<'rt, ()> {
+        let mut reported = Vec::new();
+        for entry in self.sources.borrow().iter() {
+            let source = Source::new(cx, entry.source.get());
+            reported.push((entry.id.clone(), entry.url.clone(), source));
+        }
+        for (id, url, source) in reported {
+            let text = try!(source.get_source_text(cx));
+            self.send_script_parsed(&id, &url, &text);
+        }
+        for script in try!(self.debugger.find_scripts(cx, &ScriptQuery::default())) {
+            let url = try!(script.get_url(cx));
+            try!(self.register_source(cx, try!(script.get_source(cx)), &url));
+        }
+        Ok(())
+    }
+
+    fn call_frames(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> result::Result<Vec<Json>, Error<'rt>> {
+        let mut result = Vec::new();
+        let mut current = Some(Frame::new(cx, frame.get()));
+        while let Some(frame) = current {
+            let script = match try!(frame.get_script(cx)) {
+                Some(script) => script,
+                None => {
+                    current = try!(frame.get_older(cx));
+                    continue;
+                }
+            };
+            let url = try!(script.get_url(cx));
+            let location = try!(script.get_offset_location(cx, try!(frame.get_offset(cx))));
+            let script_id = try!(self.register_source(cx, try!(script.get_source(cx)), &url));
+            let function_name = match try!(frame.get_callee(cx)) {
+                Some(callee) => try!(callee.get_name(cx)).unwrap_or(String::new()),
+                None => String::new()
+            };
+            let this = try!(self.remote_object(cx, try!(frame.get_this(cx))));
+            let scope_chain = try!(self.scope_chain(cx, &frame));
+            let older = try!(frame.get_older(cx));
+            result.push(json!({
+                "callFrameId": try!(self.register_handle(cx, Handle::Frame(frame))),
+                "functionName": function_name,
+                "location": {
+                    "scriptId": script_id,
+                    "lineNumber": location.line_number - 1,
+                    "columnNumber": location.column_number
+                },
+                "url": url,
+                "scopeChain": scope_chain,
+                "this": this
+            }));
+            current = older;
+        }
+        Ok(result)
+    }
+
+    fn scope_chain(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> result::Result<Vec<Json>, Error<'rt>> {
+        let mut result = Vec::new();
+        let mut current = try!(frame.get_environment(cx));
+        while let Some(environment) = current {
+            let parent = try!(environment.get_parent(cx));
+            let scope_type = if result.is_empty() {
+                "local"
+            } else if parent.is_none() {
+                "global"
+            } else {
+                "closure"
+            };
+            result.push(json!({
+                "type": scope_type,
+                "object": {
+                    "type": "object",
+                    "className": "Object",
+                    "description": "Scope",
+                    "objectId": try!(self.register_handle(cx, Handle::Environment(environment)))
+                }
+            }));
+            current = parent;
+        }
+        Ok(result)
+    }
+
+    fn paused(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>,
+        reason: &str,
+        hit_breakpoints: Vec<String>
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        if !self.enabled.get() || !self.connected.get() {
+            return Ok(None);
+        }
+        self.handles.enter_pause();
+        let call_frames = match self.call_frames(cx, frame) {
+            Ok(call_frames) => call_frames,
+            Err(Error::Exception(exception)) => return Err(exception),
+            Err(_) => Vec::new()
+        };
+        self.send_event("Debugger.paused", json!({
+            "callFrames": call_frames,
+            "reason": reason,
+            "hitBreakpoints": hit_breakpoints
+        }));
+        let result = self.controller().pause(cx, frame);
+        self.handles.leave_pause();
+        if self.connected.get() {
+            self.send_event("Debugger.resumed", json!({}));
+        }
+        result
+    }
+}
+
+fn breakpoint_id(id: u64, url: &str, line: usize) -> String {
+    format!("{}:{}:{}", id, line, url)
+}
+
+impl<'rt> OnNewScript<'rt> for Session<'rt> {
+    fn on_new_script(&self, cx: DebugContext<'rt>, script: &Script<'rt>) -> Result<'rt, ()> {
+        let url = try!(script.get_url(cx));
+        try!(self.register_source(cx, try!(script.get_source(cx)), &url));
+        self.breakpoints.resolve(cx, script)
+    }
+}
+
+impl<'rt> OnDebuggerStatement<'rt> for Session<'rt> {
+    fn on_debugger_statement(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        self.paused(cx, frame, "other", Vec::new())
+    }
+}
+
+impl<'rt> OnHit<'rt> for Session<'rt> {
+    fn on_hit(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        let hits = try!(self.breakpoints.hits(cx, frame)).into_iter().filter_map(|id| {
+            self.breakpoints.get_spec(id).map(|spec| breakpoint_id(id, &spec.url, spec.line - 1))
+        }).collect();
+        self.paused(cx, frame, "other", hits)
+    }
+}
+
+impl<'rt> OnStepComplete<'rt> for Session<'rt> {
+    fn on_step_complete(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        self.paused(cx, frame, "step", Vec::new())
+    }
+}
+
+impl<'rt> OnPauseRequest<'rt> for Session<'rt> {
+    fn on_pause_request(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        self.paused(cx, frame, "debugCommand", Vec::new())
+    }
+}
+
+impl<'rt> Connection<'rt> for Session<'rt> {
+    fn is_connected(&self) -> bool {
+        self.connected.get()
+    }
+
+    fn process_events(
+        &self,
+        cx: DebugContext<'rt>,
+        controller: &PauseController<'rt>
+    ) -> Result<'rt, ()> {
+        try!(self.process_message(cx, Some(controller), true));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Server;
+    use serde_json;
+    use serde_json::Value as Json;
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+    use tungstenite;
+    use tungstenite::{Message, WebSocket};
+    use url::Url;
+    use utils;
+
+    struct Client {
+        socket: WebSocket<TcpStream>,
+        events: Vec<Json>
+    }
+
+    impl Client {
+        fn send(&mut self, id: u64, method: &str, params: Json) -> Json {
+            let request = json!({ "id": id, "method": method, "params": params });
+            self.socket.write_message(Message::Text(request.to_string())).unwrap();
+            loop {
+                let message = self.receive();
+                if message["id"] == json!(id) {
+                    return message["result"].clone();
+                }
+                self.events.push(message);
+            }
+        }
+
+        fn wait_for(&mut self, method: &str) -> Json {
+            loop {
+                let message = self.receive();
+                if message["method"] == json!(method) {
+                    return message["params"].clone();
+                }
+                self.events.push(message);
+            }
+        }
+
+        fn receive(&mut self) -> Json {
+            match self.socket.read_message().unwrap() {
+                Message::Text(text) => serde_json::from_str(&text).unwrap(),
+                _ => self.receive()
+            }
+        }
+    }
+
+    #[test]
+    fn breakpoint_round_trip() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let (ready, wait) = mpsc::channel();
+        let client = thread::spawn(move || {
+            let url = Url::parse(&format!("ws://{}", address)).unwrap();
+            let stream = TcpStream::connect(address).unwrap();
+            let socket = match tungstenite::client(url, stream) {
+                Ok((socket, _)) => socket,
+                Err(_) => panic!("WebSocket handshake failed")
+            };
+            let mut client = Client {
+                socket: socket,
+                events: Vec::new()
+            };
+            client.send(1, "Debugger.enable", json!({}));
+            let breakpoint = client.send(2, "Debugger.setBreakpointByUrl", json!({
+                "url": "test",
+                "lineNumber": 1
+            }));
+            ready.send(()).unwrap();
+            let paused = client.wait_for("Debugger.paused");
+            client.send(3, "Debugger.resume", json!({}));
+            client.wait_for("Debugger.resumed");
+            client.socket.close(None).unwrap();
+            (client.events, breakpoint, paused)
+        });
+
+        utils::with_debuggee(|cx, debugger, evaluate| {
+            evaluate("function f(x) {\n    return x;\n}\n");
+            let server = Server::accept(cx, debugger.clone(), &listener).unwrap();
+            while wait.try_recv().is_err() {
+                server.poll(cx).unwrap();
+                thread::sleep(Duration::from_millis(1));
+            }
+            evaluate("f(1);\n");
+            while server.poll(cx).unwrap() {
+                thread::sleep(Duration::from_millis(1));
+            }
+        });
+
+        let (events, breakpoint, paused) = client.join().unwrap();
+        let mut script_ids: Vec<_> = events.iter().filter(|event| {
+            event["method"] == json!("Debugger.scriptParsed")
+        }).map(|event| event["params"]["scriptId"].as_str().unwrap().to_string()).collect();
+        let parsed = script_ids.len();
+        script_ids.sort();
+        script_ids.dedup();
+        assert_eq!(script_ids.len(), parsed);
+        assert_eq!(parsed, 2);
+        assert_eq!(breakpoint["locations"].as_array().unwrap().len(), 1);
+        assert_eq!(paused["reason"], json!("other"));
+        assert_eq!(paused["hitBreakpoints"], json!([breakpoint["breakpointId"].clone()]));
+        assert_eq!(paused["callFrames"][0]["functionName"], json!("f"));
+        assert_eq!(paused["callFrames"][0]["location"]["lineNumber"], json!(1));
+    }
+}
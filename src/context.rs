@@ -0,0 +1,39 @@
+use js::jsapi;
+use js::jsapi::{JSContext, JSRuntime};
+use js::rust::Runtime;
+use std::cell::Cell;
+use std::marker::PhantomData;
+use trace::ArenaGuard;
+
+#[derive(Clone, Copy)]
+pub struct DebugContext<'rt> {
+    cx: *mut JSContext,
+    marker: PhantomData<Cell<&'rt ()>>
+}
+
+impl<'rt> DebugContext<'rt> {
+    pub fn with<F, R>(runtime: &Runtime, f: F) -> R
+        where F: for<'a> FnOnce(DebugContext<'a>) -> R
+    {
+        let _arena = ArenaGuard::new(runtime.cx());
+        f(DebugContext {
+            cx: runtime.cx(),
+            marker: PhantomData
+        })
+    }
+
+    pub(crate) unsafe fn from_raw(cx: *mut JSContext) -> DebugContext<'rt> {
+        DebugContext {
+            cx: cx,
+            marker: PhantomData
+        }
+    }
+
+    pub fn as_raw(&self) -> *mut JSContext {
+        self.cx
+    }
+
+    pub fn runtime(&self) -> *mut JSRuntime {
+        unsafe { jsapi::JS_GetRuntime(self.cx) }
+    }
+}
@@ -2,18 +2,20 @@ use exception::{Exception, Result};
 use js::{glue, jsapi};
 use js::jsapi::{HandleValue, JSContext, JSObject, MutableHandleValue, JSString};
 use js::jsval;
+use std::borrow::Cow;
 use std::collections::BTreeMap;
+use std::os::raw::c_char;
 use std::slice;
-use std::iter::FromIterator;
+use std::str;
 use std::ptr;
 use utils;
 
 pub trait FromJSValue: Sized {
-    unsafe fn from_js_value(cx: *mut JSContext, v: HandleValue) -> Result<Self>;
+    unsafe fn from_js_value<'rt>(cx: *mut JSContext, v: HandleValue) -> Result<'rt, Self>;
 }
 
 impl FromJSValue for () {
-    unsafe fn from_js_value(_cx: *mut JSContext, v: HandleValue) -> Result<Self> {
+    unsafe fn from_js_value<'rt>(_cx: *mut JSContext, v: HandleValue) -> Result<'rt, Self> {
         assert!(v.is_undefined());
         Ok(())
     }
@@ -21,13 +23,13 @@ impl FromJSValue for () {
 
 
 impl FromJSValue for bool {
-    unsafe fn from_js_value(_cx: *mut JSContext, v: HandleValue) -> Result<Self> {
+    unsafe fn from_js_value<'rt>(_cx: *mut JSContext, v: HandleValue) -> Result<'rt, Self> {
         Ok(v.to_boolean())
     }
 }
 
 impl FromJSValue for i32 {
-    unsafe fn from_js_value(_cx: *mut JSContext, v: HandleValue) -> Result<Self> {
+    unsafe fn from_js_value<'rt>(_cx: *mut JSContext, v: HandleValue) -> Result<'rt, Self> {
         if v.is_int32() {
             Ok(v.to_int32())
         } else {
@@ -39,7 +41,7 @@ impl FromJSValue for i32 {
 }
 
 impl FromJSValue for u32 {
-    unsafe fn from_js_value(_cx: *mut JSContext, v: HandleValue) -> Result<Self> {
+    unsafe fn from_js_value<'rt>(_cx: *mut JSContext, v: HandleValue) -> Result<'rt, Self> {
         if v.is_int32() {
             Ok(v.to_int32() as u32)
         } else {
@@ -52,51 +54,109 @@ impl FromJSValue for u32 {
 }
 
 impl FromJSValue for f64 {
-    unsafe fn from_js_value(_cx: *mut JSContext, v: HandleValue) -> Result<Self> {
+    unsafe fn from_js_value<'rt>(_cx: *mut JSContext, v: HandleValue) -> Result<'rt, Self> {
         Ok(v.to_number())
     }
 }
 
 impl FromJSValue for *mut JSString {
-    unsafe fn from_js_value(_cx: *mut JSContext, v: HandleValue) -> Result<Self> {
+    unsafe fn from_js_value<'rt>(_cx: *mut JSContext, v: HandleValue) -> Result<'rt, Self> {
         Ok(v.to_string())
     }
 }
 
 impl FromJSValue for *mut JSObject {
-    unsafe fn from_js_value(_cx: *mut JSContext, v: HandleValue) -> Result<Self> {
+    unsafe fn from_js_value<'rt>(_cx: *mut JSContext, v: HandleValue) -> Result<'rt, Self> {
         Ok(v.to_object())
     }
 }
 
 impl FromJSValue for String {
-    unsafe fn from_js_value(cx: *mut JSContext, v: HandleValue) -> Result<Self> {
-        FromJSValue::from_js_value(cx, v).and_then(|str| {
-            if jsapi::JS_StringHasLatin1Chars(str) {
-                let mut length = 0;
-                Ok(String::from_iter(slice::from_raw_parts(
-                    try_jsapi!(
-                        cx,
-                        jsapi::JS_GetLatin1StringCharsAndLength(cx, ptr::null(), str, &mut length
-                    )),
-                    length as usize
-                ).iter().map(|&c| c as char)))
-            } else {
-                let mut length = 0;
-                Ok(String::from_utf16_lossy(slice::from_raw_parts(
-                    try_jsapi!(
-                        cx,
-                        jsapi::JS_GetTwoByteStringCharsAndLength(cx, ptr::null(), str, &mut length)
-                    ),
-                    length as usize
-                )))
-            }
-        })
+    unsafe fn from_js_value<'rt>(cx: *mut JSContext, v: HandleValue) -> Result<'rt, Self> {
+        let str = v.to_string();
+        let mut length = 0;
+        if jsapi::JS_StringHasLatin1Chars(str) {
+            let chars = try_jsapi!(
+                cx,
+                jsapi::JS_GetLatin1StringCharsAndLength(cx, ptr::null(), str, &mut length)
+            );
+            Ok(latin1_to_str(slice::from_raw_parts(chars, length as usize)).into_owned())
+        } else {
+            let chars = try_jsapi!(
+                cx,
+                jsapi::JS_GetTwoByteStringCharsAndLength(cx, ptr::null(), str, &mut length)
+            );
+            Ok(utf16_to_string(slice::from_raw_parts(chars, length as usize)))
+        }
     }
 }
 
+// The characters are only valid until the next GC, so `f` must not call into JSAPI.
+pub unsafe fn with_str<'rt, F: FnOnce(&str) -> R, R>(
+    cx: *mut JSContext,
+    v: HandleValue,
+    f: F
+) -> Result<'rt, R> {
+    let str = v.to_string();
+    let mut length = 0;
+    if jsapi::JS_StringHasLatin1Chars(str) {
+        let chars = try_jsapi!(
+            cx,
+            jsapi::JS_GetLatin1StringCharsAndLength(cx, ptr::null(), str, &mut length)
+        );
+        Ok(f(&latin1_to_str(slice::from_raw_parts(chars, length as usize))))
+    } else {
+        let chars = try_jsapi!(
+            cx,
+            jsapi::JS_GetTwoByteStringCharsAndLength(cx, ptr::null(), str, &mut length)
+        );
+        Ok(f(&utf16_to_string(slice::from_raw_parts(chars, length as usize))))
+    }
+}
+
+pub fn latin1_to_str(chars: &[u8]) -> Cow<str> {
+    let first = match chars.iter().position(|&c| c >= 0x80) {
+        Some(first) => first,
+        None => return Cow::Borrowed(unsafe { str::from_utf8_unchecked(chars) })
+    };
+    let extra = chars[first..].iter().filter(|&&c| c >= 0x80).count();
+    let mut bytes = Vec::with_capacity(chars.len() + extra);
+    bytes.extend_from_slice(&chars[..first]);
+    let mut rest = &chars[first..];
+    while let Some((&c, tail)) = rest.split_first() {
+        bytes.push(0xC0 | (c >> 6));
+        bytes.push(0x80 | (c & 0x3F));
+        let run = tail.iter().position(|&c| c >= 0x80).unwrap_or(tail.len());
+        bytes.extend_from_slice(&tail[..run]);
+        rest = &tail[run..];
+    }
+    Cow::Owned(unsafe { String::from_utf8_unchecked(bytes) })
+}
+
+pub fn utf16_to_string(chars: &[u16]) -> String {
+    if chars.iter().all(|&c| c < 0x80) {
+        let bytes = chars.iter().map(|&c| c as u8).collect();
+        return unsafe { String::from_utf8_unchecked(bytes) };
+    }
+    String::from_utf16_lossy(chars)
+}
+
+pub fn str_to_latin1(s: &str) -> Option<Cow<[u8]>> {
+    if s.is_ascii() {
+        return Some(Cow::Borrowed(s.as_bytes()));
+    }
+    let mut bytes = Vec::with_capacity(s.len());
+    for c in s.chars() {
+        if c as u32 > 0xFF {
+            return None;
+        }
+        bytes.push(c as u8);
+    }
+    Some(Cow::Owned(bytes))
+}
+
 impl<T: FromJSValue> FromJSValue for Vec<T> {
-    unsafe fn from_js_value(cx: *mut JSContext, v: HandleValue) -> Result<Self> {
+    unsafe fn from_js_value<'rt>(cx: *mut JSContext, v: HandleValue) -> Result<'rt, Self> {
         rooted!(in (cx) let obj = v.to_object());
         let mut length = 0;
         if !jsapi::JS_GetArrayLength(cx, obj.handle(), &mut length) {
@@ -172,13 +232,31 @@ impl ToJSValue for *mut JSObject {
 
 impl ToJSValue for str {
     unsafe fn to_js_value(&self, cx: *mut JSContext, rval: MutableHandleValue) -> bool {
-        let chars = Vec::from_iter(self.encode_utf16());
-        let str = try_jsapi!(jsapi::JS_NewUCStringCopyN(cx, chars.as_ptr(), chars.len()));
+        let str = match str_to_latin1(self) {
+            Some(chars) => {
+                try_jsapi!(jsapi::JS_NewStringCopyN(
+                    cx,
+                    chars.as_ptr() as *const c_char,
+                    chars.len()
+                ))
+            }
+            None => {
+                let mut chars = Vec::with_capacity(self.len());
+                chars.extend(self.encode_utf16());
+                try_jsapi!(jsapi::JS_NewUCStringCopyN(cx, chars.as_ptr(), chars.len()))
+            }
+        };
         try_jsapi!(str.to_js_value(cx, rval));
         true
     }
 }
 
+impl ToJSValue for String {
+    unsafe fn to_js_value(&self, cx: *mut JSContext, rval: MutableHandleValue) -> bool {
+        (**self).to_js_value(cx, rval)
+    }
+}
+
 impl<'a, T: ToJSValue> ToJSValue for &'a [T] {
     unsafe fn to_js_value(&self, cx: *mut JSContext, rval: MutableHandleValue) -> bool {
         let length = self.len();
@@ -207,7 +285,7 @@ impl<K: ToString, V: ToJSValue> ToJSValue for BTreeMap<K, V> {
 pub struct Null;
 
 impl FromJSValue for Null {
-    unsafe fn from_js_value(_cx: *mut JSContext, v: HandleValue) -> Result<Self> {
+    unsafe fn from_js_value<'rt>(_cx: *mut JSContext, v: HandleValue) -> Result<'rt, Self> {
         assert!(v.is_null());
         Ok(Null)
     }
@@ -229,7 +307,7 @@ impl<T> NullOr<T> {
 }
 
 impl<T: FromJSValue> FromJSValue for NullOr<T> {
-    unsafe fn from_js_value(cx: *mut JSContext, v: HandleValue) -> Result<Self> {
+    unsafe fn from_js_value<'rt>(cx: *mut JSContext, v: HandleValue) -> Result<'rt, Self> {
         if v.is_null() {
             Ok(NullOr(None))
         } else {
@@ -256,7 +334,7 @@ impl<T> UndefinedOr<T> {
 }
 
 impl<T: FromJSValue> FromJSValue for UndefinedOr<T> {
-    unsafe fn from_js_value(cx: *mut JSContext, v: HandleValue) -> Result<Self> {
+    unsafe fn from_js_value<'rt>(cx: *mut JSContext, v: HandleValue) -> Result<'rt, Self> {
         if v.is_undefined() {
             Ok(UndefinedOr(None))
         } else {
@@ -273,3 +351,33 @@ impl<T: ToJSValue> ToJSValue for UndefinedOr<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use super::{latin1_to_str, str_to_latin1, utf16_to_string};
+
+    #[test]
+    fn latin1() {
+        match latin1_to_str(b"ascii only") {
+            Cow::Borrowed(s) => assert_eq!(s, "ascii only"),
+            Cow::Owned(_) => panic!()
+        }
+        assert_eq!(latin1_to_str(&[0x63, 0x61, 0x66, 0xE9, 0x21, 0xFF]), "caf\u{e9}!\u{ff}");
+        assert_eq!(latin1_to_str(&[0xA0]), "\u{a0}");
+        assert_eq!(latin1_to_str(&[]), "");
+    }
+
+    #[test]
+    fn utf16() {
+        assert_eq!(utf16_to_string(&[0x61, 0x62]), "ab");
+        assert_eq!(utf16_to_string(&[0x61, 0x20AC, 0xD83D, 0xDE00]), "a\u{20ac}\u{1f600}");
+    }
+
+    #[test]
+    fn to_latin1() {
+        assert_eq!(str_to_latin1("abc").unwrap().as_ref(), b"abc");
+        assert_eq!(str_to_latin1("caf\u{e9}").unwrap().as_ref(), &[0x63, 0x61, 0x66, 0xE9][..]);
+        assert!(str_to_latin1("\u{20ac}").is_none());
+    }
+}
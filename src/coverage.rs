@@ -0,0 +1,255 @@
+use context::DebugContext;
+use debugger::{Debugger, ScriptQuery};
+use exception::Result;
+use script::OffsetCoverage;
+use serde_json;
+use serde_json::Value as Json;
+use std::collections::BTreeMap;
+use std::io;
+use std::io::Write;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FunctionCoverage {
+    pub name: String,
+    pub line: usize,
+    pub count: u64
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceCoverage {
+    pub url: String,
+    pub lines: BTreeMap<usize, u64>,
+    pub functions: Vec<FunctionCoverage>
+}
+
+impl SourceCoverage {
+    pub fn new(url: &str) -> SourceCoverage {
+        SourceCoverage {
+            url: url.to_string(),
+            lines: BTreeMap::new(),
+            functions: Vec::new()
+        }
+    }
+
+    pub fn get_lines_found(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn get_lines_hit(&self) -> usize {
+        self.lines.values().filter(|&&count| count > 0).count()
+    }
+
+    pub fn get_functions_found(&self) -> usize {
+        self.functions.len()
+    }
+
+    pub fn get_functions_hit(&self) -> usize {
+        self.functions.iter().filter(|function| function.count > 0).count()
+    }
+
+    pub fn add_script(
+        &mut self,
+        name: Option<&str>,
+        is_function: bool,
+        line: usize,
+        entries: &[OffsetCoverage]
+    ) {
+        let mut lines: BTreeMap<usize, u64> = BTreeMap::new();
+        for entry in entries {
+            let count = lines.entry(entry.line_number).or_insert(0);
+            if entry.count > *count {
+                *count = entry.count;
+            }
+        }
+        for (line, count) in lines {
+            let total = self.lines.entry(line).or_insert(0);
+            if count > *total {
+                *total = count;
+            }
+        }
+        if is_function {
+            let name = match name {
+                Some(name) => name.to_string(),
+                None => format!("(anonymous):{}", line)
+            };
+            let count = entries.iter().min_by_key(|entry| entry.offset).map(|entry| {
+                entry.count
+            }).unwrap_or(0);
+            if let Some(function) = self.functions.iter_mut().find(|function| {
+                function.line == line && function.name == name
+            }) {
+                function.count += count;
+                return;
+            }
+            self.functions.push(FunctionCoverage {
+                name: name,
+                line: line,
+                count: count
+            });
+        }
+    }
+
+    pub fn write_lcov<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        try!(writeln!(writer, "SF:{}", self.url));
+        let mut functions: Vec<&FunctionCoverage> = self.functions.iter().collect();
+        functions.sort_by_key(|function| function.line);
+        for function in &functions {
+            try!(writeln!(writer, "FN:{},{}", function.line, function.name));
+        }
+        for function in &functions {
+            try!(writeln!(writer, "FNDA:{},{}", function.count, function.name));
+        }
+        try!(writeln!(writer, "FNF:{}", self.get_functions_found()));
+        try!(writeln!(writer, "FNH:{}", self.get_functions_hit()));
+        for (line, count) in &self.lines {
+            try!(writeln!(writer, "DA:{},{}", line, count));
+        }
+        try!(writeln!(writer, "LF:{}", self.get_lines_found()));
+        try!(writeln!(writer, "LH:{}", self.get_lines_hit()));
+        writeln!(writer, "end_of_record")
+    }
+
+    pub fn to_json(&self) -> Json {
+        json!({
+            "lines": summary(self.get_lines_found(), self.get_lines_hit()),
+            "functions": summary(self.get_functions_found(), self.get_functions_hit())
+        })
+    }
+}
+
+pub struct Coverage {
+    sources: BTreeMap<String, SourceCoverage>
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        Coverage {
+            sources: BTreeMap::new()
+        }
+    }
+
+    pub fn collect<'rt>(cx: DebugContext<'rt>, debugger: &Debugger<'rt>) -> Result<'rt, Coverage> {
+        let mut coverage = Coverage::new();
+        for script in try!(debugger.find_scripts(cx, &ScriptQuery::default())) {
+            let entries = match try!(script.get_offsets_coverage(cx)) {
+                Some(entries) => entries,
+                None => continue
+            };
+            let url = try!(script.get_url(cx));
+            let name = try!(script.get_display_name(cx));
+            let is_function = try!(script.get_is_function(cx));
+            let line = try!(script.get_start_line(cx));
+            coverage.add_script(
+                &url,
+                name.as_ref().map(|name| name.as_str()),
+                is_function,
+                line,
+                &entries
+            );
+        }
+        Ok(coverage)
+    }
+
+    pub fn add_script(
+        &mut self,
+        url: &str,
+        name: Option<&str>,
+        is_function: bool,
+        line: usize,
+        entries: &[OffsetCoverage]
+    ) {
+        self.sources.entry(url.to_string()).or_insert_with(|| {
+            SourceCoverage::new(url)
+        }).add_script(name, is_function, line, entries);
+    }
+
+    pub fn get(&self, url: &str) -> Option<&SourceCoverage> {
+        self.sources.get(url)
+    }
+
+    pub fn get_sources(&self) -> Vec<&SourceCoverage> {
+        self.sources.values().collect()
+    }
+
+    pub fn write_lcov<W: Write>(&self, test_name: &str, writer: &mut W) -> io::Result<()> {
+        for source in self.sources.values() {
+            try!(writeln!(writer, "TN:{}", test_name));
+            try!(source.write_lcov(writer));
+        }
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> Json {
+        let mut sources = serde_json::Map::new();
+        for (url, source) in &self.sources {
+            sources.insert(url.clone(), source.to_json());
+        }
+        Json::Object(sources)
+    }
+
+    pub fn write_json<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        try!(serde_json::to_writer_pretty(&mut *writer, &self.to_json()));
+        writer.write_all(b"\n")
+    }
+}
+
+impl Default for Coverage {
+    fn default() -> Coverage {
+        Coverage::new()
+    }
+}
+
+fn summary(found: usize, hit: usize) -> Json {
+    let percent = if found == 0 { 100.0 } else { hit as f64 * 100.0 / found as f64 };
+    json!({
+        "found": found,
+        "hit": hit,
+        "percent": percent
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use script::OffsetCoverage;
+    use super::Coverage;
+
+    fn entry(line: usize, offset: usize, count: u64) -> OffsetCoverage {
+        OffsetCoverage {
+            line_number: line,
+            column_number: 0,
+            offset: offset,
+            count: count
+        }
+    }
+
+    #[test]
+    fn lcov() {
+        let mut coverage = Coverage::new();
+        let entries = [entry(1, 0, 1), entry(5, 10, 1), entry(6, 12, 0)];
+        coverage.add_script("a.js", None, false, 1, &entries);
+        let entries = [entry(2, 0, 3), entry(3, 4, 3), entry(3, 8, 1)];
+        coverage.add_script("a.js", Some("f"), true, 2, &entries);
+        coverage.add_script("a.js", Some("g"), true, 4, &[entry(4, 0, 0)]);
+        coverage.add_script("a.js", None, true, 5, &[entry(5, 0, 1)]);
+        let mut output = Vec::new();
+        coverage.write_lcov("test", &mut output).unwrap();
+        let expected = [
+            "TN:test", "SF:a.js",
+            "FN:2,f", "FN:4,g", "FN:5,(anonymous):5",
+            "FNDA:3,f", "FNDA:0,g", "FNDA:1,(anonymous):5", "FNF:3", "FNH:2",
+            "DA:1,1", "DA:2,3", "DA:3,3", "DA:4,0", "DA:5,1", "DA:6,0", "LF:6", "LH:4",
+            "end_of_record", ""
+        ];
+        assert_eq!(String::from_utf8(output).unwrap(), expected.join("\n"));
+    }
+
+    #[test]
+    fn json() {
+        let mut coverage = Coverage::new();
+        coverage.add_script("a.js", Some("f"), true, 1, &[entry(1, 0, 2), entry(2, 4, 0)]);
+        let json = coverage.to_json();
+        assert_eq!(json["a.js"]["lines"]["found"], 2);
+        assert_eq!(json["a.js"]["lines"]["hit"], 1);
+        assert_eq!(json["a.js"]["functions"]["hit"], 1);
+    }
+}
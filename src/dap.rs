@@ -0,0 +1,626 @@
+use breakpoint::{HitCount, OnLog};
+use context::DebugContext;
+use debugger::{Debugger, OnDebuggerStatement, OnNewScript};
+use exception::{Exception, Result};
+use frame::Frame;
+use pause::PauseController;
+use registry::{Handle, HandleRegistry, Lifetime};
+use script::{OnHit, Script};
+use serde_json;
+use serde_json::Value as Json;
+use session::{BreakpointSpec, Breakpoints, Connection, Dispatcher};
+use std::cell::{Cell, RefCell};
+use std::io;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::rc::{Rc, Weak};
+use std::result;
+use stepper::{OnStepComplete, StepKind, Stepper};
+use value::{CompletionValue, ResumptionValue, Value};
+
+const THREAD_ID: u64 = 1;
+const MAX_CONTENT_LENGTH: usize = 16 * 1024 * 1024;
+
+enum Error<'rt> {
+    Exception(Exception<'rt>),
+    InvalidArguments(&'static str),
+    NotFound(&'static str),
+    NotPaused,
+    UnknownCommand(String)
+}
+
+impl<'rt> From<Exception<'rt>> for Error<'rt> {
+    fn from(exception: Exception<'rt>) -> Error<'rt> {
+        Error::Exception(exception)
+    }
+}
+
+impl<'rt> Error<'rt> {
+    fn message(&self) -> String {
+        match *self {
+            Error::Exception(_) => "Uncaught exception in debugger".to_string(),
+            Error::InvalidArguments(name) => format!("Invalid argument: {}", name),
+            Error::NotFound(what) => format!("Unknown {} reference", what),
+            Error::NotPaused => "Not paused".to_string(),
+            Error::UnknownCommand(ref command) => format!("Unrecognized request '{}'", command)
+        }
+    }
+}
+
+type CommandResult<'rt> = result::Result<Json, Error<'rt>>;
+
+pub struct LaunchRequest {
+    pub program: String,
+    pub arguments: Json
+}
+
+pub struct Server<'rt>(Rc<Session<'rt>>);
+
+impl<'rt> Server<'rt> {
+    pub fn new(
+        cx: DebugContext<'rt>,
+        debugger: Rc<Debugger<'rt>>,
+        input: Box<BufRead>,
+        output: Box<Write>
+    ) -> Result<'rt, Server<'rt>> {
+        let session = Session::new(debugger, input, output);
+        try!(session.install(cx));
+        Ok(Server(session))
+    }
+
+    pub fn stdio(cx: DebugContext<'rt>, debugger: Rc<Debugger<'rt>>) -> Result<'rt, Server<'rt>> {
+        Server::new(cx, debugger, Box::new(BufReader::new(io::stdin())), Box::new(io::stdout()))
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.0.connected.get()
+    }
+
+    pub fn wait_for_launch(&self, cx: DebugContext<'rt>) -> Result<'rt, Option<LaunchRequest>> {
+        while self.0.connected.get() {
+            if self.0.configuration_done.get() && self.0.launch.borrow().is_some() {
+                return Ok(self.0.launch.borrow_mut().take());
+            }
+            try!(self.0.process_message(cx, None));
+        }
+        Ok(None)
+    }
+
+    pub fn terminate(&self, cx: DebugContext<'rt>, exit_code: i32) -> Result<'rt, ()> {
+        try!(self.0.stepper.cancel(cx));
+        self.0.send_event("exited", json!({ "exitCode": exit_code }));
+        self.0.send_event("terminated", json!({}));
+        while self.0.connected.get() {
+            try!(self.0.process_message(cx, None));
+        }
+        Ok(())
+    }
+}
+
+struct Session<'rt> {
+    this: RefCell<Weak<Session<'rt>>>,
+    debugger: Rc<Debugger<'rt>>,
+    input: RefCell<Box<BufRead>>,
+    output: RefCell<Box<Write>>,
+    controller: RefCell<Option<Rc<PauseController<'rt>>>>,
+    stepper: Stepper<'rt>,
+    breakpoints: Breakpoints<'rt>,
+    handles: HandleRegistry<'rt>,
+    launch: RefCell<Option<LaunchRequest>>,
+    seq: Cell<u64>,
+    configuration_done: Cell<bool>,
+    connected: Cell<bool>
+}
+
+impl<'rt> Session<'rt> {
+    fn new(
+        debugger: Rc<Debugger<'rt>>,
+        input: Box<BufRead>,
+        output: Box<Write>
+    ) -> Rc<Session<'rt>> {
+        let session = Rc::new(Session {
+            this: RefCell::new(Weak::new()),
+            debugger: debugger.clone(),
+            input: RefCell::new(input),
+            output: RefCell::new(output),
+            controller: RefCell::new(None),
+            stepper: Stepper::new(debugger.clone()),
+            breakpoints: Breakpoints::new(debugger),
+            handles: HandleRegistry::new(),
+            launch: RefCell::new(None),
+            seq: Cell::new(1),
+            configuration_done: Cell::new(false),
+            connected: Cell::new(true)
+        });
+        *session.this.borrow_mut() = Rc::downgrade(&session);
+        let dispatcher = Rc::new(Dispatcher(Rc::downgrade(&session)));
+        *session.controller.borrow_mut() = Some(Rc::new(PauseController::new(dispatcher)));
+        session
+    }
+
+    fn this(&self) -> Rc<Session<'rt>> {
+        self.this.borrow().upgrade().unwrap()
+    }
+
+    fn controller(&self) -> Rc<PauseController<'rt>> {
+        self.controller.borrow().as_ref().unwrap().clone()
+    }
+
+    fn install(&self, cx: DebugContext<'rt>) -> Result<'rt, ()> {
+        try!(self.debugger.set_on_new_script(cx, Some(self.this() as Rc<OnNewScript<'rt> + 'rt>)));
+        try!(self.debugger.set_on_debugger_statement(
+            cx,
+            Some(self.this() as Rc<OnDebuggerStatement<'rt> + 'rt>)
+        ));
+        Ok(())
+    }
+
+    fn send(&self, mut message: Json) {
+        let seq = self.seq.get();
+        self.seq.set(seq + 1);
+        message["seq"] = json!(seq);
+        let body = message.to_string();
+        let mut output = self.output.borrow_mut();
+        let result = write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body).and_then(|_| {
+            output.flush()
+        });
+        if result.is_err() {
+            self.connected.set(false);
+        }
+    }
+
+    fn send_event(&self, event: &str, body: Json) {
+        self.send(json!({ "type": "event", "event": event, "body": body }));
+    }
+
+    fn read_message(&self) -> io::Result<Option<Json>> {
+        let mut input = self.input.borrow_mut();
+        let mut length = None;
+        loop {
+            let mut line = String::new();
+            if try!(input.read_line(&mut line)) == 0 {
+                return Ok(None);
+            }
+            let line = line.trim();
+            if line.is_empty() {
+                break;
+            }
+            let mut parts = line.splitn(2, ':');
+            if parts.next().unwrap_or("").trim().eq_ignore_ascii_case("content-length") {
+                length = parts.next().and_then(|value| value.trim().parse::<usize>().ok());
+            }
+        }
+        let length = match length {
+            Some(length) => length,
+            None => return Err(io::Error::new(io::ErrorKind::InvalidData, "missing length"))
+        };
+        if length > MAX_CONTENT_LENGTH {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "message too long"));
+        }
+        let mut body = vec![0; length];
+        try!(input.read_exact(&mut body));
+        serde_json::from_slice(&body).map(Some).map_err(|error| {
+            io::Error::new(io::ErrorKind::InvalidData, error)
+        })
+    }
+
+    fn process_message(
+        &self,
+        cx: DebugContext<'rt>,
+        controller: Option<&PauseController<'rt>>
+    ) -> Result<'rt, ()> {
+        let request = match self.read_message() {
+            Ok(Some(request)) => request,
+            Ok(None) | Err(_) => {
+                self.disconnect(controller);
+                return Ok(());
+            }
+        };
+        let command = request["command"].as_str().unwrap_or("").to_string();
+        let result = self.dispatch(cx, controller, &command, &request["arguments"]);
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"].clone(),
+            "command": command
+        });
+        match result {
+            Ok(body) => {
+                response["success"] = json!(true);
+                response["body"] = body;
+            }
+            Err(error) => {
+                response["success"] = json!(false);
+                response["message"] = json!(error.message());
+            }
+        }
+        self.send(response);
+        if command == "initialize" {
+            self.send_event("initialized", json!({}));
+        }
+        Ok(())
+    }
+
+    fn disconnect(&self, controller: Option<&PauseController<'rt>>) {
+        self.connected.set(false);
+        if let Some(controller) = controller {
+            controller.resume(None);
+        }
+    }
+
+    fn dispatch(
+        &self,
+        cx: DebugContext<'rt>,
+        controller: Option<&PauseController<'rt>>,
+        command: &str,
+        arguments: &Json
+    ) -> CommandResult<'rt> {
+        match command {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsConditionalBreakpoints": true,
+                "supportsHitConditionalBreakpoints": true,
+                "supportsLogPoints": true,
+                "supportsEvaluateForHovers": true
+            })),
+            "launch" => {
+                let program = try!(arguments["program"].as_str().ok_or(
+                    Error::InvalidArguments("program")
+                ));
+                *self.launch.borrow_mut() = Some(LaunchRequest {
+                    program: program.to_string(),
+                    arguments: arguments.clone()
+                });
+                Ok(json!({}))
+            }
+            "configurationDone" => {
+                self.configuration_done.set(true);
+                Ok(json!({}))
+            }
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "main" }] })),
+            "setBreakpoints" => self.set_breakpoints(cx, arguments),
+            "stackTrace" => self.stack_trace(cx, controller),
+            "scopes" => self.scopes(cx, arguments),
+            "variables" => self.variables(cx, arguments),
+            "evaluate" => self.evaluate(cx, arguments),
+            "continue" => {
+                let controller = try!(controller.ok_or(Error::NotPaused));
+                controller.resume(None);
+                Ok(json!({ "allThreadsContinued": true }))
+            }
+            "next" => self.step(cx, controller, StepKind::Over),
+            "stepIn" => self.step(cx, controller, StepKind::In),
+            "stepOut" => self.step(cx, controller, StepKind::Out),
+            "disconnect" => {
+                self.disconnect(controller);
+                Ok(json!({}))
+            }
+            _ => Err(Error::UnknownCommand(command.to_string()))
+        }
+    }
+
+    fn set_breakpoints(&self, cx: DebugContext<'rt>, arguments: &Json) -> CommandResult<'rt> {
+        let path = try!(arguments["source"]["path"].as_str().ok_or(
+            Error::InvalidArguments("source")
+        )).to_string();
+        try!(self.breakpoints.remove_url(cx, &path));
+        let requested = match arguments["breakpoints"].as_array() {
+            Some(breakpoints) => breakpoints.clone(),
+            None => Vec::new()
+        };
+        let on_hit = self.this() as Rc<OnHit<'rt> + 'rt>;
+        let on_log = self.this() as Rc<OnLog<'rt> + 'rt>;
+        let mut result = Vec::new();
+        for breakpoint in requested {
+            let line = try!(breakpoint["line"].as_u64().ok_or(Error::InvalidArguments("line")));
+            let spec = BreakpointSpec {
+                url: path.clone(),
+                line: line as usize,
+                condition: non_empty(&breakpoint["condition"]),
+                hit_count: breakpoint["hitCondition"].as_str().and_then(parse_hit_condition),
+                log_message: non_empty(&breakpoint["logMessage"])
+            };
+            let id = try!(self.breakpoints.add(cx, spec, &on_hit, Some(&on_log)));
+            result.push(json!({
+                "verified": !self.breakpoints.locations(cx, id).is_empty(),
+                "line": line
+            }));
+        }
+        Ok(json!({ "breakpoints": result }))
+    }
+
+    fn stack_trace(
+        &self,
+        cx: DebugContext<'rt>,
+        controller: Option<&PauseController<'rt>>
+    ) -> CommandResult<'rt> {
+        let controller = try!(controller.ok_or(Error::NotPaused));
+        let mut current = controller.get_paused_frame(cx);
+        let mut frames = Vec::new();
+        while let Some(frame) = current {
+            let older = try!(frame.get_older(cx));
+            if let Some(script) = try!(frame.get_script(cx)) {
+                let url = try!(script.get_url(cx));
+                let location = try!(script.get_offset_location(cx, try!(frame.get_offset(cx))));
+                let name = match try!(frame.get_callee(cx)) {
+                    Some(callee) => {
+                        try!(callee.get_name(cx)).unwrap_or("<anonymous>".to_string())
+                    }
+                    None => "<global>".to_string()
+                };
+                frames.push(json!({
+                    "id": try!(self.register_handle(cx, Handle::Frame(frame))),
+                    "name": name,
+                    "source": { "path": url },
+                    "line": location.line_number,
+                    "column": location.column_number + 1
+                }));
+            }
+            current = older;
+        }
+        Ok(json!({ "stackFrames": frames, "totalFrames": frames.len() }))
+    }
+
+    fn scopes(&self, cx: DebugContext<'rt>, arguments: &Json) -> CommandResult<'rt> {
+        let frame = match self.get_handle(cx, &arguments["frameId"]) {
+            Some(Handle::Frame(frame)) => frame,
+            _ => return Err(Error::NotFound("frame"))
+        };
+        let mut scopes = Vec::new();
+        let mut current = try!(frame.get_environment(cx));
+        while let Some(environment) = current {
+            let parent = try!(environment.get_parent(cx));
+            let name = if scopes.is_empty() {
+                "Locals"
+            } else if parent.is_none() {
+                "Globals"
+            } else {
+                "Closure"
+            };
+            let reference = try!(self.register_handle(cx, Handle::Environment(environment)));
+            scopes.push(json!({
+                "name": name,
+                "variablesReference": reference,
+                "expensive": parent.is_none()
+            }));
+            current = parent;
+        }
+        Ok(json!({ "scopes": scopes }))
+    }
+
+    fn variables(&self, cx: DebugContext<'rt>, arguments: &Json) -> CommandResult<'rt> {
+        let mut variables = Vec::new();
+        match self.get_handle(cx, &arguments["variablesReference"]) {
+            Some(Handle::Environment(environment)) => {
+                for name in try!(environment.names(cx)) {
+                    let variable = match try!(environment.get_variable(cx, &name)) {
+                        Some(value) => try!(self.variable(cx, &name, value)),
+                        None => json!({
+                            "name": name,
+                            "value": "<optimized out>",
+                            "variablesReference": 0
+                        })
+                    };
+                    variables.push(variable);
+                }
+            }
+            Some(Handle::Object(object)) => {
+                for name in try!(object.get_own_property_names(cx)) {
+                    let descriptor = try!(object.get_own_property_descriptor(cx, &name));
+                    let variable = match descriptor.value {
+                        Some(value) => try!(self.variable(cx, &name, value)),
+                        None => json!({
+                            "name": name,
+                            "value": "<accessor>",
+                            "variablesReference": 0
+                        })
+                    };
+                    variables.push(variable);
+                }
+            }
+            _ => return Err(Error::NotFound("variables"))
+        }
+        Ok(json!({ "variables": variables }))
+    }
+
+    fn variable(&self, cx: DebugContext<'rt>, name: &str, value: Value<'rt>) -> CommandResult<'rt> {
+        let (description, reference) = try!(self.describe(cx, value));
+        Ok(json!({
+            "name": name,
+            "value": description,
+            "variablesReference": reference
+        }))
+    }
+
+    fn evaluate(&self, cx: DebugContext<'rt>, arguments: &Json) -> CommandResult<'rt> {
+        let expression = try!(arguments["expression"].as_str().ok_or(
+            Error::InvalidArguments("expression")
+        ));
+        let completion = if arguments["frameId"].is_null() {
+            let global = match try!(self.debugger.get_debuggees(cx)).into_iter().next() {
+                Some(global) => global,
+                None => return Err(Error::NotFound("global"))
+            };
+            try!(global.execute_in_global(cx, expression))
+        } else {
+            match self.get_handle(cx, &arguments["frameId"]) {
+                Some(Handle::Frame(frame)) => try!(frame.eval(cx, expression)),
+                _ => return Err(Error::NotFound("frame"))
+            }
+        };
+        let (description, reference) = match completion {
+            CompletionValue::Return(value) => try!(self.describe(cx, value)),
+            CompletionValue::Throw(value) => {
+                let (description, _) = try!(self.describe(cx, value));
+                (format!("Uncaught {}", description), 0)
+            }
+            CompletionValue::Terminate => ("<terminated>".to_string(), 0)
+        };
+        Ok(json!({ "result": description, "variablesReference": reference }))
+    }
+
+    fn step(
+        &self,
+        cx: DebugContext<'rt>,
+        controller: Option<&PauseController<'rt>>,
+        kind: StepKind
+    ) -> CommandResult<'rt> {
+        let controller = try!(controller.ok_or(Error::NotPaused));
+        let frame = try!(controller.get_paused_frame(cx).ok_or(Error::NotPaused));
+        try!(self.stepper.step(cx, kind, &frame, self.this() as Rc<OnStepComplete<'rt> + 'rt>));
+        controller.resume(None);
+        Ok(json!({}))
+    }
+
+    fn describe(&self, cx: DebugContext<'rt>, value: Value<'rt>) -> Result<'rt, (String, u64)> {
+        Ok(match value {
+            Value::Undefined => ("undefined".to_string(), 0),
+            Value::Null => ("null".to_string(), 0),
+            Value::Boolean(b) => (b.to_string(), 0),
+            Value::Int32(i) => (i.to_string(), 0),
+            Value::Double(d) => (d.to_string(), 0),
+            Value::String(s) => (format!("{:?}", s), 0),
+            Value::Object(object) => {
+                let description = if try!(object.get_is_callable(cx)) {
+                    format!("function {}()", try!(object.get_name(cx)).unwrap_or(String::new()))
+                } else {
+                    try!(object.get_class(cx))
+                };
+                (description, try!(self.register_handle(cx, Handle::Object(object))))
+            }
+        })
+    }
+
+    fn register_handle(&self, cx: DebugContext<'rt>, handle: Handle<'rt>) -> Result<'rt, u64> {
+        self.handles.register(cx, handle, Lifetime::Pause)
+    }
+
+    fn get_handle(&self, cx: DebugContext<'rt>, reference: &Json) -> Option<Handle<'rt>> {
+        match reference.as_u64() {
+            Some(reference) => self.handles.get(cx, reference),
+            None => None
+        }
+    }
+
+    fn stopped(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>,
+        reason: &str
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        if !self.connected.get() {
+            return Ok(None);
+        }
+        self.handles.enter_pause();
+        self.send_event("stopped", json!({
+            "reason": reason,
+            "threadId": THREAD_ID,
+            "allThreadsStopped": true
+        }));
+        let result = self.controller().pause(cx, frame);
+        self.handles.leave_pause();
+        if self.connected.get() {
+            self.send_event("continued", json!({
+                "threadId": THREAD_ID,
+                "allThreadsContinued": true
+            }));
+        }
+        result
+    }
+}
+
+impl<'rt> OnNewScript<'rt> for Session<'rt> {
+    fn on_new_script(&self, cx: DebugContext<'rt>, script: &Script<'rt>) -> Result<'rt, ()> {
+        self.breakpoints.resolve(cx, script)
+    }
+}
+
+impl<'rt> OnDebuggerStatement<'rt> for Session<'rt> {
+    fn on_debugger_statement(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        self.stopped(cx, frame, "pause")
+    }
+}
+
+impl<'rt> OnHit<'rt> for Session<'rt> {
+    fn on_hit(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        self.stopped(cx, frame, "breakpoint")
+    }
+}
+
+impl<'rt> OnStepComplete<'rt> for Session<'rt> {
+    fn on_step_complete(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        self.stopped(cx, frame, "step")
+    }
+}
+
+impl<'rt> Connection<'rt> for Session<'rt> {
+    fn is_connected(&self) -> bool {
+        self.connected.get()
+    }
+
+    fn process_events(
+        &self,
+        cx: DebugContext<'rt>,
+        controller: &PauseController<'rt>
+    ) -> Result<'rt, ()> {
+        self.process_message(cx, Some(controller))
+    }
+}
+
+impl<'rt> OnLog<'rt> for Session<'rt> {
+    fn on_log(
+        &self,
+        _cx: DebugContext<'rt>,
+        _frame: &Frame<'rt>,
+        message: &str
+    ) -> Result<'rt, ()> {
+        self.send_event("output", json!({
+            "category": "console",
+            "output": format!("{}\n", message)
+        }));
+        Ok(())
+    }
+}
+
+fn non_empty(value: &Json) -> Option<String> {
+    value.as_str().and_then(|s| if s.trim().is_empty() { None } else { Some(s.to_string()) })
+}
+
+fn parse_hit_condition(condition: &str) -> Option<HitCount> {
+    let condition = condition.trim();
+    let (constructor, count): (fn(usize) -> HitCount, &str) = if condition.starts_with(">=") {
+        (HitCount::AtLeast, &condition[2..])
+    } else if condition.starts_with("==") {
+        (HitCount::Equal, &condition[2..])
+    } else if condition.starts_with('%') {
+        (HitCount::Multiple, &condition[1..])
+    } else {
+        (HitCount::Equal, condition)
+    };
+    count.trim().parse().ok().map(constructor)
+}
+
+#[cfg(test)]
+mod tests {
+    use breakpoint::HitCount;
+    use super::parse_hit_condition;
+
+    #[test]
+    fn hit_condition() {
+        assert_eq!(parse_hit_condition("5"), Some(HitCount::Equal(5)));
+        assert_eq!(parse_hit_condition("== 2"), Some(HitCount::Equal(2)));
+        assert_eq!(parse_hit_condition(">=3"), Some(HitCount::AtLeast(3)));
+        assert_eq!(parse_hit_condition("% 4"), Some(HitCount::Multiple(4)));
+        assert_eq!(parse_hit_condition("often"), None);
+    }
+}
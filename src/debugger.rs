@@ -1,8 +1,10 @@
 use call::Call;
-use convert::{FromJSValue, ToJSValue};
+use context::DebugContext;
+use convert::{FromJSValue, NullOr, ToJSValue};
 use exception::Result;
 use ext::HandleValueArrayExt;
-use frame::Frame;
+use frame::{Frame, OnPop, OnStep};
+use hooks::Hooks;
 use js::jsapi;
 use js::jsapi::{
     CallArgs,
@@ -10,50 +12,111 @@ use js::jsapi::{
     HandleValueArray,
     JSContext,
     JSObject,
+    MutableHandleValue,
     Value,
 };
 use js::jsval;
+use memory::{Memory, OnGarbageCollection};
 use object::Object;
 use rooted::Rooted;
 use script::Script;
+use std::any::TypeId;
+use std::cell::RefCell;
 use std::ptr;
-use std::rc::Rc;
+use std::rc::{Rc, Weak};
 use trace::TracedBox;
 use utils;
 use value::ResumptionValue;
 
-pub trait OnNewScript {
-    fn on_new_script(&self, cx: *mut JSContext, script: &Script) -> Result<()>;
+pub trait OnNewScript<'rt> {
+    fn on_new_script(&self, cx: DebugContext<'rt>, script: &Script<'rt>) -> Result<'rt, ()>;
 }
 
-impl Call for OnNewScript {
+impl<'rt> Call for OnNewScript<'rt> + 'rt {
+    fn hook_type() -> TypeId {
+        TypeId::of::<OnNewScript<'static> + 'static>()
+    }
+
     unsafe fn call(&self, cx: *mut JSContext, argc: u32, vp: *mut Value) -> bool {
         let args = CallArgs::from_vp(vp, argc);
-        match self.on_new_script(cx, &Script::from_js_value(cx, args.get(0)).unwrap()) {
+        let script = Script::from_js_value(cx, args.get(0)).unwrap();
+        match self.on_new_script(DebugContext::from_raw(cx), &script) {
             Ok(result) => result.to_js_value(cx, args.rval()),
             Err(exception) => exception.into_pending_exception(cx)
         }
     }
 }
 
-pub trait OnDebuggerStatement {
-    fn on_debugger_statement(&self, cx: *mut JSContext, frame: &Frame) -> Result<ResumptionValue>;
+pub trait OnDebuggerStatement<'rt> {
+    fn on_debugger_statement(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>>;
 }
 
-impl Call for OnDebuggerStatement {
+impl<'rt> Call for OnDebuggerStatement<'rt> + 'rt {
+    fn hook_type() -> TypeId {
+        TypeId::of::<OnDebuggerStatement<'static> + 'static>()
+    }
+
     unsafe fn call(&self, cx: *mut JSContext, argc: u32, vp: *mut Value) -> bool {
         let args = CallArgs::from_vp(vp, argc);
-        match self.on_debugger_statement(cx, &Frame::from_js_value(cx, args.get(0)).unwrap()) {
+        let frame = Frame::from_js_value(cx, args.get(0)).unwrap();
+        match self.on_debugger_statement(DebugContext::from_raw(cx), &frame) {
             Ok(result) => result.to_js_value(cx, args.rval()),
             Err(exception) => exception.into_pending_exception(cx)
         }
     }
 }
 
-pub struct Debugger(TracedBox<*mut JSObject>);
+pub trait OnEnterFrame<'rt> {
+    fn on_enter_frame(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>>;
+}
+
+impl<'rt> Call for OnEnterFrame<'rt> + 'rt {
+    fn hook_type() -> TypeId {
+        TypeId::of::<OnEnterFrame<'static> + 'static>()
+    }
+
+    unsafe fn call(&self, cx: *mut JSContext, argc: u32, vp: *mut Value) -> bool {
+        let args = CallArgs::from_vp(vp, argc);
+        let frame = Frame::from_js_value(cx, args.get(0)).unwrap();
+        match self.on_enter_frame(DebugContext::from_raw(cx), &frame) {
+            Ok(result) => result.to_js_value(cx, args.rval()),
+            Err(exception) => exception.into_pending_exception(cx)
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ScriptQuery {
+    pub url: Option<String>,
+    pub line: Option<usize>
+}
 
-impl Debugger {
-    pub fn new(cx: *mut JSContext) -> Debugger {
+impl ToJSValue for ScriptQuery {
+    unsafe fn to_js_value(&self, cx: *mut JSContext, rval: MutableHandleValue) -> bool {
+        rooted!(in (cx) let obj = try_jsapi!(jsapi::JS_NewObject(cx, ptr::null_mut())));
+        if let Some(ref url) = self.url {
+            try_jsapi!(utils::define_property(cx, obj.handle(), "url", url));
+        }
+        if let Some(line) = self.line {
+            try_jsapi!(utils::define_property(cx, obj.handle(), "line", &(line as u32)));
+        }
+        obj.to_js_value(cx, rval)
+    }
+}
+
+pub struct Debugger<'rt>(TracedBox<'rt, *mut JSObject>, RefCell<Weak<Hooks<'rt>>>);
+
+impl<'rt> Debugger<'rt> {
+    pub fn new(cx: DebugContext<'rt>) -> Debugger<'rt> {
+        let cx = cx.as_raw();
         unsafe {
             rooted!(in (cx) let mut global = ptr::null_mut());
             assert!(utils::new_global_object(cx, global.handle_mut()));
@@ -73,63 +136,196 @@ impl Debugger {
                 &HandleValueArray::new(),
                 debugger.handle_mut()
             ));
-            Debugger(TracedBox::new(cx, debugger.get()))
+            Debugger(
+                TracedBox::new(DebugContext::from_raw(cx), debugger.get()),
+                RefCell::new(Weak::new())
+            )
         }
     }
 
-    pub fn has_debuggee(&self, cx: *mut JSContext, global: HandleObject) -> Result<bool> {
+    pub fn has_debuggee(&self, cx: DebugContext<'rt>, global: HandleObject) -> Result<'rt, bool> {
         method!(cx, self, "hasDebuggee", global)
     }
 
-    pub fn get_debuggees(&self, cx: *mut JSContext) -> Result<Vec<Object>> {
+    pub fn get_debuggees(&self, cx: DebugContext<'rt>) -> Result<'rt, Vec<Object<'rt>>> {
         method!(cx, self, "getDebuggees")
     }
 
-    pub fn add_debuggee(&self, cx: *mut JSContext, global: HandleObject) -> Result<Object> {
+    pub fn add_debuggee(
+        &self,
+        cx: DebugContext<'rt>,
+        global: HandleObject
+    ) -> Result<'rt, Object<'rt>> {
         method!(cx, self, "addDebuggee", global)
     }
 
-    pub fn add_all_globals_as_debuggees(&self, cx: *mut JSContext) -> Result<()> {
+    pub fn add_all_globals_as_debuggees(&self, cx: DebugContext<'rt>) -> Result<'rt, ()> {
         method!(cx, self, "addAllGlobalsAsDebuggees")
     }
 
-    pub fn remove_debuggee(&self, cx: *mut JSContext, global: HandleObject) -> Result<()> {
+    pub fn remove_debuggee(&self, cx: DebugContext<'rt>, global: HandleObject) -> Result<'rt, ()> {
         method!(cx, self, "removeDebuggee", global)
     }
 
-    pub fn remove_all_debuggees(&self, cx: *mut JSContext) -> Result<()> {
+    pub fn remove_all_debuggees(&self, cx: DebugContext<'rt>) -> Result<'rt, ()> {
         method!(cx, self, "removeAllDebuggees")
     }
 
+    pub fn get_memory(&self, cx: DebugContext<'rt>) -> Result<'rt, Memory<'rt>> {
+        getter!(cx, self, "memory")
+    }
+
+    pub fn get_collect_coverage_info(&self, cx: DebugContext<'rt>) -> Result<'rt, bool> {
+        getter!(cx, self, "collectCoverageInfo")
+    }
+
+    pub fn set_collect_coverage_info(
+        &self,
+        cx: DebugContext<'rt>,
+        collect: bool
+    ) -> Result<'rt, ()> {
+        setter!(cx, self, "collectCoverageInfo", collect)
+    }
+
+    pub fn find_scripts(
+        &self,
+        cx: DebugContext<'rt>,
+        query: &ScriptQuery
+    ) -> Result<'rt, Vec<Script<'rt>>> {
+        method!(cx, self, "findScripts", query)
+    }
+
+    pub fn get_newest_frame(&self, cx: DebugContext<'rt>) -> Result<'rt, Option<Frame<'rt>>> {
+        method!(cx, self, "getNewestFrame").map(|frame| NullOr::<Frame<'rt>>::into_option(frame))
+    }
+
     pub fn get_on_debugger_statement(
         &self, 
-        cx: *mut JSContext
-    ) -> Result<Option<Rc<OnDebuggerStatement>>> {
+        cx: DebugContext<'rt>
+    ) -> Result<'rt, Option<Rc<OnDebuggerStatement<'rt> + 'rt>>> {
         getter!(cx, self, "onDebuggerStatement")
     }
 
     pub fn set_on_debugger_statement(
         &self,
-        cx: *mut JSContext,
-        on_debugger_statement: Option<Rc<OnDebuggerStatement>>
-    ) -> Result<()> {
+        cx: DebugContext<'rt>,
+        on_debugger_statement: Option<Rc<OnDebuggerStatement<'rt> + 'rt>>
+    ) -> Result<'rt, ()> {
         setter!(cx, self, "onDebuggerStatement", on_debugger_statement)
     }
 
     pub fn get_on_new_script(
         &self, 
-        cx: *mut JSContext
-    ) -> Result<Option<Rc<OnNewScript>>> {
+        cx: DebugContext<'rt>
+    ) -> Result<'rt, Option<Rc<OnNewScript<'rt> + 'rt>>> {
         getter!(cx, self, "onNewScript")
     }
 
     pub fn set_on_new_script(
         &self,
-        cx: *mut JSContext,
-        on_new_script: Option<Rc<OnNewScript>>
-    ) -> Result<()> {
+        cx: DebugContext<'rt>,
+        on_new_script: Option<Rc<OnNewScript<'rt> + 'rt>>
+    ) -> Result<'rt, ()> {
         setter!(cx, self, "onNewScript", on_new_script)
     }
+
+    pub fn get_on_enter_frame(
+        &self,
+        cx: DebugContext<'rt>
+    ) -> Result<'rt, Option<Rc<OnEnterFrame<'rt> + 'rt>>> {
+        getter!(cx, self, "onEnterFrame")
+    }
+
+    pub fn set_on_enter_frame(
+        &self,
+        cx: DebugContext<'rt>,
+        on_enter_frame: Option<Rc<OnEnterFrame<'rt> + 'rt>>
+    ) -> Result<'rt, ()> {
+        setter!(cx, self, "onEnterFrame", on_enter_frame)
+    }
+
+    pub fn add_on_enter_frame(
+        &self,
+        cx: DebugContext<'rt>,
+        on_enter_frame: Rc<OnEnterFrame<'rt> + 'rt>
+    ) -> Result<'rt, ()> {
+        let hooks = self.hooks();
+        if hooks.add_on_enter_frame(on_enter_frame) {
+            try!(self.set_on_enter_frame(cx, Some(hooks as Rc<OnEnterFrame<'rt> + 'rt>)));
+        }
+        Ok(())
+    }
+
+    pub fn remove_on_enter_frame(
+        &self,
+        cx: DebugContext<'rt>,
+        on_enter_frame: &Rc<OnEnterFrame<'rt> + 'rt>
+    ) -> Result<'rt, ()> {
+        if self.hooks().remove_on_enter_frame(on_enter_frame) {
+            try!(self.set_on_enter_frame(cx, None));
+        }
+        Ok(())
+    }
+
+    pub fn add_on_step(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>,
+        on_step: Rc<OnStep<'rt> + 'rt>
+    ) -> Result<'rt, ()> {
+        self.hooks().add_on_step(cx, frame, on_step)
+    }
+
+    pub fn remove_on_step(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>,
+        on_step: &Rc<OnStep<'rt> + 'rt>
+    ) -> Result<'rt, ()> {
+        self.hooks().remove_on_step(cx, frame, on_step)
+    }
+
+    pub fn add_on_pop(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>,
+        on_pop: Rc<OnPop<'rt> + 'rt>
+    ) -> Result<'rt, ()> {
+        self.hooks().add_on_pop(cx, frame, on_pop)
+    }
+
+    pub fn remove_on_pop(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>,
+        on_pop: &Rc<OnPop<'rt> + 'rt>
+    ) -> Result<'rt, ()> {
+        self.hooks().remove_on_pop(cx, frame, on_pop)
+    }
+
+    fn hooks(&self) -> Rc<Hooks<'rt>> {
+        if let Some(hooks) = self.1.borrow().upgrade() {
+            return hooks;
+        }
+        let hooks = Hooks::new();
+        *self.1.borrow_mut() = Rc::downgrade(&hooks);
+        hooks
+    }
+
+    pub fn get_on_garbage_collection(
+        &self,
+        cx: DebugContext<'rt>
+    ) -> Result<'rt, Option<Rc<OnGarbageCollection<'rt> + 'rt>>> {
+        try!(self.get_memory(cx)).get_on_garbage_collection(cx)
+    }
+
+    pub fn set_on_garbage_collection(
+        &self,
+        cx: DebugContext<'rt>,
+        on_garbage_collection: Option<Rc<OnGarbageCollection<'rt> + 'rt>>
+    ) -> Result<'rt, ()> {
+        try!(self.get_memory(cx)).set_on_garbage_collection(cx, on_garbage_collection)
+    }
 }
 
-derive_rooted!(*mut JSObject, Debugger);
+derive_rooted!(*mut JSObject, Debugger<'rt>);
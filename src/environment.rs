@@ -1,4 +1,6 @@
-use convert::{FromJSValue, NullOr, ToJSValue};
+use context::DebugContext;
+use convert;
+use convert::{FromJSValue, NullOr, ToJSValue, UndefinedOr};
 use exception::Result;
 use ext::HandleValueArrayExt;
 use js::jsapi::{HandleValue, JSContext, JSObject};
@@ -8,15 +10,30 @@ use trace::TracedBox;
 use utils;
 use value::Value;
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "lowercase"))]
 pub enum EnvironmentType {
     Declarative,
     Object,
-    With
+    With,
+    Other(String)
+}
+
+impl EnvironmentType {
+    pub fn as_str(&self) -> &str {
+        match *self {
+            EnvironmentType::Declarative => "declarative",
+            EnvironmentType::Object => "object",
+            EnvironmentType::With => "with",
+            EnvironmentType::Other(ref string) => string
+        }
+    }
 }
 
 impl FromJSValue for EnvironmentType {
-    unsafe fn from_js_value(cx: *mut JSContext, v: HandleValue) -> Result<Self> {
-        String::from_js_value(cx, v).map(|string| {
+    unsafe fn from_js_value<'rt>(cx: *mut JSContext, v: HandleValue) -> Result<'rt, Self> {
+        convert::with_str(cx, v, |string| {
             if string == "declarative" {
                 EnvironmentType::Declarative
             } else if string == "object" {
@@ -24,73 +41,305 @@ impl FromJSValue for EnvironmentType {
             } else if string == "with" {
                 EnvironmentType::With
             } else {
-                panic!()
+                EnvironmentType::Other(string.to_string())
+            }
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "snake_case"))]
+pub enum ScopeKind {
+    Function,
+    FunctionBodyVar,
+    ParameterExpressionVar,
+    Lexical,
+    Catch,
+    NamedLambda,
+    StrictNamedLambda,
+    FunctionLexical,
+    ClassBody,
+    With,
+    Eval,
+    StrictEval,
+    Global,
+    NonSyntactic,
+    Module,
+    WasmInstance,
+    WasmFunction,
+    Other(String)
+}
+
+impl FromJSValue for ScopeKind {
+    unsafe fn from_js_value<'rt>(cx: *mut JSContext, v: HandleValue) -> Result<'rt, Self> {
+        convert::with_str(cx, v, |string| {
+            match string {
+                "function" => ScopeKind::Function,
+                "function body var" => ScopeKind::FunctionBodyVar,
+                "parameter expression var" => ScopeKind::ParameterExpressionVar,
+                "lexical" => ScopeKind::Lexical,
+                "catch" | "simple catch" => ScopeKind::Catch,
+                "named lambda" => ScopeKind::NamedLambda,
+                "strict named lambda" => ScopeKind::StrictNamedLambda,
+                "function lexical" => ScopeKind::FunctionLexical,
+                "class body" => ScopeKind::ClassBody,
+                "with" => ScopeKind::With,
+                "eval" => ScopeKind::Eval,
+                "strict eval" => ScopeKind::StrictEval,
+                "global" => ScopeKind::Global,
+                "non-syntactic" => ScopeKind::NonSyntactic,
+                "module" => ScopeKind::Module,
+                "wasm instance" => ScopeKind::WasmInstance,
+                "wasm function" => ScopeKind::WasmFunction,
+                _ => ScopeKind::Other(string.to_string())
             }
         })
     }
 }
 
-struct OptimizedOutOrValue(Option<Value>);
+pub enum BindingValue<'rt> {
+    Value(Value<'rt>),
+    OptimizedOut,
+    Uninitialized,
+    MissingArguments
+}
 
-impl OptimizedOutOrValue {
-    pub fn into_option(self) -> Option<Value> {
-        self.0
+impl<'rt> BindingValue<'rt> {
+    pub fn into_option(self) -> Option<Value<'rt>> {
+        match self {
+            BindingValue::Value(value) => Some(value),
+            _ => None
+        }
     }
 }
 
-impl FromJSValue for OptimizedOutOrValue {
-    unsafe fn from_js_value(cx: *mut JSContext, v: HandleValue) -> Result<Self> {
+impl<'rt> FromJSValue for BindingValue<'rt> {
+    unsafe fn from_js_value<'a>(cx: *mut JSContext, v: HandleValue) -> Result<'a, Self> {
         if v.is_object() {
             rooted!(in (cx) let obj = v.to_object());
-            try!(utils::has_property(cx, obj.handle(), "optimizedOut"));
-            if try!(utils::get_property(cx, obj.handle(), "optimizedOut")) {
-                return Ok(OptimizedOutOrValue(None));
+            if try!(utils::has_property(cx, obj.handle(), "optimizedOut")) &&
+               try!(utils::get_property(cx, obj.handle(), "optimizedOut")) {
+                return Ok(BindingValue::OptimizedOut);
+            }
+            if try!(utils::has_property(cx, obj.handle(), "uninitialized")) &&
+               try!(utils::get_property(cx, obj.handle(), "uninitialized")) {
+                return Ok(BindingValue::Uninitialized);
+            }
+            if try!(utils::has_property(cx, obj.handle(), "missingArguments")) &&
+               try!(utils::get_property(cx, obj.handle(), "missingArguments")) {
+                return Ok(BindingValue::MissingArguments);
             }
         }
-        FromJSValue::from_js_value(cx, v).map(|value| OptimizedOutOrValue(Some(value)))
+        FromJSValue::from_js_value(cx, v).map(|value| BindingValue::Value(value))
     }
 }
 
-pub struct Environment(TracedBox<*mut JSObject>);
+pub struct Binding<'rt> {
+    pub name: String,
+    pub value: BindingValue<'rt>
+}
+
+pub struct ScopeSnapshot<'rt> {
+    pub environment: Environment<'rt>,
+    pub inspectable: bool,
+    pub environment_type: Option<EnvironmentType>,
+    pub scope_kind: Option<ScopeKind>,
+    pub callee_name: Option<String>,
+    pub bindings: Vec<Binding<'rt>>
+}
+
+pub struct Environment<'rt>(TracedBox<'rt, *mut JSObject>);
 
-impl Environment {
-    pub fn new(cx: *mut JSContext, environment: *mut JSObject) -> Environment {
+impl<'rt> Environment<'rt> {
+    pub fn new(cx: DebugContext<'rt>, environment: *mut JSObject) -> Environment<'rt> {
         Environment(TracedBox::new(cx, environment))
     }
 
-    pub fn get_is_inspectable(&self, cx: *mut JSContext) -> Result<bool> {
+    pub fn get_type(&self, cx: DebugContext<'rt>) -> Result<'rt, EnvironmentType> {
+        getter!(cx, self, "type")
+    }
+
+    pub fn get_callee(&self, cx: DebugContext<'rt>) -> Result<'rt, Option<Object<'rt>>> {
+        getter!(cx, self, "callee").map(|callee| NullOr::<Object<'rt>>::into_option(callee))
+    }
+
+    pub fn get_scope_kind(&self, cx: DebugContext<'rt>) -> Result<'rt, Option<ScopeKind>> {
+        getter!(cx, self, "scopeKind").map(|kind| UndefinedOr::<ScopeKind>::into_option(kind))
+    }
+
+    pub fn get_is_module(&self, cx: DebugContext<'rt>) -> Result<'rt, bool> {
+        self.get_scope_kind(cx).map(|kind| kind == Some(ScopeKind::Module))
+    }
+
+    pub fn get_is_inspectable(&self, cx: DebugContext<'rt>) -> Result<'rt, bool> {
         getter!(cx, self, "inspectable")
     }
 
-    pub fn get_is_optimized_out(&self, cx: *mut JSContext) -> Result<bool> {
+    pub fn get_is_optimized_out(&self, cx: DebugContext<'rt>) -> Result<'rt, bool> {
         getter!(cx, self, "optimizedOut")
     }
 
-    pub fn get_parent(&self, cx: *mut JSContext) -> Result<Option<Environment>> {
+    pub fn get_parent(&self, cx: DebugContext<'rt>) -> Result<'rt, Option<Environment<'rt>>> {
         getter!(cx, self, "parent").map(|environment| {
-            NullOr::<Environment>::into_option(environment)
+            NullOr::<Environment<'rt>>::into_option(environment)
         })
     }
 
-    pub fn get_object(&self, cx: *mut JSContext) -> Result<Object> {
+    pub fn get_object(&self, cx: DebugContext<'rt>) -> Result<'rt, Object<'rt>> {
         getter!(cx, self, "object")
     }
 
-    pub fn names(&self, cx: *mut JSContext) -> Result<Vec<String>> {
+    pub fn find(&self, cx: DebugContext<'rt>, name: &str) -> Result<'rt, Option<Environment<'rt>>> {
+        method!(cx, self, "find", name).map(|environment| {
+            NullOr::<Environment<'rt>>::into_option(environment)
+        })
+    }
+
+    pub fn names(&self, cx: DebugContext<'rt>) -> Result<'rt, Vec<String>> {
         method!(cx, self, "names")
     }
 
-    pub fn get_variable(&self, cx: *mut JSContext, name: &str) -> Result<Option<Value>> {
-        method!(cx, self, "getVariable", name).map(|value| {
-            OptimizedOutOrValue::into_option(value)
-        })
+    pub fn get_variable(
+        &self,
+        cx: DebugContext<'rt>,
+        name: &str
+    ) -> Result<'rt, Option<Value<'rt>>> {
+        self.get_binding(cx, name).map(|value| value.into_option())
+    }
+
+    pub fn get_binding(&self, cx: DebugContext<'rt>, name: &str) -> Result<'rt, BindingValue<'rt>> {
+        method!(cx, self, "getVariable", name)
     }
 
-    pub fn set_variable(&self, cx: *mut JSContext, name: &str, value: &Value) -> Result<()> {
+    pub fn set_variable(
+        &self,
+        cx: DebugContext<'rt>,
+        name: &str,
+        value: &Value<'rt>
+    ) -> Result<'rt, ()> {
         method!(cx, self, "setVariable", name, value)
     }
+
+    pub fn scope_chain(&self, cx: DebugContext<'rt>) -> Result<'rt, Vec<ScopeSnapshot<'rt>>> {
+        let mut scopes = Vec::new();
+        let mut current = Some(Environment::new(cx, self.get()));
+        while let Some(environment) = current {
+            if !try!(environment.get_is_inspectable(cx)) {
+                scopes.push(ScopeSnapshot {
+                    environment: environment,
+                    inspectable: false,
+                    environment_type: None,
+                    scope_kind: None,
+                    callee_name: None,
+                    bindings: Vec::new()
+                });
+                break;
+            }
+            let callee_name = match try!(environment.get_callee(cx)) {
+                Some(callee) => try!(callee.get_name(cx)),
+                None => None
+            };
+            let mut bindings = Vec::new();
+            for name in try!(environment.names(cx)) {
+                let value = try!(environment.get_binding(cx, &name));
+                bindings.push(Binding {
+                    name: name,
+                    value: value
+                });
+            }
+            current = try!(environment.get_parent(cx));
+            scopes.push(ScopeSnapshot {
+                inspectable: true,
+                environment_type: Some(try!(environment.get_type(cx))),
+                scope_kind: try!(environment.get_scope_kind(cx)),
+                callee_name: callee_name,
+                bindings: bindings,
+                environment: environment
+            });
+        }
+        Ok(scopes)
+    }
 }
 
-derive_rooted!(*mut JSObject, Environment);
+derive_rooted!(*mut JSObject, Environment<'rt>);
+
+derive_convert!(Environment<'rt>);
+
+#[cfg(test)]
+mod tests {
+    use super::{BindingValue, EnvironmentType};
+    use context::DebugContext;
+    use debugger::OnDebuggerStatement;
+    use exception::Result;
+    use frame::Frame;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use utils;
+    use value::{ResumptionValue, Value};
+
+    struct Scopes(RefCell<Vec<String>>);
+
+    impl<'rt> OnDebuggerStatement<'rt> for Scopes {
+        fn on_debugger_statement(
+            &self,
+            cx: DebugContext<'rt>,
+            frame: &Frame<'rt>
+        ) -> Result<'rt, ResumptionValue<'rt>> {
+            let environment = try!(frame.get_environment(cx)).unwrap();
+            let mut lines = self.0.borrow_mut();
+            for scope in try!(environment.scope_chain(cx)) {
+                let mut line = format!(
+                    "{} {}",
+                    scope.environment_type.as_ref().map_or("-", |kind| kind.as_str()),
+                    scope.callee_name.unwrap_or_default()
+                );
+                for binding in scope.bindings {
+                    match binding.value {
+                        BindingValue::Value(Value::Int32(value)) => {
+                            line.push_str(&format!(" {}={}", binding.name, value));
+                        }
+                        _ => ()
+                    }
+                }
+                lines.push(line.trim().to_string());
+            }
+            for name in &["a", "b", "c"] {
+                let found = match try!(environment.find(cx, name)) {
+                    Some(found) => try!(found.names(cx)).iter().any(|known| known == name),
+                    None => false
+                };
+                lines.push(format!("{}:{}", name, found));
+            }
+            assert_eq!(try!(environment.get_type(cx)), EnvironmentType::Declarative);
+            Ok(None)
+        }
+    }
 
-derive_convert!(Environment);
+    #[test]
+    fn scope_chain() {
+        utils::with_debuggee(|cx, debugger, evaluate| {
+            let scopes = Rc::new(Scopes(RefCell::new(Vec::new())));
+            debugger.set_on_debugger_statement(cx, Some(scopes.clone())).unwrap();
+            evaluate(concat!(
+                "function outer(a) {\n",
+                "    let b = 2;\n",
+                "    function inner() {\n",
+                "        debugger;\n",
+                "        return a + b;\n",
+                "    }\n",
+                "    return inner();\n",
+                "}\n",
+                "outer(1);\n"
+            ));
+            let lines = scopes.0.borrow();
+            assert_eq!(lines[0], "declarative inner");
+            assert!(lines.iter().any(|line| line.starts_with("declarative") &&
+                                            line.contains(" b=2")));
+            assert!(lines.iter().any(|line| line.starts_with("declarative outer") &&
+                                            line.contains(" a=1")));
+            assert!(lines.iter().any(|line| line.starts_with("object")));
+            assert_eq!(&lines[lines.len() - 3..], &["a:true", "b:true", "c:false"]);
+        });
+    }
+}
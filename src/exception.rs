@@ -1,3 +1,4 @@
+use context::DebugContext;
 use js::jsapi;
 use js::jsapi::{JSContext, Value};
 use js::jsval;
@@ -7,14 +8,14 @@ use std::fmt;
 use std::fmt::{Debug, Formatter};
 use std::result;
 
-pub struct Exception(TracedBox<Value>);
+pub struct Exception<'rt>(TracedBox<'rt, Value>);
 
-impl Exception {
-    pub unsafe fn from_pending_exception(cx: *mut JSContext) -> Exception {
+impl<'rt> Exception<'rt> {
+    pub unsafe fn from_pending_exception(cx: *mut JSContext) -> Exception<'rt> {
         rooted!(in (cx) let mut v = jsval::UndefinedValue());
         assert!(jsapi::JS_GetPendingException(cx, v.handle_mut()));
         jsapi::JS_ClearPendingException(cx);
-        Exception(TracedBox::new(cx, v.get()))
+        Exception(TracedBox::new(DebugContext::from_raw(cx), v.get()))
     }
 
     pub unsafe fn into_pending_exception(self, cx: *mut JSContext) -> bool {
@@ -23,12 +24,12 @@ impl Exception {
     }
 }
 
-derive_rooted!(Value, Exception);
+derive_rooted!(Value, Exception<'rt>);
 
-impl Debug for Exception {
+impl<'rt> Debug for Exception<'rt> {
     fn fmt(&self, _: &mut Formatter) -> fmt::Result {
         unimplemented!();
     }
 }
 
-pub type Result<T> = result::Result<T, Exception>;
+pub type Result<'rt, T> = result::Result<T, Exception<'rt>>;
@@ -1,31 +1,39 @@
 use call::Call;
+use context::DebugContext;
+use convert;
 use convert::{FromJSValue, NullOr, ToJSValue};
 use environment::Environment;
 use exception::Result;
+use ext::HandleValueArrayExt;
 use js::jsapi;
-use js::jsapi::{CallArgs, HandleValue, JSAutoCompartment, JSContext, JSObject};
+use js::jsapi::{CallArgs, HandleValue, JSContext, JSObject};
 use object::Object;
 use rooted::Rooted;
 use script::Script;
+use std::any::TypeId;
 use std::rc::Rc;
 use trace::TracedBox;
 use utils;
 use value::{CompletionValue, ResumptionValue, Value};
 
-pub trait OnPop {
+pub trait OnPop<'rt> {
     fn on_pop(
         &self,
-        cx: *mut JSContext,
-        frame: &Frame,
-        value: &CompletionValue
-    ) -> Result<ResumptionValue>;
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>,
+        value: &CompletionValue<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>>;
 }
 
-impl Call for OnPop {
+impl<'rt> Call for OnPop<'rt> + 'rt {
+    fn hook_type() -> TypeId {
+        TypeId::of::<OnPop<'static> + 'static>()
+    }
+
     unsafe fn call(&self, cx: *mut JSContext, argc: u32, vp: *mut jsapi::Value) -> bool {
         let args = CallArgs::from_vp(vp, argc);
         match self.on_pop(
-            cx,
+            DebugContext::from_raw(cx),
             &Frame::from_js_value(cx, args.get(0)).unwrap(),
             &CompletionValue::from_js_value(cx, args.get(1)).unwrap()
         ) {
@@ -35,20 +43,31 @@ impl Call for OnPop {
     }
 }
 
-pub trait OnStep {
-    fn on_step(&self, cx: *mut JSContext, frame: &Frame) -> Result<ResumptionValue>;
+pub trait OnStep<'rt> {
+    fn on_step(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>>;
 }
 
-impl Call for OnStep {
+impl<'rt> Call for OnStep<'rt> + 'rt {
+    fn hook_type() -> TypeId {
+        TypeId::of::<OnStep<'static> + 'static>()
+    }
+
     unsafe fn call(&self, cx: *mut JSContext, argc: u32, vp: *mut jsapi::Value) -> bool {
         let args = CallArgs::from_vp(vp, argc);
-        match self.on_step(cx, &Frame::from_js_value(cx, args.get(0)).unwrap()) {
+        let frame = Frame::from_js_value(cx, args.get(0)).unwrap();
+        match self.on_step(DebugContext::from_raw(cx), &frame) {
             Ok(result) => result.to_js_value(cx, args.rval()),
             Err(exception) => exception.into_pending_exception(cx)
         }
     }
 }
 
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "lowercase"))]
 pub enum FrameType {
     Call,
     Eval,
@@ -57,8 +76,8 @@ pub enum FrameType {
 }
 
 impl FromJSValue for FrameType {
-    unsafe fn from_js_value(cx: *mut JSContext, v: HandleValue) -> Result<Self> {
-        String::from_js_value(cx, v).map(|string| {
+    unsafe fn from_js_value<'rt>(cx: *mut JSContext, v: HandleValue) -> Result<'rt, Self> {
+        convert::with_str(cx, v, |string| {
             if string == "call" {
                 FrameType::Call
             } else if string == "eval" {
@@ -74,6 +93,8 @@ impl FromJSValue for FrameType {
     }
 }
 
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "lowercase"))]
 pub enum FrameImplementation {
     Interpreter,
     Baseline,
@@ -81,8 +102,8 @@ pub enum FrameImplementation {
 }
 
 impl FromJSValue for FrameImplementation {
-    unsafe fn from_js_value(cx: *mut JSContext, v: HandleValue) -> Result<Self> {
-        String::from_js_value(cx, v).map(|string| {
+    unsafe fn from_js_value<'rt>(cx: *mut JSContext, v: HandleValue) -> Result<'rt, Self> {
+        convert::with_str(cx, v, |string| {
             if string == "interpreter" {
                 FrameImplementation::Interpreter
             } else if string == "baseline" {
@@ -96,113 +117,167 @@ impl FromJSValue for FrameImplementation {
     }
 }
 
-pub struct Arguments(TracedBox<*mut JSObject>);
+pub struct Arguments<'rt>(TracedBox<'rt, *mut JSObject>);
 
-impl Arguments {
-    pub fn new(cx: *mut JSContext, arguments: *mut JSObject) -> Arguments {
+impl<'rt> Arguments<'rt> {
+    pub fn new(cx: DebugContext<'rt>, arguments: *mut JSObject) -> Arguments<'rt> {
         Arguments(TracedBox::new(cx, arguments))
     }
 
-    pub fn get_length(&self, cx: *mut JSContext) -> Result<usize> {
+    pub fn get_length(&self, cx: DebugContext<'rt>) -> Result<'rt, usize> {
         getter!(cx, self, "length").map(|length: u32| length as usize)
     }
 
-    pub fn get_element(&self, cx: *mut JSContext, index: usize) -> Result<Value> {
+    pub fn get_element(&self, cx: DebugContext<'rt>, index: usize) -> Result<'rt, Value<'rt>> {
         unsafe {
-            let _ac = JSAutoCompartment::new(cx, self.get());
+            let _ac = enter_compartment!(cx, self);
+            let cx = cx.as_raw();
             utils::get_element(cx, self.handle(), index)
         }
     }
 
-    pub fn set_element(&self, cx: *mut JSContext, index: usize, value: &Value) -> Result<()> {
+    pub fn set_element(
+        &self,
+        cx: DebugContext<'rt>,
+        index: usize,
+        value: &Value<'rt>
+    ) -> Result<'rt, ()> {
         unsafe {
-            let _ac = JSAutoCompartment::new(cx, self.get());
+            let _ac = enter_compartment!(cx, self);
+            let cx = cx.as_raw();
             try_jsapi!(cx, utils::set_element(cx, self.handle(), index, value));
             Ok(())
         }
     }
 }
 
-derive_rooted!(*mut JSObject, Arguments);
+derive_rooted!(*mut JSObject, Arguments<'rt>);
 
-derive_convert!(Arguments);
+derive_convert!(Arguments<'rt>);
 
-pub struct Frame(TracedBox<*mut JSObject>);
+pub struct Frame<'rt>(TracedBox<'rt, *mut JSObject>);
 
-impl Frame {
-    pub fn new(cx: *mut JSContext, frame: *mut JSObject) -> Frame {
+impl<'rt> Frame<'rt> {
+    pub fn new(cx: DebugContext<'rt>, frame: *mut JSObject) -> Frame<'rt> {
         Frame(TracedBox::new(cx, frame))
     }
 
-    pub fn get_is_live(&self, cx: *mut JSContext) -> Result<bool> {
+    pub fn get_is_live(&self, cx: DebugContext<'rt>) -> Result<'rt, bool> {
         getter!(cx, self, "live")
     }
 
-    pub fn get_is_constructing(&self, cx: *mut JSContext) -> Result<bool> {
+    pub fn get_is_constructing(&self, cx: DebugContext<'rt>) -> Result<'rt, bool> {
         getter!(cx, self, "constructing")
     }
 
-    pub fn get_type(&self, cx: *mut JSContext) -> Result<FrameType> {
+    pub fn get_type(&self, cx: DebugContext<'rt>) -> Result<'rt, FrameType> {
         getter!(cx, self, "type")
     }
 
-    pub fn get_implementation(&self, cx: *mut JSContext) -> Result<FrameImplementation> {
+    pub fn get_implementation(&self, cx: DebugContext<'rt>) -> Result<'rt, FrameImplementation> {
         getter!(cx, self, "implementation")
     }
 
-    pub fn get_depth(&self, cx: *mut JSContext) -> Result<usize> {
+    pub fn get_depth(&self, cx: DebugContext<'rt>) -> Result<'rt, usize> {
         getter!(cx, self, "depth").map(|depth: u32| depth as usize)
     }
 
-    pub fn get_older(&self, cx: *mut JSContext) -> Result<Option<Frame>> {
-        getter!(cx, self, "older").map(|older| NullOr::<Frame>::into_option(older))
+    pub fn get_older(&self, cx: DebugContext<'rt>) -> Result<'rt, Option<Frame<'rt>>> {
+        getter!(cx, self, "older").map(|older| NullOr::<Frame<'rt>>::into_option(older))
+    }
+
+    pub fn get_callee(&self, cx: DebugContext<'rt>) -> Result<'rt, Option<Object<'rt>>> {
+        getter!(cx, self, "callee").map(|callee| NullOr::<Object<'rt>>::into_option(callee))
     }
 
-    pub fn get_callee(&self, cx: *mut JSContext) -> Result<Option<Object>> {
-        getter!(cx, self, "callee").map(|callee| NullOr::<Object>::into_option(callee))
+    pub fn get_function_name(&self, cx: DebugContext<'rt>) -> Result<'rt, String> {
+        Ok(match try!(self.get_type(cx)) {
+            FrameType::Call => {
+                let name = match try!(self.get_callee(cx)) {
+                    Some(callee) => try!(callee.get_name(cx)),
+                    None => None
+                };
+                name.unwrap_or("(anonymous)".to_string())
+            }
+            FrameType::Eval => "(eval)".to_string(),
+            FrameType::Global => "(global)".to_string(),
+            FrameType::Module => "(module)".to_string()
+        })
     }
 
-    pub fn get_this(&self, cx: *mut JSContext) -> Result<Value> {
+    pub fn get_this(&self, cx: DebugContext<'rt>) -> Result<'rt, Value<'rt>> {
         getter!(cx, self, "this")
     }
 
-    pub fn get_arguments(&self, cx: *mut JSContext) -> Result<Option<Arguments>> {
+    pub fn get_arguments(&self, cx: DebugContext<'rt>) -> Result<'rt, Option<Arguments<'rt>>> {
         getter!(cx, self, "arguments").map(|arguments| {
-            NullOr::<Arguments>::into_option(arguments)
+            NullOr::<Arguments<'rt>>::into_option(arguments)
         })
     }
 
-    pub fn get_script(&self, cx: *mut JSContext) -> Result<Option<Script>> {
-        getter!(cx, self, "script").map(|script| NullOr::<Script>::into_option(script))
+    pub fn get_script(&self, cx: DebugContext<'rt>) -> Result<'rt, Option<Script<'rt>>> {
+        getter!(cx, self, "script").map(|script| NullOr::<Script<'rt>>::into_option(script))
     }
 
-    pub fn get_offset(&self, cx: *mut JSContext) -> Result<usize> {
+    pub fn get_offset(&self, cx: DebugContext<'rt>) -> Result<'rt, usize> {
         getter!(cx, self, "offset").map(|offset: u32| offset as usize)
     }
 
-    pub fn get_environment(&self, cx: *mut JSContext) -> Result<Option<Environment>> {
+    pub fn get_source_line(&self, cx: DebugContext<'rt>) -> Result<'rt, Option<(usize, String)>> {
+        self.get_source_context(cx, 0).map(|mut lines| lines.pop())
+    }
+
+    pub fn get_source_context(
+        &self,
+        cx: DebugContext<'rt>,
+        radius: usize
+    ) -> Result<'rt, Vec<(usize, String)>> {
+        let script = match try!(self.get_script(cx)) {
+            Some(script) => script,
+            None => return Ok(Vec::new())
+        };
+        let line = try!(script.get_offset_location(cx, try!(self.get_offset(cx)))).line_number;
+        let text = try!(script.get_source_text(cx));
+        Ok(text.context(line, radius).into_iter().map(|(line, text)| {
+            (line, text.to_string())
+        }).collect())
+    }
+
+    pub fn get_environment(&self, cx: DebugContext<'rt>) -> Result<'rt, Option<Environment<'rt>>> {
         getter!(cx, self, "environment").map(|environment| {
-            NullOr::<Environment>::into_option(environment)
+            NullOr::<Environment<'rt>>::into_option(environment)
         })
     }
 
-    pub fn get_on_pop(&self, cx: *mut JSContext) -> Result<Option<Rc<OnPop>>> {
+    pub fn eval(&self, cx: DebugContext<'rt>, code: &str) -> Result<'rt, CompletionValue<'rt>> {
+        method!(cx, self, "eval", code)
+    }
+
+    pub fn get_on_pop(&self, cx: DebugContext<'rt>) -> Result<'rt, Option<Rc<OnPop<'rt> + 'rt>>> {
         getter!(cx, self, "onPop")
     }
 
-    pub fn set_on_pop(&self, cx: *mut JSContext, on_pop: Option<Rc<OnPop>>) -> Result<()> {
+    pub fn set_on_pop(
+        &self,
+        cx: DebugContext<'rt>,
+        on_pop: Option<Rc<OnPop<'rt> + 'rt>>
+    ) -> Result<'rt, ()> {
         setter!(cx, self, "onPop", on_pop)
     }
 
-    pub fn get_on_step(&self, cx: *mut JSContext) -> Result<Option<Rc<OnStep>>> {
+    pub fn get_on_step(&self, cx: DebugContext<'rt>) -> Result<'rt, Option<Rc<OnStep<'rt> + 'rt>>> {
         getter!(cx, self, "onStep")
     }
 
-    pub fn set_on_step(&self, cx: *mut JSContext, on_step: Option<Rc<OnStep>>) -> Result<()> {
+    pub fn set_on_step(
+        &self,
+        cx: DebugContext<'rt>,
+        on_step: Option<Rc<OnStep<'rt> + 'rt>>
+    ) -> Result<'rt, ()> {
         setter!(cx, self, "onStep", on_step)
     }
 }
 
-derive_rooted!(*mut JSObject, Frame);
+derive_rooted!(*mut JSObject, Frame<'rt>);
 
-derive_convert!(Frame);
+derive_convert!(Frame<'rt>);
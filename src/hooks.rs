@@ -0,0 +1,218 @@
+use context::DebugContext;
+use debugger::OnEnterFrame;
+use exception::Result;
+use frame::{Frame, OnPop, OnStep};
+use rooted::Rooted;
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+use value::{CompletionValue, ResumptionValue};
+
+struct FrameHooks<'rt> {
+    frame: Frame<'rt>,
+    on_step: Vec<Rc<OnStep<'rt> + 'rt>>,
+    on_pop: Vec<Rc<OnPop<'rt> + 'rt>>
+}
+
+pub struct Hooks<'rt> {
+    this: RefCell<Weak<Hooks<'rt>>>,
+    on_enter_frame: RefCell<Vec<Rc<OnEnterFrame<'rt> + 'rt>>>,
+    frames: RefCell<Vec<FrameHooks<'rt>>>
+}
+
+impl<'rt> Hooks<'rt> {
+    pub fn new() -> Rc<Hooks<'rt>> {
+        let hooks = Rc::new(Hooks {
+            this: RefCell::new(Weak::new()),
+            on_enter_frame: RefCell::new(Vec::new()),
+            frames: RefCell::new(Vec::new())
+        });
+        *hooks.this.borrow_mut() = Rc::downgrade(&hooks);
+        hooks
+    }
+
+    fn this(&self) -> Rc<Hooks<'rt>> {
+        self.this.borrow().upgrade().unwrap()
+    }
+
+    pub fn add_on_enter_frame(&self, hook: Rc<OnEnterFrame<'rt> + 'rt>) -> bool {
+        let mut hooks = self.on_enter_frame.borrow_mut();
+        hooks.push(hook);
+        hooks.len() == 1
+    }
+
+    pub fn remove_on_enter_frame(&self, hook: &Rc<OnEnterFrame<'rt> + 'rt>) -> bool {
+        let mut hooks = self.on_enter_frame.borrow_mut();
+        let length = hooks.len();
+        hooks.retain(|known| !same(known, hook));
+        length != 0 && hooks.is_empty()
+    }
+
+    pub fn add_on_step(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>,
+        hook: Rc<OnStep<'rt> + 'rt>
+    ) -> Result<'rt, ()> {
+        let install = self.with_frame(cx, frame, |hooks| {
+            hooks.on_step.push(hook);
+            hooks.on_step.len() == 1
+        });
+        if install {
+            try!(frame.set_on_step(cx, Some(self.this() as Rc<OnStep<'rt> + 'rt>)));
+        }
+        Ok(())
+    }
+
+    pub fn remove_on_step(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>,
+        hook: &Rc<OnStep<'rt> + 'rt>
+    ) -> Result<'rt, ()> {
+        let uninstall = self.with_frame(cx, frame, |hooks| {
+            let length = hooks.on_step.len();
+            hooks.on_step.retain(|known| !same(known, hook));
+            length != 0 && hooks.on_step.is_empty()
+        });
+        self.prune();
+        if uninstall && try!(frame.get_is_live(cx)) {
+            try!(frame.set_on_step(cx, None));
+        }
+        Ok(())
+    }
+
+    pub fn add_on_pop(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>,
+        hook: Rc<OnPop<'rt> + 'rt>
+    ) -> Result<'rt, ()> {
+        let install = self.with_frame(cx, frame, |hooks| {
+            hooks.on_pop.push(hook);
+            hooks.on_pop.len() == 1
+        });
+        if install {
+            try!(frame.set_on_pop(cx, Some(self.this() as Rc<OnPop<'rt> + 'rt>)));
+        }
+        Ok(())
+    }
+
+    pub fn remove_on_pop(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>,
+        hook: &Rc<OnPop<'rt> + 'rt>
+    ) -> Result<'rt, ()> {
+        let uninstall = self.with_frame(cx, frame, |hooks| {
+            let length = hooks.on_pop.len();
+            hooks.on_pop.retain(|known| !same(known, hook));
+            length != 0 && hooks.on_pop.is_empty()
+        });
+        self.prune();
+        if uninstall && try!(frame.get_is_live(cx)) {
+            try!(frame.set_on_pop(cx, None));
+        }
+        Ok(())
+    }
+
+    fn with_frame<F: FnOnce(&mut FrameHooks<'rt>) -> R, R>(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>,
+        f: F
+    ) -> R {
+        let mut frames = self.frames.borrow_mut();
+        let index = match frames.iter().position(|hooks| hooks.frame.get() == frame.get()) {
+            Some(index) => index,
+            None => {
+                frames.push(FrameHooks {
+                    frame: Frame::new(cx, frame.get()),
+                    on_step: Vec::new(),
+                    on_pop: Vec::new()
+                });
+                frames.len() - 1
+            }
+        };
+        f(&mut frames[index])
+    }
+
+    fn prune(&self) {
+        self.frames.borrow_mut().retain(|hooks| {
+            !hooks.on_step.is_empty() || !hooks.on_pop.is_empty()
+        });
+    }
+
+    fn frame_hooks<T, F>(&self, frame: &Frame<'rt>, f: F) -> Vec<T>
+        where F: Fn(&FrameHooks<'rt>) -> Vec<T>
+    {
+        self.frames.borrow().iter().find(|hooks| hooks.frame.get() == frame.get()).map(f)
+            .unwrap_or(Vec::new())
+    }
+}
+
+impl<'rt> OnEnterFrame<'rt> for Hooks<'rt> {
+    fn on_enter_frame(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        let mut resumption = None;
+        let hooks = self.on_enter_frame.borrow().clone();
+        for hook in hooks {
+            if !self.on_enter_frame.borrow().iter().any(|known| same(known, &hook)) {
+                continue;
+            }
+            let value = try!(hook.on_enter_frame(cx, frame));
+            if resumption.is_none() {
+                resumption = value;
+            }
+        }
+        Ok(resumption)
+    }
+}
+
+impl<'rt> OnStep<'rt> for Hooks<'rt> {
+    fn on_step(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        let mut resumption = None;
+        for hook in self.frame_hooks(frame, |hooks| hooks.on_step.clone()) {
+            if !self.frame_hooks(frame, |hooks| hooks.on_step.clone()).iter().any(|known| {
+                same(known, &hook)
+            }) {
+                continue;
+            }
+            let value = try!(hook.on_step(cx, frame));
+            if resumption.is_none() {
+                resumption = value;
+            }
+        }
+        Ok(resumption)
+    }
+}
+
+impl<'rt> OnPop<'rt> for Hooks<'rt> {
+    fn on_pop(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>,
+        value: &CompletionValue<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        let hooks = self.frame_hooks(frame, |hooks| hooks.on_pop.clone());
+        self.frames.borrow_mut().retain(|hooks| hooks.frame.get() != frame.get());
+        let mut resumption = None;
+        for hook in hooks {
+            let value = try!(hook.on_pop(cx, frame, value));
+            if resumption.is_none() {
+                resumption = value;
+            }
+        }
+        Ok(resumption)
+    }
+}
+
+fn same<T: ?Sized>(a: &Rc<T>, b: &Rc<T>) -> bool {
+    &**a as *const T as *const u8 == &**b as *const T as *const u8
+}
@@ -1,5 +1,17 @@
 #[macro_use]
 extern crate js;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde_derive")]
+#[macro_use]
+extern crate serde_derive;
+#[cfg(feature = "serde_json")]
+#[macro_use]
+extern crate serde_json;
+#[cfg(feature = "tungstenite")]
+extern crate tungstenite;
+#[cfg(all(test, feature = "cdp"))]
+extern crate url;
 
 #[macro_use]
 mod macros;
@@ -7,36 +19,88 @@ mod macros;
 mod call;
 mod convert;
 mod ext;
+mod hooks;
 mod rooted;
 mod trace;
 mod utils;
 
+pub mod breakpoint;
+#[cfg(feature = "calltrace")]
+pub mod call_trace;
+#[cfg(feature = "cdp")]
+pub mod cdp;
+pub mod context;
+#[cfg(feature = "coverage")]
+pub mod coverage;
+#[cfg(feature = "dap")]
+pub mod dap;
 pub mod debugger;
 pub mod environment;
 pub mod exception;
 pub mod frame;
+pub mod memory;
 pub mod object;
+pub mod pause;
+pub mod preview;
+pub mod profiler;
+#[cfg(feature = "rdp")]
+pub mod rdp;
+#[cfg(feature = "reachabilitygraph")]
+pub mod reachability_graph;
+pub mod registry;
+pub mod saved_frame;
 pub mod script;
+#[cfg(feature = "serialize")]
+pub mod serialize;
+pub mod session;
+pub mod snapshot;
 pub mod source;
+#[cfg(feature = "sourcemap")]
+pub mod source_map;
+pub mod stepper;
 pub mod value;
 
-pub use debugger::{Debugger, OnDebuggerStatement, OnNewScript};
+pub use breakpoint::{BreakpointOptions, HitCount, LogMessage, OnLog};
+pub use context::DebugContext;
+pub use debugger::{Debugger, OnDebuggerStatement, OnEnterFrame, OnNewScript};
 pub use exception::{Exception, Result};
-pub use environment::Environment;
+pub use environment::{
+    Binding,
+    BindingValue,
+    Environment,
+    EnvironmentType,
+    ScopeKind,
+    ScopeSnapshot
+};
 pub use frame::{Arguments, Frame};
-pub use object::{Object, PropertyDescriptor};
-pub use source::Source;
-pub use script::Script;
+pub use memory::{
+    AllocationLogEntry,
+    Breakdown,
+    Census,
+    GcSlice,
+    GcStatistics,
+    Memory,
+    OnGarbageCollection
+};
+pub use object::{Object, PropertyDescriptor, WeakObject};
+pub use pause::{EventLoop, PauseController};
+pub use preview::{Preview, PreviewOptions};
+pub use registry::{Handle, HandleRegistry, Lifetime};
+pub use saved_frame::SavedFrame;
+pub use source::{IntroductionType, Source, SourceText};
+pub use script::{Script, WeakScript};
+pub use stepper::{OnStepComplete, StepKind, Stepper};
+pub use utils::new_global_object;
 pub use value::{CompletionValue, ResumptionValue, Value};
 
 #[cfg(test)]
 mod tests {
     #[test]
     fn it_works() {
+        use context::DebugContext;
         use debugger::{Debugger, OnDebuggerStatement, OnNewScript};
         use exception::Result;
         use frame::Frame;
-        use js::jsapi::JSContext;
         use js::jsval::UndefinedValue;
         use js::rust::Runtime;
         use script::Script;
@@ -47,8 +111,12 @@ mod tests {
 
         struct Handler;
 
-        impl OnNewScript for Handler {
-            fn on_new_script(&self, cx: *mut JSContext, script: &Script) -> Result<()> {
+        impl<'rt> OnNewScript<'rt> for Handler {
+            fn on_new_script(
+                &self,
+                cx: DebugContext<'rt>,
+                script: &Script<'rt>
+            ) -> Result<'rt, ()> {
                 let source = try!(script.get_source(cx));
                 let text = try!(source.get_text(cx));
                 println!("{}", text);
@@ -56,12 +124,12 @@ mod tests {
             }
         }
 
-        impl OnDebuggerStatement for Handler {
+        impl<'rt> OnDebuggerStatement<'rt> for Handler {
             fn on_debugger_statement(
                 &self,
-                cx: *mut JSContext,
-                frame: &Frame
-            ) -> Result<ResumptionValue> {
+                cx: DebugContext<'rt>,
+                frame: &Frame<'rt>
+            ) -> Result<'rt, ResumptionValue<'rt>> {
                 let arguments = try!(frame.get_arguments(cx)).unwrap();
                 let argument = try!(arguments.get_element(cx, 0));
                 match argument {
@@ -77,22 +145,24 @@ mod tests {
         }
 
         let runtime = Runtime::new();
-        rooted!(in (runtime.cx()) let mut global = ptr::null_mut());
-        unsafe {
-            assert!(utils::new_global_object(runtime.cx(), global.handle_mut()));
-        }
-        let debugger = Debugger::new(runtime.cx());
-        debugger.add_debuggee(runtime.cx(), global.handle()).unwrap();
-        let handler = Rc::new(Handler);
-        debugger.set_on_new_script(runtime.cx(), Some(handler.clone())).unwrap();
-        debugger.set_on_debugger_statement(runtime.cx(), Some(handler.clone())).unwrap();
-        rooted!(in (runtime.cx()) let mut rval = UndefinedValue());
-        runtime.evaluate_script(global.handle(), r#"
-            function f(x) {
-                debugger;
+        DebugContext::with(&runtime, |cx| {
+            rooted!(in (runtime.cx()) let mut global = ptr::null_mut());
+            unsafe {
+                assert!(utils::new_global_object(runtime.cx(), global.handle_mut()));
             }
+            let debugger = Debugger::new(cx);
+            debugger.add_debuggee(cx, global.handle()).unwrap();
+            let handler = Rc::new(Handler);
+            debugger.set_on_new_script(cx, Some(handler.clone())).unwrap();
+            debugger.set_on_debugger_statement(cx, Some(handler.clone())).unwrap();
+            rooted!(in (runtime.cx()) let mut rval = UndefinedValue());
+            runtime.evaluate_script(global.handle(), r#"
+                function f(x) {
+                    debugger;
+                }
 
-            f("TEST");
-        "#, "test", 0, rval.handle_mut()).unwrap();
+                f("TEST");
+            "#, "test", 0, rval.handle_mut()).unwrap();
+        });
     }
 }
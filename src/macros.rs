@@ -6,7 +6,7 @@ macro_rules! c_str {
 
 macro_rules! derive_rooted {
     ($T:ty, $U:ty) => {
-        impl ::rooted::Rooted<$T> for $U {
+        impl<'rt> ::rooted::Rooted<$T> for $U {
             fn get(&self) -> $T {
                 self.0.get()
             }
@@ -18,22 +18,28 @@ macro_rules! derive_rooted {
             fn handle_mut(&mut self) -> ::js::jsapi::MutableHandle<$T> {
                 self.0.handle_mut()
             }
+
+            fn runtime(&self) -> *mut ::js::jsapi::JSRuntime {
+                self.0.runtime()
+            }
         }
     }
 }
 
 macro_rules! derive_convert {
     ($T:ty) => {
-        impl ::convert::FromJSValue for $T {
-            unsafe fn from_js_value(
+        impl<'rt> ::convert::FromJSValue for $T {
+            unsafe fn from_js_value<'a>(
                 cx: *mut ::js::jsapi::JSContext,
                 v: ::js::jsapi::HandleValue
-            ) -> ::exception::Result<Self> {
-                ::convert::FromJSValue::from_js_value(cx, v).map(|obj| <$T>::new(cx, obj))
+            ) -> ::exception::Result<'a, Self> {
+                ::convert::FromJSValue::from_js_value(cx, v).map(|obj| {
+                    <$T>::new(::context::DebugContext::from_raw(cx), obj)
+                })
             }
         }
 
-        impl ::convert::ToJSValue for $T {
+        impl<'rt> ::convert::ToJSValue for $T {
             unsafe fn to_js_value(
                 &self,
                 cx: *mut ::js::jsapi::JSContext,
@@ -63,11 +69,18 @@ macro_rules! try_jsapi {
     }}
 }
 
+macro_rules! enter_compartment {
+    ($cx:expr, $obj:expr) => {{
+        assert!($obj.runtime() == $cx.runtime(), "context belongs to a different runtime");
+        ::js::jsapi::JSAutoCompartment::new($cx.as_raw(), $obj.get())
+    }}
+}
+
 macro_rules! getter {
     ($cx:expr, $obj:expr, $name:expr) => {
         unsafe {
-            let _ac = ::js::jsapi::JSAutoCompartment::new($cx, $obj.get());
-            ::utils::get_property($cx, $obj.handle(), $name)
+            let _ac = enter_compartment!($cx, $obj);
+            ::utils::get_property($cx.as_raw(), $obj.handle(), $name)
         }
     }
 }
@@ -75,8 +88,9 @@ macro_rules! getter {
 macro_rules! setter {
     ($cx:expr, $obj:expr, $name:expr, $value:expr) => {
         unsafe {
-            let _ac = ::js::jsapi::JSAutoCompartment::new($cx, $obj.get());
-            try_jsapi!($cx, ::utils::set_property($cx, $obj.handle(), $name, $value));
+            let _ac = enter_compartment!($cx, $obj);
+            let cx = $cx.as_raw();
+            try_jsapi!(cx, ::utils::set_property(cx, $obj.handle(), $name, $value));
             Ok(())
         }
     }
@@ -85,9 +99,9 @@ macro_rules! setter {
 macro_rules! method {
     ($cx:expr, $obj:expr, $name:expr) => {
         unsafe {
-            let _ac = ::js::jsapi::JSAutoCompartment::new($cx, $obj.get());
+            let _ac = enter_compartment!($cx, $obj);
             ::utils::call_method(
-                $cx,
+                $cx.as_raw(),
                 $obj.handle(),
                 $name,
                 &::js::jsapi::HandleValueArray::new()
@@ -96,15 +110,16 @@ macro_rules! method {
     };
     ($cx:expr, $obj:expr, $name:expr, $($arg:expr),*) => {
         unsafe {
-            let _ac = ::js::jsapi::JSAutoCompartment::new($cx, $obj.get());
+            let _ac = enter_compartment!($cx, $obj);
+            let cx = $cx.as_raw();
             let mut args = Vec::new();
             $(
-                rooted!(in ($cx) let mut arg = ::js::jsval::UndefinedValue());
-                try_jsapi!($cx, $arg.to_js_value($cx, arg.handle_mut()));
+                rooted!(in (cx) let mut arg = ::js::jsval::UndefinedValue());
+                try_jsapi!(cx, $arg.to_js_value(cx, arg.handle_mut()));
                 args.push(arg.get());
             )*
             ::utils::call_method(
-                $cx,
+                cx,
                 $obj.handle(),
                 $name,
                 &::js::jsapi::HandleValueArray::from_slice(&args)
@@ -0,0 +1,681 @@
+use call::Call;
+use context::DebugContext;
+use convert::{FromJSValue, NullOr, ToJSValue, UndefinedOr};
+#[cfg(feature = "reachabilitygraph")]
+use debugger::Debugger;
+use exception::{Exception, Result};
+use ext::HandleValueArrayExt;
+use js::jsapi;
+use js::jsapi::{
+    CallArgs,
+    ESClass,
+    HandleObject,
+    HandleValue,
+    HandleValueArray,
+    JSContext,
+    JSObject,
+    MutableHandleValue,
+    Value,
+};
+use js::jsval;
+#[cfg(feature = "reachabilitygraph")]
+use reachability_graph;
+#[cfg(feature = "reachabilitygraph")]
+use reachability_graph::ReachabilityGraph;
+use rooted::Rooted;
+use saved_frame::SavedFrame;
+use std::any::TypeId;
+use std::collections::BTreeMap;
+#[cfg(feature = "reachabilitygraph")]
+use std::path::Path;
+use std::ptr;
+use std::rc::Rc;
+#[cfg(feature = "reachabilitygraph")]
+use std::result;
+use trace::TracedBox;
+use utils;
+
+pub enum Breakdown {
+    Count {
+        count: bool,
+        bytes: bool
+    },
+    ObjectClass {
+        then: Box<Breakdown>,
+        other: Box<Breakdown>
+    },
+    CoarseType {
+        objects: Box<Breakdown>,
+        scripts: Box<Breakdown>,
+        strings: Box<Breakdown>,
+        other: Box<Breakdown>
+    },
+    InternalType {
+        then: Box<Breakdown>
+    },
+    AllocationStack {
+        then: Box<Breakdown>,
+        no_stack: Box<Breakdown>
+    },
+    Filename {
+        then: Box<Breakdown>,
+        no_filename: Box<Breakdown>
+    }
+}
+
+impl Breakdown {
+    pub fn count() -> Breakdown {
+        Breakdown::Count {
+            count: true,
+            bytes: true
+        }
+    }
+}
+
+impl ToJSValue for Breakdown {
+    unsafe fn to_js_value(&self, cx: *mut JSContext, rval: MutableHandleValue) -> bool {
+        rooted!(in (cx) let obj = try_jsapi!(jsapi::JS_NewObject(cx, ptr::null_mut())));
+        let by = match *self {
+            Breakdown::Count { .. } => "count",
+            Breakdown::ObjectClass { .. } => "objectClass",
+            Breakdown::CoarseType { .. } => "coarseType",
+            Breakdown::InternalType { .. } => "internalType",
+            Breakdown::AllocationStack { .. } => "allocationStack",
+            Breakdown::Filename { .. } => "filename"
+        };
+        try_jsapi!(utils::define_property(cx, obj.handle(), "by", &by.to_string()));
+        match *self {
+            Breakdown::Count { count, bytes } => {
+                try_jsapi!(utils::define_property(cx, obj.handle(), "count", &count));
+                try_jsapi!(utils::define_property(cx, obj.handle(), "bytes", &bytes));
+            }
+            Breakdown::ObjectClass { ref then, ref other } => {
+                try_jsapi!(utils::define_property(cx, obj.handle(), "then", &**then));
+                try_jsapi!(utils::define_property(cx, obj.handle(), "other", &**other));
+            }
+            Breakdown::CoarseType { ref objects, ref scripts, ref strings, ref other } => {
+                try_jsapi!(utils::define_property(cx, obj.handle(), "objects", &**objects));
+                try_jsapi!(utils::define_property(cx, obj.handle(), "scripts", &**scripts));
+                try_jsapi!(utils::define_property(cx, obj.handle(), "strings", &**strings));
+                try_jsapi!(utils::define_property(cx, obj.handle(), "other", &**other));
+            }
+            Breakdown::InternalType { ref then } => {
+                try_jsapi!(utils::define_property(cx, obj.handle(), "then", &**then));
+            }
+            Breakdown::AllocationStack { ref then, ref no_stack } => {
+                try_jsapi!(utils::define_property(cx, obj.handle(), "then", &**then));
+                try_jsapi!(utils::define_property(cx, obj.handle(), "noStack", &**no_stack));
+            }
+            Breakdown::Filename { ref then, ref no_filename } => {
+                try_jsapi!(utils::define_property(cx, obj.handle(), "then", &**then));
+                try_jsapi!(utils::define_property(cx, obj.handle(), "noFilename", &**no_filename));
+            }
+        }
+        obj.to_js_value(cx, rval)
+    }
+}
+
+pub enum Census<'rt> {
+    Count {
+        count: Option<u64>,
+        bytes: Option<u64>
+    },
+    ObjectClass {
+        classes: BTreeMap<String, Census<'rt>>,
+        other: Box<Census<'rt>>
+    },
+    CoarseType {
+        objects: Box<Census<'rt>>,
+        scripts: Box<Census<'rt>>,
+        strings: Box<Census<'rt>>,
+        other: Box<Census<'rt>>
+    },
+    InternalType(BTreeMap<String, Census<'rt>>),
+    AllocationStack {
+        stacks: Vec<(SavedFrame<'rt>, Census<'rt>)>,
+        no_stack: Box<Census<'rt>>
+    },
+    Filename {
+        files: BTreeMap<String, Census<'rt>>,
+        no_filename: Box<Census<'rt>>
+    }
+}
+
+impl<'rt> Census<'rt> {
+    pub fn get_total_count(&self) -> u64 {
+        self.fold(&|count, _| count.unwrap_or(0))
+    }
+
+    pub fn get_total_bytes(&self) -> u64 {
+        self.fold(&|_, bytes| bytes.unwrap_or(0))
+    }
+
+    fn fold(&self, f: &Fn(Option<u64>, Option<u64>) -> u64) -> u64 {
+        match *self {
+            Census::Count { count, bytes } => f(count, bytes),
+            Census::ObjectClass { ref classes, ref other } => {
+                classes.values().map(|census| census.fold(f)).sum::<u64>() + other.fold(f)
+            }
+            Census::CoarseType { ref objects, ref scripts, ref strings, ref other } => {
+                objects.fold(f) + scripts.fold(f) + strings.fold(f) + other.fold(f)
+            }
+            Census::InternalType(ref types) => types.values().map(|census| census.fold(f)).sum(),
+            Census::AllocationStack { ref stacks, ref no_stack } => {
+                stacks.iter().map(|entry| entry.1.fold(f)).sum::<u64>() + no_stack.fold(f)
+            }
+            Census::Filename { ref files, ref no_filename } => {
+                files.values().map(|census| census.fold(f)).sum::<u64>() + no_filename.fold(f)
+            }
+        }
+    }
+
+    unsafe fn from_js_object(
+        cx: *mut JSContext,
+        obj: HandleObject,
+        breakdown: &Breakdown
+    ) -> Result<'rt, Census<'rt>> {
+        match *breakdown {
+            Breakdown::Count { .. } => {
+                let count: UndefinedOr<f64> = try!(utils::get_property(cx, obj, "count"));
+                let bytes: UndefinedOr<f64> = try!(utils::get_property(cx, obj, "bytes"));
+                Ok(Census::Count {
+                    count: count.into_option().map(|count| count as u64),
+                    bytes: bytes.into_option().map(|bytes| bytes as u64)
+                })
+            }
+            Breakdown::ObjectClass { ref then, ref other } => {
+                let mut classes = BTreeMap::new();
+                let mut other_census = None;
+                for (key, census) in try!(census_entries(cx, obj, then, other, "other")) {
+                    match key {
+                        Some(CensusKey::Name(name)) => {
+                            classes.insert(name, census);
+                        }
+                        _ => other_census = Some(census)
+                    }
+                }
+                Ok(Census::ObjectClass {
+                    classes: classes,
+                    other: Box::new(other_census.unwrap_or_else(|| empty_census(other)))
+                })
+            }
+            Breakdown::CoarseType { ref objects, ref scripts, ref strings, ref other } => {
+                Ok(Census::CoarseType {
+                    objects: Box::new(try!(census_property(cx, obj, "objects", objects))),
+                    scripts: Box::new(try!(census_property(cx, obj, "scripts", scripts))),
+                    strings: Box::new(try!(census_property(cx, obj, "strings", strings))),
+                    other: Box::new(try!(census_property(cx, obj, "other", other)))
+                })
+            }
+            Breakdown::InternalType { ref then } => {
+                let mut types = BTreeMap::new();
+                for (key, census) in try!(census_entries(cx, obj, then, then, "")) {
+                    if let Some(CensusKey::Name(name)) = key {
+                        types.insert(name, census);
+                    }
+                }
+                Ok(Census::InternalType(types))
+            }
+            Breakdown::AllocationStack { ref then, ref no_stack } => {
+                let mut stacks = Vec::new();
+                let mut no_stack_census = None;
+                for (key, census) in try!(census_entries(cx, obj, then, no_stack, "noStack")) {
+                    match key {
+                        Some(CensusKey::Stack(frame)) => stacks.push((frame, census)),
+                        _ => no_stack_census = Some(census)
+                    }
+                }
+                Ok(Census::AllocationStack {
+                    stacks: stacks,
+                    no_stack: Box::new(no_stack_census.unwrap_or_else(|| empty_census(no_stack)))
+                })
+            }
+            Breakdown::Filename { ref then, ref no_filename } => {
+                let mut files = BTreeMap::new();
+                let mut no_filename_census = None;
+                let entries = try!(census_entries(cx, obj, then, no_filename, "noFilename"));
+                for (key, census) in entries {
+                    match key {
+                        Some(CensusKey::Name(name)) => {
+                            files.insert(name, census);
+                        }
+                        _ => no_filename_census = Some(census)
+                    }
+                }
+                Ok(Census::Filename {
+                    files: files,
+                    no_filename: Box::new(no_filename_census.unwrap_or_else(|| {
+                        empty_census(no_filename)
+                    }))
+                })
+            }
+        }
+    }
+}
+
+enum CensusKey<'rt> {
+    Name(String),
+    Stack(SavedFrame<'rt>)
+}
+
+fn empty_census<'rt>(breakdown: &Breakdown) -> Census<'rt> {
+    match *breakdown {
+        Breakdown::Count { count, bytes } => {
+            Census::Count {
+                count: if count { Some(0) } else { None },
+                bytes: if bytes { Some(0) } else { None }
+            }
+        }
+        Breakdown::ObjectClass { ref other, .. } => {
+            Census::ObjectClass {
+                classes: BTreeMap::new(),
+                other: Box::new(empty_census(other))
+            }
+        }
+        Breakdown::CoarseType { ref objects, ref scripts, ref strings, ref other } => {
+            Census::CoarseType {
+                objects: Box::new(empty_census(objects)),
+                scripts: Box::new(empty_census(scripts)),
+                strings: Box::new(empty_census(strings)),
+                other: Box::new(empty_census(other))
+            }
+        }
+        Breakdown::InternalType { .. } => Census::InternalType(BTreeMap::new()),
+        Breakdown::AllocationStack { ref no_stack, .. } => {
+            Census::AllocationStack {
+                stacks: Vec::new(),
+                no_stack: Box::new(empty_census(no_stack))
+            }
+        }
+        Breakdown::Filename { ref no_filename, .. } => {
+            Census::Filename {
+                files: BTreeMap::new(),
+                no_filename: Box::new(empty_census(no_filename))
+            }
+        }
+    }
+}
+
+unsafe fn census_property<'rt>(
+    cx: *mut JSContext,
+    obj: HandleObject,
+    name: &str,
+    breakdown: &Breakdown
+) -> Result<'rt, Census<'rt>> {
+    let child: *mut JSObject = try!(utils::get_property(cx, obj, name));
+    rooted!(in (cx) let child = child);
+    Census::from_js_object(cx, child.handle(), breakdown)
+}
+
+unsafe fn census_entries<'rt>(
+    cx: *mut JSContext,
+    obj: HandleObject,
+    then: &Breakdown,
+    other: &Breakdown,
+    other_key: &str
+) -> Result<'rt, Vec<(Option<CensusKey<'rt>>, Census<'rt>)>> {
+    let mut class = ESClass::Other;
+    try_jsapi!(cx, jsapi::GetBuiltinClass(cx, obj, &mut class));
+    let (constructor, name) = if class == ESClass::Map {
+        ("Array", "from")
+    } else {
+        ("Object", "entries")
+    };
+    rooted!(in (cx) let global = jsapi::CurrentGlobalOrNull(cx));
+    let constructor: *mut JSObject = try!(utils::get_property(cx, global.handle(), constructor));
+    rooted!(in (cx) let constructor = constructor);
+    let args = [jsval::ObjectValue(&*obj.get())];
+    let entries: *mut JSObject = try!(utils::call_method(
+        cx,
+        constructor.handle(),
+        name,
+        &HandleValueArray::from_slice(&args)
+    ));
+    rooted!(in (cx) let entries = entries);
+    let mut length = 0;
+    if !jsapi::JS_GetArrayLength(cx, entries.handle(), &mut length) {
+        return Err(Exception::from_pending_exception(cx));
+    }
+    let mut result = Vec::new();
+    for index in 0..length {
+        let entry: *mut JSObject = try!(utils::get_element(cx, entries.handle(), index as usize));
+        rooted!(in (cx) let entry = entry);
+        rooted!(in (cx) let mut key = jsval::UndefinedValue());
+        if !jsapi::JS_GetElement(cx, entry.handle(), 0, key.handle_mut()) {
+            return Err(Exception::from_pending_exception(cx));
+        }
+        let value: *mut JSObject = try!(utils::get_element(cx, entry.handle(), 1));
+        rooted!(in (cx) let value = value);
+        let key = try!(CensusKey::from_js_value(cx, key.handle()));
+        let is_other = match key {
+            CensusKey::Name(ref name) => name == other_key,
+            CensusKey::Stack(_) => false
+        };
+        if is_other {
+            result.push((None, try!(Census::from_js_object(cx, value.handle(), other))));
+        } else {
+            result.push((Some(key), try!(Census::from_js_object(cx, value.handle(), then))));
+        }
+    }
+    Ok(result)
+}
+
+impl<'rt> FromJSValue for CensusKey<'rt> {
+    unsafe fn from_js_value<'a>(cx: *mut JSContext, v: HandleValue) -> Result<'a, Self> {
+        if v.is_object() {
+            FromJSValue::from_js_value(cx, v).map(|frame| CensusKey::Stack(frame))
+        } else {
+            FromJSValue::from_js_value(cx, v).map(|name| CensusKey::Name(name))
+        }
+    }
+}
+
+pub struct AllocationLogEntry<'rt> {
+    pub timestamp: f64,
+    pub frame: Option<SavedFrame<'rt>>,
+    pub class_name: String,
+    pub size: Option<usize>,
+    pub in_nursery: Option<bool>
+}
+
+impl<'rt> FromJSValue for AllocationLogEntry<'rt> {
+    unsafe fn from_js_value<'a>(cx: *mut JSContext, v: HandleValue) -> Result<'a, Self> {
+        rooted!(in (cx) let obj = v.to_object());
+        let frame: NullOr<SavedFrame<'rt>> = try!(utils::get_property(cx, obj.handle(), "frame"));
+        let size: UndefinedOr<f64> = try!(utils::get_property(cx, obj.handle(), "size"));
+        let in_nursery: UndefinedOr<bool> = try!(utils::get_property(
+            cx,
+            obj.handle(),
+            "inNursery"
+        ));
+        Ok(AllocationLogEntry {
+            timestamp: try!(utils::get_property(cx, obj.handle(), "timestamp")),
+            frame: frame.into_option(),
+            class_name: try!(utils::get_property(cx, obj.handle(), "class")),
+            size: size.into_option().map(|size| size as usize),
+            in_nursery: in_nursery.into_option()
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GcSlice {
+    pub start_timestamp: f64,
+    pub end_timestamp: f64
+}
+
+impl GcSlice {
+    pub fn get_duration(&self) -> f64 {
+        self.end_timestamp - self.start_timestamp
+    }
+}
+
+impl FromJSValue for GcSlice {
+    unsafe fn from_js_value<'rt>(cx: *mut JSContext, v: HandleValue) -> Result<'rt, Self> {
+        rooted!(in (cx) let obj = v.to_object());
+        Ok(GcSlice {
+            start_timestamp: try!(utils::get_property(cx, obj.handle(), "startTimestamp")),
+            end_timestamp: try!(utils::get_property(cx, obj.handle(), "endTimestamp"))
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GcStatistics {
+    pub reason: String,
+    pub non_incremental_reason: Option<String>,
+    pub collected_zones: Option<u32>,
+    pub slices: Vec<GcSlice>
+}
+
+impl GcStatistics {
+    pub fn is_incremental(&self) -> bool {
+        self.non_incremental_reason.is_none()
+    }
+
+    pub fn get_total_duration(&self) -> f64 {
+        self.slices.iter().map(|slice| slice.get_duration()).sum()
+    }
+
+    pub fn get_longest_slice(&self) -> Option<&GcSlice> {
+        self.slices.iter().fold(None, |longest: Option<&GcSlice>, slice| {
+            match longest {
+                Some(longest) if longest.get_duration() >= slice.get_duration() => Some(longest),
+                _ => Some(slice)
+            }
+        })
+    }
+
+    pub fn get_elapsed(&self) -> f64 {
+        match (self.slices.first(), self.slices.last()) {
+            (Some(first), Some(last)) => last.end_timestamp - first.start_timestamp,
+            _ => 0.0
+        }
+    }
+}
+
+impl FromJSValue for GcStatistics {
+    unsafe fn from_js_value<'rt>(cx: *mut JSContext, v: HandleValue) -> Result<'rt, Self> {
+        rooted!(in (cx) let obj = v.to_object());
+        let non_incremental_reason: NullOr<String> = try!(utils::get_property(
+            cx,
+            obj.handle(),
+            "nonincrementalReason"
+        ));
+        let collected_zones: UndefinedOr<u32> = try!(utils::get_property(
+            cx,
+            obj.handle(),
+            "collectedZones"
+        ));
+        Ok(GcStatistics {
+            reason: try!(utils::get_property(cx, obj.handle(), "reason")),
+            non_incremental_reason: non_incremental_reason.into_option(),
+            collected_zones: collected_zones.into_option(),
+            slices: try!(utils::get_property(cx, obj.handle(), "collections"))
+        })
+    }
+}
+
+pub trait OnGarbageCollection<'rt> {
+    fn on_garbage_collection(
+        &self,
+        cx: DebugContext<'rt>,
+        statistics: &GcStatistics
+    ) -> Result<'rt, ()>;
+}
+
+impl<'rt> Call for OnGarbageCollection<'rt> + 'rt {
+    fn hook_type() -> TypeId {
+        TypeId::of::<OnGarbageCollection<'static> + 'static>()
+    }
+
+    unsafe fn call(&self, cx: *mut JSContext, argc: u32, vp: *mut Value) -> bool {
+        let args = CallArgs::from_vp(vp, argc);
+        let statistics = match GcStatistics::from_js_value(cx, args.get(0)) {
+            Ok(statistics) => statistics,
+            Err(exception) => return exception.into_pending_exception(cx)
+        };
+        match self.on_garbage_collection(DebugContext::from_raw(cx), &statistics) {
+            Ok(result) => result.to_js_value(cx, args.rval()),
+            Err(exception) => exception.into_pending_exception(cx)
+        }
+    }
+}
+
+pub struct Memory<'rt>(TracedBox<'rt, *mut JSObject>);
+
+impl<'rt> Memory<'rt> {
+    pub fn new(cx: DebugContext<'rt>, memory: *mut JSObject) -> Memory<'rt> {
+        Memory(TracedBox::new(cx, memory))
+    }
+
+    pub fn get_tracking_allocation_sites(&self, cx: DebugContext<'rt>) -> Result<'rt, bool> {
+        getter!(cx, self, "trackingAllocationSites")
+    }
+
+    pub fn set_tracking_allocation_sites(
+        &self,
+        cx: DebugContext<'rt>,
+        tracking: bool
+    ) -> Result<'rt, ()> {
+        setter!(cx, self, "trackingAllocationSites", tracking)
+    }
+
+    pub fn get_allocation_sampling_probability(&self, cx: DebugContext<'rt>) -> Result<'rt, f64> {
+        getter!(cx, self, "allocationSamplingProbability")
+    }
+
+    pub fn set_allocation_sampling_probability(
+        &self,
+        cx: DebugContext<'rt>,
+        probability: f64
+    ) -> Result<'rt, ()> {
+        setter!(cx, self, "allocationSamplingProbability", probability)
+    }
+
+    pub fn get_max_allocations_log_length(&self, cx: DebugContext<'rt>) -> Result<'rt, usize> {
+        getter!(cx, self, "maxAllocationsLogLength").map(|length: u32| length as usize)
+    }
+
+    pub fn set_max_allocations_log_length(
+        &self,
+        cx: DebugContext<'rt>,
+        length: usize
+    ) -> Result<'rt, ()> {
+        setter!(cx, self, "maxAllocationsLogLength", length as u32)
+    }
+
+    pub fn get_allocations_log_overflowed(&self, cx: DebugContext<'rt>) -> Result<'rt, bool> {
+        getter!(cx, self, "allocationsLogOverflowed")
+    }
+
+    pub fn get_on_garbage_collection(
+        &self,
+        cx: DebugContext<'rt>
+    ) -> Result<'rt, Option<Rc<OnGarbageCollection<'rt> + 'rt>>> {
+        getter!(cx, self, "onGarbageCollection")
+    }
+
+    pub fn set_on_garbage_collection(
+        &self,
+        cx: DebugContext<'rt>,
+        on_garbage_collection: Option<Rc<OnGarbageCollection<'rt> + 'rt>>
+    ) -> Result<'rt, ()> {
+        setter!(cx, self, "onGarbageCollection", on_garbage_collection)
+    }
+
+    pub fn drain_allocations_log(
+        &self,
+        cx: DebugContext<'rt>
+    ) -> Result<'rt, Vec<AllocationLogEntry<'rt>>> {
+        method!(cx, self, "drainAllocationsLog")
+    }
+
+    pub fn take_census(
+        &self,
+        cx: DebugContext<'rt>,
+        breakdown: &Breakdown
+    ) -> Result<'rt, Census<'rt>> {
+        unsafe {
+            let _ac = enter_compartment!(cx, self);
+            let cx = cx.as_raw();
+            rooted!(in (cx) let options = try_jsapi!(cx, jsapi::JS_NewObject(cx, ptr::null_mut())));
+            try_jsapi!(cx, utils::define_property(cx, options.handle(), "breakdown", breakdown));
+            let args = [jsval::ObjectValue(&*options.get())];
+            let census: *mut JSObject = try!(utils::call_method(
+                cx,
+                self.handle(),
+                "takeCensus",
+                &HandleValueArray::from_slice(&args)
+            ));
+            rooted!(in (cx) let census = census);
+            Census::from_js_object(cx, census.handle(), breakdown)
+        }
+    }
+
+    #[cfg(feature = "reachabilitygraph")]
+    pub fn save_reachability_graph<P: AsRef<Path>>(
+        &self,
+        cx: DebugContext<'rt>,
+        debugger: &Debugger<'rt>,
+        path: P
+    ) -> result::Result<ReachabilityGraph, reachability_graph::Error<'rt>> {
+        let graph = try!(ReachabilityGraph::capture(cx, debugger));
+        try!(graph.save(path));
+        Ok(graph)
+    }
+}
+
+derive_rooted!(*mut JSObject, Memory<'rt>);
+
+derive_convert!(Memory<'rt>);
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use super::{Breakdown, Census, GcSlice, GcStatistics, empty_census};
+
+    fn count(count: u64, bytes: u64) -> Census<'static> {
+        Census::Count {
+            count: Some(count),
+            bytes: Some(bytes)
+        }
+    }
+
+    #[test]
+    fn totals() {
+        let mut classes = BTreeMap::new();
+        classes.insert("Array".to_string(), count(3, 96));
+        classes.insert("Object".to_string(), count(5, 160));
+        let census = Census::CoarseType {
+            objects: Box::new(Census::ObjectClass {
+                classes: classes,
+                other: Box::new(count(1, 32))
+            }),
+            scripts: Box::new(count(2, 512)),
+            strings: Box::new(count(4, 64)),
+            other: Box::new(count(0, 0))
+        };
+        assert_eq!(census.get_total_count(), 15);
+        assert_eq!(census.get_total_bytes(), 864);
+
+        let breakdown = Breakdown::Filename {
+            then: Box::new(Breakdown::count()),
+            no_filename: Box::new(Breakdown::Count {
+                count: true,
+                bytes: false
+            })
+        };
+        let census = empty_census(&breakdown);
+        assert_eq!(census.get_total_count(), 0);
+        assert_eq!(census.get_total_bytes(), 0);
+    }
+
+    #[test]
+    fn gc_statistics() {
+        let slice = |start: f64, end: f64| GcSlice {
+            start_timestamp: start,
+            end_timestamp: end
+        };
+        let statistics = GcStatistics {
+            reason: "ALLOC_TRIGGER".to_string(),
+            non_incremental_reason: None,
+            collected_zones: None,
+            slices: vec![slice(100.0, 102.5), slice(110.0, 116.0), slice(120.0, 121.0)]
+        };
+        assert!(statistics.is_incremental());
+        assert_eq!(statistics.get_total_duration(), 9.5);
+        assert_eq!(statistics.get_longest_slice(), Some(&slice(110.0, 116.0)));
+        assert_eq!(statistics.get_elapsed(), 21.0);
+
+        let statistics = GcStatistics {
+            reason: "API".to_string(),
+            non_incremental_reason: Some("GC mode".to_string()),
+            collected_zones: Some(1),
+            slices: Vec::new()
+        };
+        assert!(!statistics.is_incremental());
+        assert_eq!(statistics.get_total_duration(), 0.0);
+        assert_eq!(statistics.get_longest_slice(), None);
+    }
+}
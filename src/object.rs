@@ -1,4 +1,7 @@
-use convert::{FromJSValue, ToJSValue, UndefinedOr};
+use context::DebugContext;
+use convert;
+use convert::{FromJSValue, NullOr, ToJSValue, UndefinedOr};
+use environment::Environment;
 use ext::HandleValueArrayExt;
 use exception::Result;
 use js::jsapi;
@@ -6,21 +9,28 @@ use js::jsapi::{HandleValue, JSContext, JSObject, MutableHandleValue};
 use rooted::Rooted;
 use std::collections::BTreeMap;
 use std::ptr;
-use trace::TracedBox;
+use trace::{TracedBox, WeakBox};
 use utils;
 use value::{CompletionValue, Value};
 
-pub struct PropertyDescriptor {
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+pub struct PropertyDescriptor<'rt> {
+    #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
     pub configurable: Option<bool>,
+    #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
     pub enumerable: Option<bool>,
+    #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
     pub writable: Option<bool>,
-    pub value: Option<Value>,
-    pub get: Option<Value>,
-    pub set: Option<Value>
+    #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
+    pub value: Option<Value<'rt>>,
+    #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
+    pub get: Option<Value<'rt>>,
+    #[cfg_attr(feature = "serialize", serde(skip_serializing_if = "Option::is_none"))]
+    pub set: Option<Value<'rt>>
 }
 
-impl FromJSValue for PropertyDescriptor {
-    unsafe fn from_js_value(cx: *mut JSContext, v: HandleValue) -> Result<Self> {
+impl<'rt> FromJSValue for PropertyDescriptor<'rt> {
+    unsafe fn from_js_value<'a>(cx: *mut JSContext, v: HandleValue) -> Result<'a, Self> {
         rooted!(in (cx) let obj = v.to_object());
         Ok(PropertyDescriptor {
             configurable: if try!(utils::has_property(cx, obj.handle(), "configurable")) {
@@ -57,7 +67,7 @@ impl FromJSValue for PropertyDescriptor {
     }
 }
 
-impl ToJSValue for PropertyDescriptor {
+impl<'rt> ToJSValue for PropertyDescriptor<'rt> {
     unsafe fn to_js_value(&self, cx: *mut JSContext, rval: MutableHandleValue) -> bool {
         rooted!(in (cx) let obj = try_jsapi!(jsapi::JS_NewObject(cx, ptr::null_mut())));
         if let Some(ref configurable) = self.configurable {
@@ -82,6 +92,8 @@ impl ToJSValue for PropertyDescriptor {
     }
 }
 
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "lowercase"))]
 pub enum PromiseState {
     Pending,
     Fulfilled,
@@ -89,8 +101,8 @@ pub enum PromiseState {
 }
 
 impl FromJSValue for PromiseState {
-    unsafe fn from_js_value(cx: *mut JSContext, v: HandleValue) -> Result<Self> {
-        String::from_js_value(cx, v).map(|string| {
+    unsafe fn from_js_value<'rt>(cx: *mut JSContext, v: HandleValue) -> Result<'rt, Self> {
+        convert::with_str(cx, v, |string| {
             if string == "pending" {
                 PromiseState::Pending
             } else if string == "fulfilled" {
@@ -104,146 +116,223 @@ impl FromJSValue for PromiseState {
     }
 }
 
-pub struct Object(TracedBox<*mut JSObject>);
+pub struct Object<'rt>(TracedBox<'rt, *mut JSObject>);
 
-impl Object {
-    pub fn new(cx: *mut JSContext, object: *mut JSObject) -> Object {
+impl<'rt> Object<'rt> {
+    pub fn new(cx: DebugContext<'rt>, object: *mut JSObject) -> Object<'rt> {
         Object(TracedBox::new(cx, object))
     }
 
-    pub fn get_is_callable(&self, cx: *mut JSContext) -> Result<bool> {
+    pub fn downgrade(&self, cx: DebugContext<'rt>) -> WeakObject<'rt> {
+        WeakObject(WeakBox::new(cx, self.get()))
+    }
+
+    pub fn get_class(&self, cx: DebugContext<'rt>) -> Result<'rt, String> {
+        getter!(cx, self, "class")
+    }
+
+    pub fn get_proto(&self, cx: DebugContext<'rt>) -> Result<'rt, Option<Object<'rt>>> {
+        getter!(cx, self, "proto").map(|proto| NullOr::<Object<'rt>>::into_option(proto))
+    }
+
+    pub fn get_is_callable(&self, cx: DebugContext<'rt>) -> Result<'rt, bool> {
         getter!(cx, self, "callable")
     }
 
-    pub fn get_is_bound_function(&self, cx: *mut JSContext) -> Result<bool> {
+    pub fn get_is_bound_function(&self, cx: DebugContext<'rt>) -> Result<'rt, bool> {
         getter!(cx, self, "isBoundFunction")
     }
 
-    pub fn get_is_proxy(&self, cx: *mut JSContext) -> Result<bool> {
+    pub fn get_is_proxy(&self, cx: DebugContext<'rt>) -> Result<'rt, bool> {
         getter!(cx, self, "isProxy")
     }
 
-    pub fn is_extensible(&self, cx: *mut JSContext) -> Result<bool> {
+    pub fn is_extensible(&self, cx: DebugContext<'rt>) -> Result<'rt, bool> {
         method!(cx, self, "isExtensible")
     }
 
-    pub fn is_sealed(&self, cx: *mut JSContext) -> Result<bool> {
+    pub fn is_sealed(&self, cx: DebugContext<'rt>) -> Result<'rt, bool> {
         method!(cx, self, "isSealed")
     }
 
-    pub fn is_frozen(&self, cx: *mut JSContext) -> Result<bool> {
+    pub fn is_frozen(&self, cx: DebugContext<'rt>) -> Result<'rt, bool> {
         method!(cx, self, "isFrozen")
     }
 
-    pub fn get_own_property_names(&self, cx: *mut JSContext) -> Result<Vec<String>> {
+    pub fn get_own_property_names(&self, cx: DebugContext<'rt>) -> Result<'rt, Vec<String>> {
         method!(cx, self, "getOwnPropertyNames")
     }
 
     pub fn get_own_property_descriptor(
         &self,
-        cx: *mut JSContext,
+        cx: DebugContext<'rt>,
         name: &str
-    ) -> Result<PropertyDescriptor> {
+    ) -> Result<'rt, PropertyDescriptor<'rt>> {
         method!(cx, self, "getOwnPropertyDescriptor", name)
     }
 
-    pub fn prevent_extensions(&self, cx: *mut JSContext) -> Result<()> {
+    pub fn prevent_extensions(&self, cx: DebugContext<'rt>) -> Result<'rt, ()> {
         method!(cx, self, "preventExtensions")
     }
 
-    pub fn seal(&self, cx: *mut JSContext) -> Result<()> {
+    pub fn seal(&self, cx: DebugContext<'rt>) -> Result<'rt, ()> {
         method!(cx, self, "seal")
     }
 
-    pub fn freeze(&self, cx: *mut JSContext) -> Result<()> {
+    pub fn freeze(&self, cx: DebugContext<'rt>) -> Result<'rt, ()> {
         method!(cx, self, "freeze")
     }
 
     pub fn define_property(
         &self,
-        cx: *mut JSContext,
+        cx: DebugContext<'rt>,
         name: &str,
-        descriptor: &PropertyDescriptor
-    ) -> Result<()> {
+        descriptor: &PropertyDescriptor<'rt>
+    ) -> Result<'rt, ()> {
         method!(cx, self, "defineProperty", name, descriptor)
     }
 
     pub fn define_properties(
         &self,
-        cx: *mut JSContext,
-        properties: BTreeMap<String, PropertyDescriptor>,
-    ) -> Result<()> {
+        cx: DebugContext<'rt>,
+        properties: BTreeMap<String, PropertyDescriptor<'rt>>,
+    ) -> Result<'rt, ()> {
         method!(cx, self, "defineProperties", properties)
     }
 
-    pub fn delete_property(&self, cx: *mut JSContext, name: &str) -> Result<bool> {
+    pub fn delete_property(&self, cx: DebugContext<'rt>, name: &str) -> Result<'rt, bool> {
         method!(cx, self, "deleteProperty", name)
     }
 
     pub fn call(
         &self,
-        cx: *mut JSContext,
-        this: Value,
-        arguments: &[Value]
-    ) -> Result<CompletionValue> {
+        cx: DebugContext<'rt>,
+        this: Value<'rt>,
+        arguments: &[Value<'rt>]
+    ) -> Result<'rt, CompletionValue<'rt>> {
         method!(cx, self, "apply", this, arguments)
     }
 
-    pub fn get_name(&self, cx: *mut JSContext) -> Result<Option<String>> {
+    pub fn execute_in_global(
+        &self,
+        cx: DebugContext<'rt>,
+        code: &str
+    ) -> Result<'rt, CompletionValue<'rt>> {
+        method!(cx, self, "executeInGlobal", code)
+    }
+
+    pub fn get_name(&self, cx: DebugContext<'rt>) -> Result<'rt, Option<String>> {
         getter!(cx, self, "name").map(|name| {
             UndefinedOr::<String>::into_option(name)
         })
     }
 
-    pub fn get_parameter_names(&self, cx: *mut JSContext) -> Result<Option<Vec<String>>> {
+    pub fn get_parameter_names(&self, cx: DebugContext<'rt>) -> Result<'rt, Option<Vec<String>>> {
         getter!(cx, self, "parameterNames").map(|names| {
             UndefinedOr::<Vec<String>>::into_option(names)
         })
     }
 
-    pub fn get_bound_target_function(&self, cx: *mut JSContext) -> Result<Option<Object>> {
+    pub fn get_environment(&self, cx: DebugContext<'rt>) -> Result<'rt, Option<Environment<'rt>>> {
+        getter!(cx, self, "environment").map(|environment| {
+            UndefinedOr::<Environment<'rt>>::into_option(environment)
+        })
+    }
+
+    pub fn get_bound_target_function(
+        &self,
+        cx: DebugContext<'rt>
+    ) -> Result<'rt, Option<Object<'rt>>> {
         getter!(cx, self, "boundTargetFunction").map(|function| {
-            UndefinedOr::<Object>::into_option(function)
+            UndefinedOr::<Object<'rt>>::into_option(function)
         })
     }
 
-    pub fn get_bound_this(&self, cx: *mut JSContext) -> Result<Option<Value>> {
+    pub fn get_bound_this(&self, cx: DebugContext<'rt>) -> Result<'rt, Option<Value<'rt>>> {
         getter!(cx, self, "boundThis").map(|this| {
-            UndefinedOr::<Value>::into_option(this)
+            UndefinedOr::<Value<'rt>>::into_option(this)
         })
     }
 
-    pub fn get_bound_arguments(&self, cx: *mut JSContext) -> Result<Option<Vec<Value>>> {
+    pub fn get_bound_arguments(
+        &self,
+        cx: DebugContext<'rt>
+    ) -> Result<'rt, Option<Vec<Value<'rt>>>> {
         getter!(cx, self, "boundArguments").map(|arguments| {
-            UndefinedOr::<Vec<Value>>::into_option(arguments)
+            UndefinedOr::<Vec<Value<'rt>>>::into_option(arguments)
         })
     }
 
-    pub fn get_proxy_target(&self, cx: *mut JSContext) -> Result<Option<Object>> {
+    pub fn get_proxy_target(&self, cx: DebugContext<'rt>) -> Result<'rt, Option<Object<'rt>>> {
         getter!(cx, self, "proxyTarget").map(|target| {
-            UndefinedOr::<Object>::into_option(target)
+            UndefinedOr::<Object<'rt>>::into_option(target)
         })
     }
 
-    pub fn get_proxy_handler(&self, cx: *mut JSContext) -> Result<Option<Object>> {
+    pub fn get_proxy_handler(&self, cx: DebugContext<'rt>) -> Result<'rt, Option<Object<'rt>>> {
         getter!(cx, self, "proxyHandler").map(|handler| {
-            UndefinedOr::<Object>::into_option(handler)
+            UndefinedOr::<Object<'rt>>::into_option(handler)
         })
     }
 
-    pub fn get_promise_state(&self, cx: *mut JSContext) -> Result<PromiseState> {
+    pub fn get_promise_state(&self, cx: DebugContext<'rt>) -> Result<'rt, PromiseState> {
         getter!(cx, self, "promiseState")
     }
 
-    pub fn get_promise_value(&self, cx: *mut JSContext) -> Result<Value> {
+    pub fn get_promise_value(&self, cx: DebugContext<'rt>) -> Result<'rt, Value<'rt>> {
         getter!(cx, self, "promiseValue")
     }
 
-    pub fn get_promise_reason(&self, cx: *mut JSContext) -> Result<Value> {
+    pub fn get_promise_reason(&self, cx: DebugContext<'rt>) -> Result<'rt, Value<'rt>> {
         getter!(cx, self, "promiseReason")
     }
 }
 
-derive_rooted!(*mut JSObject, Object);
+derive_rooted!(*mut JSObject, Object<'rt>);
+
+derive_convert!(Object<'rt>);
 
-derive_convert!(Object);
+pub struct WeakObject<'rt>(WeakBox<'rt>);
+
+impl<'rt> WeakObject<'rt> {
+    pub fn is_alive(&self) -> bool {
+        self.0.is_alive()
+    }
+
+    pub fn upgrade(&self, cx: DebugContext<'rt>) -> Option<Object<'rt>> {
+        self.0.get().map(|object| Object::new(cx, object))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use js::jsapi;
+    use utils;
+    use value::{CompletionValue, Value};
+
+    #[test]
+    fn weak_object() {
+        utils::with_debuggee(|cx, debugger, _| {
+            let global = debugger.get_debuggees(cx).unwrap().remove(0);
+            let object = match global.execute_in_global(cx, "({})").unwrap() {
+                CompletionValue::Return(Value::Object(object)) => object,
+                _ => panic!()
+            };
+            let weak = object.downgrade(cx);
+
+            unsafe {
+                jsapi::JS_GC(cx.runtime());
+            }
+            assert!(weak.is_alive());
+            assert!(weak.upgrade(cx).is_some());
+
+            drop(object);
+            assert!(weak.is_alive());
+            unsafe {
+                jsapi::JS_GC(cx.runtime());
+            }
+            assert!(!weak.is_alive());
+            assert!(weak.upgrade(cx).is_none());
+        });
+    }
+}
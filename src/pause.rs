@@ -0,0 +1,164 @@
+use context::DebugContext;
+use debugger::OnDebuggerStatement;
+use exception::Result;
+use frame::Frame;
+use rooted::Rooted;
+use script::OnHit;
+use std::cell::RefCell;
+use std::rc::Rc;
+use stepper::OnStepComplete;
+use value::ResumptionValue;
+
+pub trait EventLoop<'rt> {
+    fn process_events(
+        &self,
+        cx: DebugContext<'rt>,
+        controller: &PauseController<'rt>
+    ) -> Result<'rt, ()>;
+}
+
+struct Pause<'rt> {
+    frame: Frame<'rt>,
+    resumption: Option<ResumptionValue<'rt>>
+}
+
+pub struct PauseController<'rt> {
+    event_loop: Rc<EventLoop<'rt> + 'rt>,
+    pauses: RefCell<Vec<Pause<'rt>>>
+}
+
+impl<'rt> PauseController<'rt> {
+    pub fn new(event_loop: Rc<EventLoop<'rt> + 'rt>) -> PauseController<'rt> {
+        PauseController {
+            event_loop: event_loop,
+            pauses: RefCell::new(Vec::new())
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        !self.pauses.borrow().is_empty()
+    }
+
+    pub fn get_depth(&self) -> usize {
+        self.pauses.borrow().len()
+    }
+
+    pub fn get_paused_frame(&self, cx: DebugContext<'rt>) -> Option<Frame<'rt>> {
+        self.pauses.borrow().last().map(|pause| Frame::new(cx, pause.frame.get()))
+    }
+
+    pub fn pause(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        let depth = {
+            let mut pauses = self.pauses.borrow_mut();
+            pauses.push(Pause {
+                frame: Frame::new(cx, frame.get()),
+                resumption: None
+            });
+            pauses.len()
+        };
+        loop {
+            let resumption = self.pauses.borrow_mut()[depth - 1].resumption.take();
+            if let Some(resumption) = resumption {
+                self.pauses.borrow_mut().pop();
+                return Ok(resumption);
+            }
+            if let Err(exception) = self.event_loop.process_events(cx, self) {
+                self.pauses.borrow_mut().truncate(depth - 1);
+                return Err(exception);
+            }
+        }
+    }
+
+    pub fn resume(&self, resumption: ResumptionValue<'rt>) -> bool {
+        match self.pauses.borrow_mut().last_mut() {
+            Some(pause) => {
+                pause.resumption = Some(resumption);
+                true
+            }
+            None => false
+        }
+    }
+}
+
+impl<'rt> OnDebuggerStatement<'rt> for PauseController<'rt> {
+    fn on_debugger_statement(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        self.pause(cx, frame)
+    }
+}
+
+impl<'rt> OnHit<'rt> for PauseController<'rt> {
+    fn on_hit(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        self.pause(cx, frame)
+    }
+}
+
+impl<'rt> OnStepComplete<'rt> for PauseController<'rt> {
+    fn on_step_complete(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        self.pause(cx, frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EventLoop, PauseController};
+    use context::DebugContext;
+    use exception::Result;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use utils;
+
+    struct Script(RefCell<Vec<(usize, String)>>);
+
+    impl<'rt> EventLoop<'rt> for Script {
+        fn process_events(
+            &self,
+            cx: DebugContext<'rt>,
+            controller: &PauseController<'rt>
+        ) -> Result<'rt, ()> {
+            let frame = controller.get_paused_frame(cx).unwrap();
+            let name = try!(frame.get_function_name(cx));
+            let first = self.0.borrow().is_empty();
+            self.0.borrow_mut().push((controller.get_depth(), name));
+            if first {
+                try!(frame.eval(cx, "inner()"));
+                assert_eq!(controller.get_depth(), 1);
+            } else {
+                assert!(controller.resume(None));
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn nested_pauses() {
+        utils::with_debuggee(|cx, debugger, evaluate| {
+            let events = Rc::new(Script(RefCell::new(Vec::new())));
+            let controller = Rc::new(PauseController::new(events.clone()));
+            debugger.set_on_debugger_statement(cx, Some(controller.clone())).unwrap();
+            evaluate("function inner() {\n    debugger;\n}\ndebugger;\n");
+            assert!(!controller.is_paused());
+            assert!(!controller.resume(None));
+            assert_eq!(*events.0.borrow(), vec![
+                (1, "(global)".to_string()),
+                (2, "inner".to_string()),
+                (1, "(global)".to_string())
+            ]);
+        });
+    }
+}
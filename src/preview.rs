@@ -0,0 +1,228 @@
+use context::DebugContext;
+use exception::Result;
+use object::{Object, PromiseState};
+use value::Value;
+
+pub struct PreviewOptions {
+    pub max_depth: usize,
+    pub max_properties: usize,
+    pub max_string_length: usize
+}
+
+impl Default for PreviewOptions {
+    fn default() -> PreviewOptions {
+        PreviewOptions {
+            max_depth: 1,
+            max_properties: 5,
+            max_string_length: 100
+        }
+    }
+}
+
+pub enum Preview<'rt> {
+    Undefined,
+    Null,
+    Boolean(bool),
+    Number(f64),
+    String(StringPreview),
+    Object(ObjectPreview<'rt>)
+}
+
+pub struct StringPreview {
+    pub value: String,
+    pub length: usize,
+    pub truncated: bool
+}
+
+pub struct ObjectPreview<'rt> {
+    pub object: Object<'rt>,
+    pub class_name: String,
+    pub kind: ObjectKind<'rt>,
+    pub properties: Vec<PropertyPreview<'rt>>,
+    pub overflow: bool
+}
+
+pub enum ObjectKind<'rt> {
+    Ordinary,
+    Function {
+        name: Option<String>,
+        parameter_names: Option<Vec<String>>
+    },
+    Array {
+        length: usize
+    },
+    Promise {
+        state: PromiseState,
+        result: Option<Box<Preview<'rt>>>
+    },
+    Proxy {
+        target: Option<Box<Preview<'rt>>>
+    }
+}
+
+pub struct PropertyPreview<'rt> {
+    pub name: String,
+    pub value: PropertyValue<'rt>
+}
+
+pub enum PropertyValue<'rt> {
+    Data(Preview<'rt>),
+    Accessor {
+        has_getter: bool,
+        has_setter: bool
+    }
+}
+
+pub fn preview<'rt>(
+    cx: DebugContext<'rt>,
+    value: Value<'rt>,
+    options: &PreviewOptions
+) -> Result<'rt, Preview<'rt>> {
+    preview_at_depth(cx, value, options, 0)
+}
+
+fn preview_at_depth<'rt>(
+    cx: DebugContext<'rt>,
+    value: Value<'rt>,
+    options: &PreviewOptions,
+    depth: usize
+) -> Result<'rt, Preview<'rt>> {
+    Ok(match value {
+        Value::Undefined => Preview::Undefined,
+        Value::Null => Preview::Null,
+        Value::Boolean(b) => Preview::Boolean(b),
+        Value::Int32(i) => Preview::Number(i as f64),
+        Value::Double(d) => Preview::Number(d),
+        Value::String(s) => Preview::String(preview_string(s, options)),
+        Value::Object(object) => Preview::Object(try!(preview_object(cx, object, options, depth)))
+    })
+}
+
+fn preview_string(s: String, options: &PreviewOptions) -> StringPreview {
+    let length = s.chars().count();
+    if length <= options.max_string_length {
+        return StringPreview {
+            value: s,
+            length: length,
+            truncated: false
+        };
+    }
+    StringPreview {
+        value: s.chars().take(options.max_string_length).collect(),
+        length: length,
+        truncated: true
+    }
+}
+
+fn preview_object<'rt>(
+    cx: DebugContext<'rt>,
+    object: Object<'rt>,
+    options: &PreviewOptions,
+    depth: usize
+) -> Result<'rt, ObjectPreview<'rt>> {
+    let class_name = try!(object.get_class(cx));
+    if try!(object.get_is_proxy(cx)) {
+        let target = if depth < options.max_depth {
+            match try!(object.get_proxy_target(cx)) {
+                Some(target) => Some(Box::new(try!(preview_at_depth(
+                    cx,
+                    Value::Object(target),
+                    options,
+                    depth + 1
+                )))),
+                None => None
+            }
+        } else {
+            None
+        };
+        return Ok(ObjectPreview {
+            object: object,
+            class_name: class_name,
+            kind: ObjectKind::Proxy { target: target },
+            properties: Vec::new(),
+            overflow: false
+        });
+    }
+
+    let names = try!(object.get_own_property_names(cx));
+    let kind = if try!(object.get_is_callable(cx)) {
+        ObjectKind::Function {
+            name: try!(object.get_name(cx)),
+            parameter_names: try!(object.get_parameter_names(cx))
+        }
+    } else if class_name == "Array" {
+        let length = match try!(object.get_own_property_descriptor(cx, "length")).value {
+            Some(Value::Int32(length)) => length as usize,
+            Some(Value::Double(length)) => length as usize,
+            _ => 0
+        };
+        ObjectKind::Array { length: length }
+    } else if class_name == "Promise" {
+        let state = try!(object.get_promise_state(cx));
+        let result = match state {
+            PromiseState::Pending => None,
+            PromiseState::Fulfilled => Some(try!(object.get_promise_value(cx))),
+            PromiseState::Rejected => Some(try!(object.get_promise_reason(cx)))
+        };
+        let result = match result {
+            Some(result) if depth < options.max_depth => {
+                Some(Box::new(try!(preview_at_depth(cx, result, options, depth + 1))))
+            }
+            _ => None
+        };
+        ObjectKind::Promise {
+            state: state,
+            result: result
+        }
+    } else {
+        ObjectKind::Ordinary
+    };
+
+    let mut properties = Vec::new();
+    let mut overflow = false;
+    for name in names {
+        if let ObjectKind::Function { .. } = kind {
+            if name == "length" || name == "name" || name == "prototype" {
+                continue;
+            }
+        }
+        if let ObjectKind::Array { .. } = kind {
+            if name == "length" {
+                continue;
+            }
+        }
+        if depth >= options.max_depth || properties.len() >= options.max_properties {
+            overflow = true;
+            break;
+        }
+        let descriptor = try!(object.get_own_property_descriptor(cx, &name));
+        let value = match descriptor.value {
+            Some(value) => {
+                PropertyValue::Data(try!(preview_at_depth(cx, value, options, depth + 1)))
+            }
+            None => PropertyValue::Accessor {
+                has_getter: try!(is_callable(cx, &descriptor.get)),
+                has_setter: try!(is_callable(cx, &descriptor.set))
+            }
+        };
+        properties.push(PropertyPreview {
+            name: name,
+            value: value
+        });
+    }
+
+    Ok(ObjectPreview {
+        object: object,
+        class_name: class_name,
+        kind: kind,
+        properties: properties,
+        overflow: overflow
+    })
+}
+
+fn is_callable<'rt>(cx: DebugContext<'rt>, value: &Option<Value<'rt>>) -> Result<'rt, bool> {
+    match *value {
+        Some(Value::Object(ref object)) => object.get_is_callable(cx),
+        _ => Ok(false)
+    }
+}
@@ -0,0 +1,410 @@
+use context::DebugContext;
+use debugger::Debugger;
+use exception::Result;
+use js::jsapi;
+use js::jsapi::{JSContext, JSRuntime};
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
+use std::io::Write;
+use std::rc::{Rc, Weak};
+use std::sync::Arc;
+use std::sync::atomic::{self, AtomicBool};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use trace;
+use trace::Teardown;
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FrameKey {
+    pub function: String,
+    pub url: Option<String>,
+    pub line: Option<usize>
+}
+
+impl FrameKey {
+    pub fn label(&self) -> String {
+        let label = match (self.url.as_ref(), self.line) {
+            (Some(url), Some(line)) => format!("{} ({}:{})", self.function, url, line),
+            (Some(url), None) => format!("{} ({})", self.function, url),
+            _ => self.function.clone()
+        };
+        label.replace(';', ":")
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProfileEntry {
+    pub frame: FrameKey,
+    pub self_samples: u64,
+    pub total_samples: u64
+}
+
+#[derive(Clone, Default)]
+pub struct Profile {
+    stacks: BTreeMap<Vec<FrameKey>, u64>,
+    samples: u64
+}
+
+impl Profile {
+    pub fn new() -> Profile {
+        Profile::default()
+    }
+
+    pub fn add_sample(&mut self, stack: Vec<FrameKey>) {
+        if stack.is_empty() {
+            return;
+        }
+        *self.stacks.entry(stack).or_insert(0) += 1;
+        self.samples += 1;
+    }
+
+    pub fn get_sample_count(&self) -> u64 {
+        self.samples
+    }
+
+    pub fn get_entries(&self) -> Vec<ProfileEntry> {
+        let mut entries: BTreeMap<&FrameKey, (u64, u64)> = BTreeMap::new();
+        for (stack, &count) in &self.stacks {
+            let leaf = stack.last().unwrap();
+            entries.entry(leaf).or_insert((0, 0)).0 += count;
+            let frames: BTreeSet<&FrameKey> = stack.iter().collect();
+            for frame in frames {
+                entries.entry(frame).or_insert((0, 0)).1 += count;
+            }
+        }
+        let mut entries: Vec<ProfileEntry> = entries.into_iter().map(|(frame, counts)| {
+            ProfileEntry {
+                frame: frame.clone(),
+                self_samples: counts.0,
+                total_samples: counts.1
+            }
+        }).collect();
+        entries.sort_by(|a, b| {
+            match b.self_samples.cmp(&a.self_samples) {
+                Ordering::Equal => b.total_samples.cmp(&a.total_samples),
+                ordering => ordering
+            }
+        });
+        entries
+    }
+
+    pub fn write_folded<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        for (stack, count) in &self.stacks {
+            let labels: Vec<String> = stack.iter().map(|frame| frame.label()).collect();
+            try!(writeln!(writer, "{} {}", labels.join(";"), count));
+        }
+        Ok(())
+    }
+
+    pub fn write_table<W: Write>(&self, writer: &mut W, interval: Duration) -> io::Result<()> {
+        let interval = interval.as_secs() as f64 * 1000.0 +
+                       interval.subsec_nanos() as f64 / 1000000.0;
+        let samples = if self.samples == 0 { 1.0 } else { self.samples as f64 };
+        try!(writeln!(
+            writer,
+            "{:>10} {:>7} {:>10} {:>7}  {}",
+            "self ms",
+            "self %",
+            "total ms",
+            "total %",
+            "function"
+        ));
+        for entry in self.get_entries() {
+            try!(writeln!(
+                writer,
+                "{:>10.1} {:>6.1}% {:>10.1} {:>6.1}%  {}",
+                entry.self_samples as f64 * interval,
+                entry.self_samples as f64 * 100.0 / samples,
+                entry.total_samples as f64 * interval,
+                entry.total_samples as f64 * 100.0 / samples,
+                entry.frame.label()
+            ));
+        }
+        Ok(())
+    }
+}
+
+struct SendContext(*mut JSContext);
+
+unsafe impl Send for SendContext {}
+
+#[derive(Clone, Copy)]
+struct Target {
+    profiler: *const (),
+    sample: unsafe fn(*const (), *mut JSContext)
+}
+
+unsafe fn sample_profiler<'rt>(profiler: *const (), cx: *mut JSContext) {
+    let profiler = &*(profiler as *const Profiler<'rt>);
+    let _ = profiler.sample(DebugContext::from_raw(cx));
+}
+
+struct Sampler {
+    target: Target,
+    running: Arc<AtomicBool>,
+    requested: Arc<AtomicBool>,
+    thread: JoinHandle<()>
+}
+
+struct Interrupts {
+    runtime: *mut JSRuntime,
+    sampler: RefCell<Option<Sampler>>
+}
+
+thread_local!(static INTERRUPTS: RefCell<Vec<Rc<Interrupts>>> = RefCell::new(Vec::new()));
+
+impl Interrupts {
+    fn get(cx: DebugContext) -> Rc<Interrupts> {
+        let rt = cx.runtime();
+        if let Some(interrupts) = Interrupts::find(rt) {
+            return interrupts;
+        }
+        let interrupts = Rc::new(Interrupts {
+            runtime: rt,
+            sampler: RefCell::new(None)
+        });
+        unsafe {
+            jsapi::JS_AddInterruptCallback(cx.as_raw(), Some(interrupt_callback));
+        }
+        INTERRUPTS.with(|known| known.borrow_mut().push(interrupts.clone()));
+        trace::on_teardown(cx.as_raw(), Rc::downgrade(&interrupts) as Weak<Teardown>);
+        interrupts
+    }
+
+    fn find(rt: *mut JSRuntime) -> Option<Rc<Interrupts>> {
+        INTERRUPTS.with(|interrupts| {
+            interrupts.borrow().iter().find(|interrupts| interrupts.runtime == rt).cloned()
+        })
+    }
+
+    fn is_sampling(&self, profiler: *const ()) -> bool {
+        match *self.sampler.borrow() {
+            Some(ref sampler) => sampler.target.profiler == profiler,
+            None => false
+        }
+    }
+
+    fn start(&self, cx: DebugContext, target: Target, interval: Duration) {
+        self.stop();
+        let running = Arc::new(AtomicBool::new(true));
+        let requested = Arc::new(AtomicBool::new(false));
+        let context = SendContext(cx.as_raw());
+        let thread = {
+            let running = running.clone();
+            let requested = requested.clone();
+            thread::spawn(move || {
+                let context = context;
+                while running.load(atomic::Ordering::SeqCst) {
+                    thread::sleep(interval);
+                    if running.load(atomic::Ordering::SeqCst) {
+                        requested.store(true, atomic::Ordering::SeqCst);
+                        unsafe {
+                            jsapi::JS_RequestInterruptCallback(context.0);
+                        }
+                    }
+                }
+            })
+        };
+        *self.sampler.borrow_mut() = Some(Sampler {
+            target: target,
+            running: running,
+            requested: requested,
+            thread: thread
+        });
+    }
+
+    fn stop(&self) {
+        if let Some(sampler) = self.sampler.borrow_mut().take() {
+            sampler.running.store(false, atomic::Ordering::SeqCst);
+            let _ = sampler.thread.join();
+        }
+    }
+
+    unsafe fn sample(&self, cx: *mut JSContext) {
+        let target = match *self.sampler.borrow() {
+            Some(ref sampler) if sampler.requested.swap(false, atomic::Ordering::SeqCst) => {
+                sampler.target
+            }
+            _ => return
+        };
+        (target.sample)(target.profiler, cx);
+    }
+}
+
+impl Teardown for Interrupts {
+    fn teardown(&self) {
+        self.stop();
+        let rt = self.runtime;
+        INTERRUPTS.with(|interrupts| {
+            interrupts.borrow_mut().retain(|interrupts| interrupts.runtime != rt);
+        });
+    }
+}
+
+unsafe extern "C" fn interrupt_callback(cx: *mut JSContext) -> bool {
+    if let Some(interrupts) = Interrupts::find(jsapi::JS_GetRuntime(cx)) {
+        interrupts.sample(cx);
+    }
+    true
+}
+
+pub struct Profiler<'rt> {
+    debugger: Rc<Debugger<'rt>>,
+    interval: Duration,
+    profile: RefCell<Profile>,
+    sampling: Cell<bool>
+}
+
+impl<'rt> Profiler<'rt> {
+    pub fn new(debugger: Rc<Debugger<'rt>>, interval: Duration) -> Rc<Profiler<'rt>> {
+        Rc::new(Profiler {
+            debugger: debugger,
+            interval: interval,
+            profile: RefCell::new(Profile::new()),
+            sampling: Cell::new(false)
+        })
+    }
+
+    pub fn get_interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn is_running(&self) -> bool {
+        let this = self.as_target_ptr();
+        INTERRUPTS.with(|interrupts| {
+            interrupts.borrow().iter().any(|interrupts| interrupts.is_sampling(this))
+        })
+    }
+
+    pub fn start(&self, cx: DebugContext<'rt>) {
+        if self.is_running() {
+            return;
+        }
+        let target = Target {
+            profiler: self.as_target_ptr(),
+            sample: sample_profiler
+        };
+        Interrupts::get(cx).start(cx, target, self.interval);
+    }
+
+    pub fn stop(&self) {
+        let this = self.as_target_ptr();
+        let interrupts: Vec<Rc<Interrupts>> = INTERRUPTS.with(|interrupts| {
+            interrupts.borrow().iter().filter(|interrupts| {
+                interrupts.is_sampling(this)
+            }).cloned().collect()
+        });
+        for interrupts in interrupts {
+            interrupts.stop();
+        }
+    }
+
+    pub fn sample(&self, cx: DebugContext<'rt>) -> Result<'rt, ()> {
+        if self.sampling.get() {
+            return Ok(());
+        }
+        self.sampling.set(true);
+        let stack = self.capture_stack(cx);
+        self.sampling.set(false);
+        self.profile.borrow_mut().add_sample(try!(stack));
+        Ok(())
+    }
+
+    pub fn get_profile(&self) -> Profile {
+        self.profile.borrow().clone()
+    }
+
+    pub fn clear(&self) {
+        *self.profile.borrow_mut() = Profile::new();
+    }
+
+    pub fn write_folded<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.profile.borrow().write_folded(writer)
+    }
+
+    pub fn write_table<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        self.profile.borrow().write_table(writer, self.interval)
+    }
+
+    fn as_target_ptr(&self) -> *const () {
+        self as *const Profiler<'rt> as *const ()
+    }
+
+    fn capture_stack(&self, cx: DebugContext<'rt>) -> Result<'rt, Vec<FrameKey>> {
+        let mut stack = Vec::new();
+        let mut current = try!(self.debugger.get_newest_frame(cx));
+        while let Some(frame) = current {
+            let (url, line) = match try!(frame.get_script(cx)) {
+                Some(script) => {
+                    let offset = try!(frame.get_offset(cx));
+                    let location = try!(script.get_offset_location(cx, offset));
+                    (Some(try!(script.get_url(cx))), Some(location.line_number))
+                }
+                None => (None, None)
+            };
+            stack.push(FrameKey {
+                function: try!(frame.get_function_name(cx)),
+                url: url,
+                line: line
+            });
+            current = try!(frame.get_older(cx));
+        }
+        stack.reverse();
+        Ok(stack)
+    }
+}
+
+impl<'rt> Drop for Profiler<'rt> {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::{FrameKey, Profile};
+
+    fn frame(function: &str, line: usize) -> FrameKey {
+        FrameKey {
+            function: function.to_string(),
+            url: Some("a.js".to_string()),
+            line: Some(line)
+        }
+    }
+
+    #[test]
+    fn profile() {
+        let mut profile = Profile::new();
+        profile.add_sample(vec![frame("main", 1), frame("f", 5)]);
+        profile.add_sample(vec![frame("main", 1), frame("f", 5)]);
+        profile.add_sample(vec![frame("main", 1), frame("g", 9), frame("g", 10)]);
+        profile.add_sample(vec![frame("main", 1)]);
+        profile.add_sample(Vec::new());
+        assert_eq!(profile.get_sample_count(), 4);
+
+        let entries = profile.get_entries();
+        assert_eq!(entries[0].frame, frame("f", 5));
+        assert_eq!((entries[0].self_samples, entries[0].total_samples), (2, 2));
+        let main = entries.iter().find(|entry| entry.frame == frame("main", 1)).unwrap();
+        assert_eq!((main.self_samples, main.total_samples), (1, 4));
+
+        let mut output = Vec::new();
+        profile.write_folded(&mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), [
+            "main (a.js:1) 1",
+            "main (a.js:1);f (a.js:5) 2",
+            "main (a.js:1);g (a.js:9);g (a.js:10) 1",
+            ""
+        ].join("\n"));
+
+        let mut output = Vec::new();
+        profile.write_table(&mut output, Duration::from_millis(10)).unwrap();
+        let table = String::from_utf8(output).unwrap();
+        assert!(table.lines().nth(1).unwrap().ends_with("f (a.js:5)"));
+        assert!(table.contains("20.0   50.0%"));
+    }
+}
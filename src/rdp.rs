@@ -0,0 +1,854 @@
+use context::DebugContext;
+use debugger::{Debugger, OnDebuggerStatement, OnNewScript, ScriptQuery};
+use environment::Environment;
+use exception::{Exception, Result};
+use frame::Frame;
+use object::Object;
+use pause::PauseController;
+use registry::{Handle, HandleRegistry, Lifetime};
+use rooted::Rooted;
+use script::{OnHit, Script};
+use serde_json;
+use serde_json::Value as Json;
+use serde_json::map::Map;
+use session::{
+    BreakpointSpec,
+    Breakpoints,
+    Connection,
+    Dispatcher,
+    OnPauseRequest,
+    PauseRequest
+};
+use source::Source;
+use std::cell::{Cell, RefCell};
+use std::io;
+use std::io::{Read, Write};
+use std::mem;
+use std::net::{TcpListener, TcpStream};
+use std::rc::{Rc, Weak};
+use std::result;
+use stepper::{OnStepComplete, StepKind, Stepper};
+use value::{CompletionValue, ResumptionValue, Value};
+
+const ROOT_ACTOR: &'static str = "root";
+const TAB_ACTOR: &'static str = "tab1";
+const THREAD_ACTOR: &'static str = "thread1";
+const CONSOLE_ACTOR: &'static str = "console1";
+const MAX_PACKET_LENGTH: usize = 16 * 1024 * 1024;
+
+enum Error<'rt> {
+    Exception(Exception<'rt>),
+    MissingParameter(&'static str),
+    NoSuchActor(String),
+    UnrecognizedPacketType(String),
+    WrongState
+}
+
+impl<'rt> From<Exception<'rt>> for Error<'rt> {
+    fn from(exception: Exception<'rt>) -> Error<'rt> {
+        Error::Exception(exception)
+    }
+}
+
+impl<'rt> Error<'rt> {
+    fn to_json(&self, from: &str) -> Json {
+        let (error, message) = match *self {
+            Error::Exception(_) => ("unknownError", "Uncaught exception in debugger".to_string()),
+            Error::MissingParameter(name) => ("missingParameter", format!("Missing {}", name)),
+            Error::NoSuchActor(ref actor) => ("noSuchActor", format!("No such actor {}", actor)),
+            Error::UnrecognizedPacketType(ref kind) => {
+                ("unrecognizedPacketType", format!("Actor does not recognize '{}'", kind))
+            }
+            Error::WrongState => ("wrongState", "Thread is not paused".to_string())
+        };
+        json!({ "from": from, "error": error, "message": message })
+    }
+}
+
+type PacketResult<'rt> = result::Result<Json, Error<'rt>>;
+
+enum Actor<'rt> {
+    Source(Source<'rt>, String),
+    Breakpoint(u64)
+}
+
+impl<'rt> Actor<'rt> {
+    fn to_owned_in(&self, cx: DebugContext<'rt>) -> Actor<'rt> {
+        match *self {
+            Actor::Source(ref source, ref url) => {
+                Actor::Source(Source::new(cx, source.get()), url.clone())
+            }
+            Actor::Breakpoint(id) => Actor::Breakpoint(id)
+        }
+    }
+}
+
+fn grip_prefix<'rt>(handle: &Handle<'rt>) -> &'static str {
+    match *handle {
+        Handle::Object(_) => "obj",
+        Handle::Environment(_) => "environment",
+        Handle::Frame(_) => "frame"
+    }
+}
+
+pub struct Server<'rt>(Rc<Session<'rt>>);
+
+impl<'rt> Server<'rt> {
+    pub fn accept(
+        cx: DebugContext<'rt>,
+        debugger: Rc<Debugger<'rt>>,
+        listener: &TcpListener
+    ) -> io::Result<Server<'rt>> {
+        let (stream, _) = try!(listener.accept());
+        let session = Session::new(debugger, stream);
+        try!(session.install(cx).map_err(|_| {
+            io::Error::new(io::ErrorKind::Other, "Failed to install debugger hooks")
+        }));
+        session.send(json!({
+            "from": ROOT_ACTOR,
+            "applicationType": "browser",
+            "traits": {}
+        }));
+        Ok(Server(session))
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.0.connected.get()
+    }
+
+    pub fn poll(&self, cx: DebugContext<'rt>) -> Result<'rt, bool> {
+        while self.0.connected.get() && self.0.has_packet() {
+            try!(self.0.process_packet(cx, None));
+        }
+        Ok(self.0.connected.get())
+    }
+}
+
+struct Session<'rt> {
+    this: RefCell<Weak<Session<'rt>>>,
+    debugger: Rc<Debugger<'rt>>,
+    stream: RefCell<TcpStream>,
+    controller: RefCell<Option<Rc<PauseController<'rt>>>>,
+    pause_request: RefCell<Option<Rc<PauseRequest<'rt>>>>,
+    stepper: Stepper<'rt>,
+    actors: RefCell<Vec<(String, Actor<'rt>)>>,
+    breakpoints: Breakpoints<'rt>,
+    grips: HandleRegistry<'rt>,
+    next_id: Cell<usize>,
+    pause_reason: RefCell<Json>,
+    attached: Cell<bool>,
+    connected: Cell<bool>
+}
+
+impl<'rt> Session<'rt> {
+    fn new(debugger: Rc<Debugger<'rt>>, stream: TcpStream) -> Rc<Session<'rt>> {
+        let session = Rc::new(Session {
+            this: RefCell::new(Weak::new()),
+            debugger: debugger.clone(),
+            stream: RefCell::new(stream),
+            controller: RefCell::new(None),
+            pause_request: RefCell::new(None),
+            stepper: Stepper::new(debugger.clone()),
+            actors: RefCell::new(Vec::new()),
+            breakpoints: Breakpoints::new(debugger.clone()),
+            grips: HandleRegistry::new(),
+            next_id: Cell::new(1),
+            pause_reason: RefCell::new(Json::Null),
+            attached: Cell::new(false),
+            connected: Cell::new(true)
+        });
+        *session.this.borrow_mut() = Rc::downgrade(&session);
+        let dispatcher = Rc::new(Dispatcher(Rc::downgrade(&session)));
+        *session.controller.borrow_mut() = Some(Rc::new(PauseController::new(dispatcher)));
+        let target = Rc::downgrade(&session) as Weak<OnPauseRequest<'rt> + 'rt>;
+        *session.pause_request.borrow_mut() = Some(PauseRequest::new(debugger, target));
+        session
+    }
+
+    fn this(&self) -> Rc<Session<'rt>> {
+        self.this.borrow().upgrade().unwrap()
+    }
+
+    fn controller(&self) -> Rc<PauseController<'rt>> {
+        self.controller.borrow().as_ref().unwrap().clone()
+    }
+
+    fn pause_request(&self) -> Rc<PauseRequest<'rt>> {
+        self.pause_request.borrow().as_ref().unwrap().clone()
+    }
+
+    fn install(&self, cx: DebugContext<'rt>) -> Result<'rt, ()> {
+        try!(self.debugger.set_on_new_script(cx, Some(self.this() as Rc<OnNewScript<'rt> + 'rt>)));
+        try!(self.debugger.set_on_debugger_statement(
+            cx,
+            Some(self.this() as Rc<OnDebuggerStatement<'rt> + 'rt>)
+        ));
+        Ok(())
+    }
+
+    fn send(&self, packet: Json) {
+        let body = packet.to_string();
+        let mut stream = self.stream.borrow_mut();
+        if write!(stream, "{}:{}", body.len(), body).and_then(|_| stream.flush()).is_err() {
+            self.connected.set(false);
+        }
+    }
+
+    fn has_packet(&self) -> bool {
+        let stream = self.stream.borrow();
+        if stream.set_nonblocking(true).is_err() {
+            return false;
+        }
+        let mut buffer = [0; 1];
+        let result = stream.peek(&mut buffer);
+        let _ = stream.set_nonblocking(false);
+        match result {
+            Ok(_) => true,
+            Err(ref error) => error.kind() != io::ErrorKind::WouldBlock
+        }
+    }
+
+    fn read_packet(&self) -> io::Result<Json> {
+        let mut stream = self.stream.borrow_mut();
+        let mut length = 0usize;
+        loop {
+            let mut byte = [0; 1];
+            try!(stream.read_exact(&mut byte));
+            match byte[0] {
+                b':' => break,
+                digit @ b'0'...b'9' => length = length * 10 + (digit - b'0') as usize,
+                _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "bad packet length"))
+            }
+            if length > MAX_PACKET_LENGTH {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "packet too long"));
+            }
+        }
+        let mut body = vec![0; length];
+        try!(stream.read_exact(&mut body));
+        serde_json::from_slice(&body).map_err(|error| {
+            io::Error::new(io::ErrorKind::InvalidData, error)
+        })
+    }
+
+    fn process_packet(
+        &self,
+        cx: DebugContext<'rt>,
+        controller: Option<&PauseController<'rt>>
+    ) -> Result<'rt, ()> {
+        let packet = match self.read_packet() {
+            Ok(packet) => packet,
+            Err(_) => {
+                self.connected.set(false);
+                if let Some(controller) = controller {
+                    controller.resume(None);
+                }
+                return Ok(());
+            }
+        };
+        let to = packet["to"].as_str().unwrap_or(ROOT_ACTOR).to_string();
+        let kind = packet["type"].as_str().unwrap_or("").to_string();
+        let response = match self.dispatch(cx, controller, &to, &kind, &packet) {
+            Ok(mut response) => {
+                response["from"] = json!(to);
+                response
+            }
+            Err(error) => error.to_json(&to)
+        };
+        self.send(response);
+        Ok(())
+    }
+
+    fn dispatch(
+        &self,
+        cx: DebugContext<'rt>,
+        controller: Option<&PauseController<'rt>>,
+        to: &str,
+        kind: &str,
+        packet: &Json
+    ) -> PacketResult<'rt> {
+        match (to, kind) {
+            (ROOT_ACTOR, "listTabs") => Ok(json!({
+                "tabs": [{
+                    "actor": TAB_ACTOR,
+                    "title": "JavaScript",
+                    "url": "",
+                    "consoleActor": CONSOLE_ACTOR
+                }],
+                "selected": 0
+            })),
+            (TAB_ACTOR, "attach") => Ok(json!({
+                "type": "tabAttached",
+                "threadActor": THREAD_ACTOR
+            })),
+            (TAB_ACTOR, "detach") => Ok(json!({ "type": "detached" })),
+            (THREAD_ACTOR, "attach") => {
+                self.attached.set(true);
+                Ok(json!({ "type": "paused", "why": { "type": "attached" } }))
+            }
+            (THREAD_ACTOR, "detach") => {
+                self.attached.set(false);
+                try!(self.pause_request().cancel(cx));
+                self.grips.clear();
+                if let Some(controller) = controller {
+                    controller.resume(None);
+                }
+                Ok(json!({ "type": "detached" }))
+            }
+            (THREAD_ACTOR, "resume") => self.resume(cx, controller, packet),
+            (THREAD_ACTOR, "interrupt") => {
+                *self.pause_reason.borrow_mut() = json!({ "type": "interrupted" });
+                try!(self.pause_request().request(cx));
+                Ok(json!({}))
+            }
+            (THREAD_ACTOR, "frames") => self.frames(cx, controller, packet),
+            (THREAD_ACTOR, "sources") => self.sources(cx),
+            (THREAD_ACTOR, "releaseMany") => {
+                let actors = try!(packet["actors"].as_array().ok_or(
+                    Error::MissingParameter("actors")
+                ));
+                for name in actors.iter().filter_map(|name| name.as_str()) {
+                    if let Some((id, _)) = self.get_grip(cx, name) {
+                        self.grips.release(id);
+                    }
+                }
+                Ok(json!({}))
+            }
+            (CONSOLE_ACTOR, "evaluateJS") => self.evaluate(cx, packet),
+            (ROOT_ACTOR, _) | (TAB_ACTOR, _) | (THREAD_ACTOR, _) | (CONSOLE_ACTOR, _) => {
+                Err(Error::UnrecognizedPacketType(kind.to_string()))
+            }
+            _ => {
+                if let Some((id, handle)) = self.get_grip(cx, to) {
+                    return self.dispatch_grip(cx, id, handle, kind);
+                }
+                let actor = match self.get_actor(cx, to) {
+                    Some(actor) => actor,
+                    None => return Err(Error::NoSuchActor(to.to_string()))
+                };
+                match (actor, kind) {
+                    (Actor::Source(source, _), "source") => {
+                        Ok(json!({ "source": try!(source.get_text(cx)) }))
+                    }
+                    (Actor::Source(_, url), "setBreakpoint") => {
+                        self.set_breakpoint(cx, &url, packet)
+                    }
+                    (Actor::Breakpoint(_), "delete") => self.delete_breakpoint(cx, to),
+                    _ => Err(Error::UnrecognizedPacketType(kind.to_string()))
+                }
+            }
+        }
+    }
+
+    fn dispatch_grip(
+        &self,
+        cx: DebugContext<'rt>,
+        id: u64,
+        handle: Handle<'rt>,
+        kind: &str
+    ) -> PacketResult<'rt> {
+        match (handle, kind) {
+            (Handle::Environment(environment), "bindings") => {
+                Ok(json!({ "bindings": try!(self.bindings(cx, &environment)) }))
+            }
+            (Handle::Object(object), "prototypeAndProperties") => {
+                self.prototype_and_properties(cx, &object)
+            }
+            (Handle::Object(object), "ownPropertyNames") => {
+                Ok(json!({ "ownPropertyNames": try!(object.get_own_property_names(cx)) }))
+            }
+            (Handle::Object(object), "threadGrip") => {
+                self.grips.promote(id);
+                self.grip(cx, Value::Object(object))
+            }
+            (_, "release") => {
+                if self.grips.get_lifetime(id) != Some(Lifetime::Thread) {
+                    return Err(Error::UnrecognizedPacketType(kind.to_string()));
+                }
+                self.grips.release(id);
+                Ok(json!({}))
+            }
+            _ => Err(Error::UnrecognizedPacketType(kind.to_string()))
+        }
+    }
+
+    fn register_actor(&self, prefix: &str, actor: Actor<'rt>) -> String {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        let name = format!("{}{}", prefix, id);
+        self.actors.borrow_mut().push((name.clone(), actor));
+        name
+    }
+
+    fn get_actor(&self, cx: DebugContext<'rt>, name: &str) -> Option<Actor<'rt>> {
+        self.actors.borrow().iter().find(|&&(ref actor_name, _)| actor_name == name).map(|entry| {
+            entry.1.to_owned_in(cx)
+        })
+    }
+
+    fn register_grip(&self, cx: DebugContext<'rt>, handle: Handle<'rt>) -> Result<'rt, String> {
+        let prefix = grip_prefix(&handle);
+        let id = try!(self.grips.register(cx, handle, Lifetime::Pause));
+        Ok(format!("{}{}", prefix, id))
+    }
+
+    fn get_grip(&self, cx: DebugContext<'rt>, name: &str) -> Option<(u64, Handle<'rt>)> {
+        let digits = name.trim_left_matches(|c: char| c.is_alphabetic());
+        let id = match digits.parse::<u64>() {
+            Ok(id) => id,
+            Err(_) => return None
+        };
+        match self.grips.get(cx, id) {
+            Some(handle) if name == format!("{}{}", grip_prefix(&handle), id) => Some((id, handle)),
+            _ => None
+        }
+    }
+
+    fn source_actor(&self, source: Source<'rt>, url: &str) -> String {
+        let found = self.actors.borrow().iter().find(|&&(_, ref actor)| match *actor {
+            Actor::Source(ref known, _) => known.get() == source.get(),
+            _ => false
+        }).map(|entry| entry.0.clone());
+        match found {
+            Some(name) => name,
+            None => self.register_actor("source", Actor::Source(source, url.to_string()))
+        }
+    }
+
+    fn resume(
+        &self,
+        cx: DebugContext<'rt>,
+        controller: Option<&PauseController<'rt>>,
+        packet: &Json
+    ) -> PacketResult<'rt> {
+        let controller = match controller {
+            Some(controller) => controller,
+            None => return Ok(json!({ "type": "resumed" }))
+        };
+        let kind = match packet["resumeLimit"]["type"].as_str() {
+            Some("next") => Some(StepKind::Over),
+            Some("step") => Some(StepKind::In),
+            Some("finish") => Some(StepKind::Out),
+            _ => None
+        };
+        if let Some(kind) = kind {
+            let frame = match controller.get_paused_frame(cx) {
+                Some(frame) => frame,
+                None => return Err(Error::WrongState)
+            };
+            try!(self.stepper.step(cx, kind, &frame, self.this() as Rc<OnStepComplete<'rt> + 'rt>));
+        }
+        controller.resume(None);
+        Ok(json!({ "type": "resumed" }))
+    }
+
+    fn frames(
+        &self,
+        cx: DebugContext<'rt>,
+        controller: Option<&PauseController<'rt>>,
+        packet: &Json
+    ) -> PacketResult<'rt> {
+        let controller = try!(controller.ok_or(Error::WrongState));
+        let start = packet["start"].as_u64().unwrap_or(0) as usize;
+        let count = packet["count"].as_u64().map(|count| count as usize);
+        let mut frames = Vec::new();
+        let mut current = controller.get_paused_frame(cx);
+        let mut depth = 0;
+        while let Some(frame) = current {
+            if count.map_or(false, |count| frames.len() >= count) {
+                break;
+            }
+            let older = try!(frame.get_older(cx));
+            if depth >= start {
+                frames.push(try!(self.frame_form(cx, frame, depth)));
+            }
+            depth += 1;
+            current = older;
+        }
+        Ok(json!({ "frames": frames }))
+    }
+
+    fn frame_form(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: Frame<'rt>,
+        depth: usize
+    ) -> PacketResult<'rt> {
+        let mut form = json!({
+            "depth": depth,
+            "type": "call",
+            "this": try!(self.grip(cx, try!(frame.get_this(cx))))
+        });
+        if let Some(callee) = try!(frame.get_callee(cx)) {
+            form["callee"] = try!(self.grip(cx, Value::Object(callee)));
+        }
+        if let Some(script) = try!(frame.get_script(cx)) {
+            let url = try!(script.get_url(cx));
+            let location = try!(script.get_offset_location(cx, try!(frame.get_offset(cx))));
+            let source = self.source_actor(try!(script.get_source(cx)), &url);
+            form["where"] = json!({
+                "source": { "actor": source, "url": url },
+                "line": location.line_number,
+                "column": location.column_number
+            });
+        }
+        if let Some(environment) = try!(frame.get_environment(cx)) {
+            form["environment"] = try!(self.environment_form(cx, environment));
+        }
+        form["actor"] = json!(try!(self.register_grip(cx, Handle::Frame(frame))));
+        Ok(form)
+    }
+
+    fn environment_form(
+        &self,
+        cx: DebugContext<'rt>,
+        environment: Environment<'rt>
+    ) -> PacketResult<'rt> {
+        let parent = try!(environment.get_parent(cx));
+        let mut form = json!({
+            "type": if parent.is_some() { "block" } else { "object" },
+            "bindings": try!(self.bindings(cx, &environment))
+        });
+        if let Some(parent) = parent {
+            form["parent"] = try!(self.environment_form(cx, parent));
+        }
+        form["actor"] = json!(try!(self.register_grip(cx, Handle::Environment(environment))));
+        Ok(form)
+    }
+
+    fn bindings(&self, cx: DebugContext<'rt>, environment: &Environment<'rt>) -> PacketResult<'rt> {
+        let mut variables = Map::new();
+        for name in try!(environment.names(cx)) {
+            let value = match try!(environment.get_variable(cx, &name)) {
+                Some(value) => try!(self.grip(cx, value)),
+                None => json!({ "optimizedOut": true })
+            };
+            variables.insert(name, json!({ "value": value }));
+        }
+        Ok(json!({ "arguments": [], "variables": variables }))
+    }
+
+    fn sources(&self, cx: DebugContext<'rt>) -> PacketResult<'rt> {
+        let mut sources = Vec::new();
+        for script in try!(self.debugger.find_scripts(cx, &ScriptQuery::default())) {
+            let url = try!(script.get_url(cx));
+            let actor = self.source_actor(try!(script.get_source(cx)), &url);
+            if !sources.iter().any(|source: &Json| source["actor"] == json!(actor)) {
+                sources.push(json!({ "actor": actor, "url": url, "isBlackBoxed": false }));
+            }
+        }
+        Ok(json!({ "sources": sources }))
+    }
+
+    fn set_breakpoint(&self, cx: DebugContext<'rt>, url: &str, packet: &Json) -> PacketResult<'rt> {
+        let line = try!(packet["location"]["line"].as_u64().ok_or(
+            Error::MissingParameter("location")
+        )) as usize;
+        let spec = BreakpointSpec {
+            url: url.to_string(),
+            line: line,
+            ..Default::default()
+        };
+        let id = try!(self.breakpoints.add(cx, spec, &(self.this() as Rc<OnHit<'rt> + 'rt>), None));
+        let is_pending = self.breakpoints.locations(cx, id).is_empty();
+        let actor = self.register_actor("breakpoint", Actor::Breakpoint(id));
+        Ok(json!({ "actor": actor, "isPending": is_pending }))
+    }
+
+    fn delete_breakpoint(&self, cx: DebugContext<'rt>, name: &str) -> PacketResult<'rt> {
+        let actor = {
+            let mut actors = self.actors.borrow_mut();
+            match actors.iter().position(|&(ref actor_name, _)| actor_name == name) {
+                Some(index) => actors.remove(index).1,
+                None => return Err(Error::NoSuchActor(name.to_string()))
+            }
+        };
+        if let Actor::Breakpoint(id) = actor {
+            try!(self.breakpoints.remove(cx, id));
+        }
+        Ok(json!({}))
+    }
+
+    fn evaluate(&self, cx: DebugContext<'rt>, packet: &Json) -> PacketResult<'rt> {
+        let text = try!(packet["text"].as_str().ok_or(Error::MissingParameter("text")));
+        let completion = match packet["frameActor"].as_str() {
+            Some(frame_actor) => match self.get_grip(cx, frame_actor) {
+                Some((_, Handle::Frame(frame))) => try!(frame.eval(cx, text)),
+                _ => return Err(Error::NoSuchActor(frame_actor.to_string()))
+            },
+            None => {
+                let global = match try!(self.debugger.get_debuggees(cx)).into_iter().next() {
+                    Some(global) => global,
+                    None => return Err(Error::WrongState)
+                };
+                try!(global.execute_in_global(cx, text))
+            }
+        };
+        let mut response = json!({ "input": text });
+        match completion {
+            CompletionValue::Return(value) => response["result"] = try!(self.grip(cx, value)),
+            CompletionValue::Throw(value) => {
+                response["result"] = json!({ "type": "undefined" });
+                response["exception"] = try!(self.grip(cx, value));
+            }
+            CompletionValue::Terminate => response["result"] = json!({ "type": "undefined" })
+        }
+        Ok(response)
+    }
+
+    fn prototype_and_properties(
+        &self,
+        cx: DebugContext<'rt>,
+        object: &Object<'rt>
+    ) -> PacketResult<'rt> {
+        let mut properties = Map::new();
+        for name in try!(object.get_own_property_names(cx)) {
+            let descriptor = try!(object.get_own_property_descriptor(cx, &name));
+            let mut form = json!({
+                "configurable": descriptor.configurable.unwrap_or(false),
+                "enumerable": descriptor.enumerable.unwrap_or(false)
+            });
+            if let Some(writable) = descriptor.writable {
+                form["writable"] = json!(writable);
+            }
+            if let Some(value) = descriptor.value {
+                form["value"] = try!(self.grip(cx, value));
+            }
+            if let Some(get) = descriptor.get {
+                form["get"] = try!(self.grip(cx, get));
+            }
+            if let Some(set) = descriptor.set {
+                form["set"] = try!(self.grip(cx, set));
+            }
+            properties.insert(name, form);
+        }
+        let prototype = match try!(object.get_proto(cx)) {
+            Some(prototype) => try!(self.grip(cx, Value::Object(prototype))),
+            None => json!({ "type": "null" })
+        };
+        Ok(json!({ "prototype": prototype, "ownProperties": properties }))
+    }
+
+    fn grip(&self, cx: DebugContext<'rt>, value: Value<'rt>) -> PacketResult<'rt> {
+        Ok(match value {
+            Value::Undefined => json!({ "type": "undefined" }),
+            Value::Null => json!({ "type": "null" }),
+            Value::Boolean(b) => json!(b),
+            Value::Int32(i) => json!(i),
+            Value::Double(d) => {
+                if d.is_nan() {
+                    json!({ "type": "NaN" })
+                } else if d.is_infinite() {
+                    json!({ "type": if d > 0.0 { "Infinity" } else { "-Infinity" } })
+                } else if d == 0.0 && d.is_sign_negative() {
+                    json!({ "type": "-0" })
+                } else {
+                    json!(d)
+                }
+            }
+            Value::String(s) => json!(s),
+            Value::Object(object) => {
+                let mut grip = json!({
+                    "type": "object",
+                    "class": try!(object.get_class(cx)),
+                    "extensible": try!(object.is_extensible(cx)),
+                    "frozen": try!(object.is_frozen(cx)),
+                    "sealed": try!(object.is_sealed(cx))
+                });
+                if try!(object.get_is_callable(cx)) {
+                    if let Some(name) = try!(object.get_name(cx)) {
+                        grip["name"] = json!(name);
+                    }
+                }
+                grip["actor"] = json!(try!(self.register_grip(cx, Handle::Object(object))));
+                grip
+            }
+        })
+    }
+
+    fn paused(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>,
+        why: Json
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        if !self.attached.get() || !self.connected.get() {
+            return Ok(None);
+        }
+        self.grips.enter_pause();
+        let form = match self.frame_form(cx, Frame::new(cx, frame.get()), 0) {
+            Ok(form) => form,
+            Err(Error::Exception(exception)) => return Err(exception),
+            Err(_) => Json::Null
+        };
+        self.send(json!({
+            "from": THREAD_ACTOR,
+            "type": "paused",
+            "frame": form,
+            "why": why
+        }));
+        let result = self.controller().pause(cx, frame);
+        self.grips.leave_pause();
+        result
+    }
+}
+
+impl<'rt> OnNewScript<'rt> for Session<'rt> {
+    fn on_new_script(&self, cx: DebugContext<'rt>, script: &Script<'rt>) -> Result<'rt, ()> {
+        self.breakpoints.resolve(cx, script)
+    }
+}
+
+impl<'rt> OnDebuggerStatement<'rt> for Session<'rt> {
+    fn on_debugger_statement(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        self.paused(cx, frame, json!({ "type": "debuggerStatement" }))
+    }
+}
+
+impl<'rt> OnHit<'rt> for Session<'rt> {
+    fn on_hit(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        self.paused(cx, frame, json!({ "type": "breakpoint" }))
+    }
+}
+
+impl<'rt> OnStepComplete<'rt> for Session<'rt> {
+    fn on_step_complete(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        self.paused(cx, frame, json!({ "type": "resumeLimit" }))
+    }
+}
+
+impl<'rt> OnPauseRequest<'rt> for Session<'rt> {
+    fn on_pause_request(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        let why = mem::replace(&mut *self.pause_reason.borrow_mut(), Json::Null);
+        self.paused(cx, frame, why)
+    }
+}
+
+impl<'rt> Connection<'rt> for Session<'rt> {
+    fn is_connected(&self) -> bool {
+        self.connected.get()
+    }
+
+    fn process_events(
+        &self,
+        cx: DebugContext<'rt>,
+        controller: &PauseController<'rt>
+    ) -> Result<'rt, ()> {
+        self.process_packet(cx, Some(controller))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Server;
+    use serde_json;
+    use serde_json::Value as Json;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+    use utils;
+
+    fn send(stream: &mut TcpStream, packet: Json) {
+        let body = packet.to_string();
+        write!(stream, "{}:{}", body.len(), body).unwrap();
+    }
+
+    fn receive(stream: &mut TcpStream) -> Json {
+        let mut length = 0;
+        loop {
+            let mut byte = [0; 1];
+            stream.read_exact(&mut byte).unwrap();
+            match byte[0] {
+                b':' => break,
+                digit => length = length * 10 + (digit - b'0') as usize
+            }
+        }
+        let mut body = vec![0; length];
+        stream.read_exact(&mut body).unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn request(stream: &mut TcpStream, to: &str, kind: &str, mut packet: Json) -> Json {
+        packet["to"] = json!(to);
+        packet["type"] = json!(kind);
+        send(stream, packet);
+        receive(stream)
+    }
+
+    #[test]
+    fn protocol_round_trip() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let (ready, wait) = mpsc::channel();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(address).unwrap();
+            assert_eq!(receive(&mut stream)["from"], json!("root"));
+            let tabs = request(&mut stream, "root", "listTabs", json!({}));
+            let tab = tabs["tabs"][0]["actor"].as_str().unwrap().to_string();
+            let attached = request(&mut stream, &tab, "attach", json!({}));
+            let thread = attached["threadActor"].as_str().unwrap().to_string();
+            let paused = request(&mut stream, &thread, "attach", json!({}));
+            assert_eq!(paused["type"], json!("paused"));
+            assert_eq!(paused["why"]["type"], json!("attached"));
+            let resumed = request(&mut stream, &thread, "resume", json!({}));
+            assert_eq!(resumed["type"], json!("resumed"));
+
+            let sources = request(&mut stream, &thread, "sources", json!({}));
+            let source = sources["sources"].as_array().unwrap().iter().find(|source| {
+                source["url"] == json!("test")
+            }).unwrap()["actor"].as_str().unwrap().to_string();
+            let breakpoint = request(&mut stream, &source, "setBreakpoint", json!({
+                "location": { "line": 2 }
+            }));
+            assert_eq!(breakpoint["isPending"], json!(false));
+            ready.send(()).unwrap();
+
+            let paused = receive(&mut stream);
+            assert_eq!(paused["type"], json!("paused"));
+            assert_eq!(paused["why"]["type"], json!("breakpoint"));
+            assert_eq!(paused["frame"]["where"]["line"], json!(2));
+            let frame = paused["frame"]["actor"].as_str().unwrap().to_string();
+            let evaluated = request(&mut stream, "console1", "evaluateJS", json!({
+                "text": "x + 1",
+                "frameActor": frame
+            }));
+            assert_eq!(evaluated["result"], json!(2));
+            let resumed = request(&mut stream, &thread, "resume", json!({}));
+            assert_eq!(resumed["type"], json!("resumed"));
+
+            stream.write_all(b"999999999999:").unwrap();
+        });
+
+        utils::with_debuggee(|cx, debugger, evaluate| {
+            evaluate("function f(x) {\n    return x;\n}\n");
+            let server = Server::accept(cx, debugger.clone(), &listener).unwrap();
+            while wait.try_recv().is_err() {
+                server.poll(cx).unwrap();
+                thread::sleep(Duration::from_millis(1));
+            }
+            evaluate("f(1);\n");
+            while server.poll(cx).unwrap() {
+                thread::sleep(Duration::from_millis(1));
+            }
+        });
+
+        client.join().unwrap();
+    }
+}
@@ -0,0 +1,661 @@
+use context::DebugContext;
+use convert::{ToJSValue, UndefinedOr};
+use debugger::Debugger;
+use environment::{BindingValue, Environment, EnvironmentType};
+use exception::{Exception, Result};
+use ext::HandleValueArrayExt;
+use js::jsapi;
+use js::jsapi::{HandleValueArray, JSObject};
+use js::jsval;
+use object::Object;
+use rooted::Rooted;
+use serde_json;
+use serde_json::Value as Json;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::ptr;
+use std::result;
+use trace::TracedBox;
+use value::Value;
+
+pub enum Error<'rt> {
+    Exception(Exception<'rt>),
+    Io(io::Error),
+    Json(serde_json::Error),
+    InvalidGraph(String)
+}
+
+impl<'rt> From<Exception<'rt>> for Error<'rt> {
+    fn from(exception: Exception<'rt>) -> Error<'rt> {
+        Error::Exception(exception)
+    }
+}
+
+impl<'rt> From<io::Error> for Error<'rt> {
+    fn from(error: io::Error) -> Error<'rt> {
+        Error::Io(error)
+    }
+}
+
+impl<'rt> From<serde_json::Error> for Error<'rt> {
+    fn from(error: serde_json::Error) -> Error<'rt> {
+        Error::Json(error)
+    }
+}
+
+impl<'rt> fmt::Debug for Error<'rt> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl<'rt> fmt::Display for Error<'rt> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Exception(_) => write!(f, "uncaught exception in debugger"),
+            Error::Io(ref error) => write!(f, "{}", error),
+            Error::Json(ref error) => write!(f, "{}", error),
+            Error::InvalidGraph(ref message) => write!(f, "invalid reachability graph: {}", message)
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Node {
+    pub kind: String,
+    pub class_name: String,
+    pub name: Option<String>,
+    pub estimated_size: u64
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub name: String
+}
+
+pub struct ReachabilityGraph {
+    nodes: Vec<Node>,
+    edges: Vec<Edge>,
+    roots: Vec<usize>,
+    outgoing: Vec<Vec<usize>>
+}
+
+impl ReachabilityGraph {
+    pub fn new(nodes: Vec<Node>, edges: Vec<Edge>, roots: Vec<usize>) -> ReachabilityGraph {
+        let mut outgoing = vec![Vec::new(); nodes.len()];
+        for (index, edge) in edges.iter().enumerate() {
+            outgoing[edge.from].push(index);
+        }
+        ReachabilityGraph {
+            nodes: nodes,
+            edges: edges,
+            roots: roots,
+            outgoing: outgoing
+        }
+    }
+
+    // This is a graph of what Debugger.Object reflection can reach from the debuggees and the live
+    // frames, not an engine heap dump: sizes are estimates, and Map, Set and WeakMap entries,
+    // buffer contents and engine-internal edges are not visited.
+    pub fn capture<'rt>(
+        cx: DebugContext<'rt>,
+        debugger: &Debugger<'rt>
+    ) -> Result<'rt, ReachabilityGraph> {
+        let mut capture = Capture {
+            ids: try!(IdMap::new(cx, debugger)),
+            nodes: Vec::new(),
+            queue: VecDeque::new()
+        };
+        let mut roots = Vec::new();
+        for global in try!(debugger.get_debuggees(cx)) {
+            roots.push(try!(capture.intern(cx, Target::Object(global))));
+        }
+        let mut current = try!(debugger.get_newest_frame(cx));
+        while let Some(frame) = current {
+            if let Some(environment) = try!(frame.get_environment(cx)) {
+                roots.push(try!(capture.intern(cx, Target::Environment(environment))));
+            }
+            if let Some(callee) = try!(frame.get_callee(cx)) {
+                roots.push(try!(capture.intern(cx, Target::Object(callee))));
+            }
+            if let Value::Object(this) = try!(frame.get_this(cx)) {
+                roots.push(try!(capture.intern(cx, Target::Object(this))));
+            }
+            current = try!(frame.get_older(cx));
+        }
+        roots.sort();
+        roots.dedup();
+        let mut edges = Vec::new();
+        while let Some((from, target)) = capture.queue.pop_front() {
+            let (size, references) = try!(target.get_references(cx));
+            capture.nodes[from].estimated_size = size;
+            for (name, target) in references {
+                let to = try!(capture.intern(cx, target));
+                edges.push(Edge {
+                    from: from,
+                    to: to,
+                    name: name
+                });
+            }
+        }
+        Ok(ReachabilityGraph::new(capture.nodes, edges, roots))
+    }
+
+    pub fn parse<'rt>(text: &str) -> result::Result<ReachabilityGraph, Error<'rt>> {
+        let json: Json = try!(serde_json::from_str(text));
+        let invalid = |message: &str| Error::InvalidGraph(message.to_string());
+        let mut nodes = Vec::new();
+        for node in try!(json["nodes"].as_array().ok_or_else(|| invalid("missing nodes"))) {
+            nodes.push(Node {
+                kind: try!(node["kind"].as_str().ok_or_else(|| {
+                    invalid("missing node kind")
+                })).to_string(),
+                class_name: node["className"].as_str().unwrap_or("").to_string(),
+                name: node["name"].as_str().map(|name| name.to_string()),
+                estimated_size: node["estimatedSize"].as_u64().unwrap_or(0)
+            });
+        }
+        let index = |value: &Json| -> result::Result<usize, Error<'rt>> {
+            match value.as_u64() {
+                Some(index) if (index as usize) < nodes.len() => Ok(index as usize),
+                _ => Err(invalid("node index out of range"))
+            }
+        };
+        let mut edges = Vec::new();
+        for edge in try!(json["edges"].as_array().ok_or_else(|| invalid("missing edges"))) {
+            edges.push(Edge {
+                from: try!(index(&edge["from"])),
+                to: try!(index(&edge["to"])),
+                name: edge["name"].as_str().unwrap_or("").to_string()
+            });
+        }
+        let mut roots = Vec::new();
+        for root in try!(json["roots"].as_array().ok_or_else(|| invalid("missing roots"))) {
+            roots.push(try!(index(root)));
+        }
+        Ok(ReachabilityGraph::new(nodes, edges, roots))
+    }
+
+    pub fn load<'rt, P: AsRef<Path>>(path: P) -> result::Result<ReachabilityGraph, Error<'rt>> {
+        let mut text = String::new();
+        try!(try!(File::open(path)).read_to_string(&mut text));
+        ReachabilityGraph::parse(&text)
+    }
+
+    pub fn to_json(&self) -> Json {
+        let nodes: Vec<Json> = self.nodes.iter().map(|node| {
+            json!({
+                "kind": node.kind,
+                "className": node.class_name,
+                "name": node.name,
+                "estimatedSize": node.estimated_size
+            })
+        }).collect();
+        let edges: Vec<Json> = self.edges.iter().map(|edge| {
+            json!({
+                "from": edge.from,
+                "to": edge.to,
+                "name": edge.name
+            })
+        }).collect();
+        json!({
+            "nodes": nodes,
+            "edges": edges,
+            "roots": self.roots
+        })
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        try!(serde_json::to_writer(&mut *writer, &self.to_json()));
+        writer.write_all(b"\n")
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write(&mut try!(File::create(path)))
+    }
+
+    pub fn get_nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    pub fn get_edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    pub fn get_roots(&self) -> &[usize] {
+        &self.roots
+    }
+
+    pub fn get_outgoing_edges(&self, node: usize) -> Vec<&Edge> {
+        self.outgoing[node].iter().map(|&index| &self.edges[index]).collect()
+    }
+
+    pub fn dominators(&self) -> Vec<Option<usize>> {
+        let (order, idom) = self.compute_dominators();
+        let root = self.nodes.len();
+        let mut result = vec![None; self.nodes.len()];
+        for &node in &order {
+            if node != root && idom[node] != root {
+                result[node] = Some(idom[node]);
+            }
+        }
+        result
+    }
+
+    pub fn retained_sizes(&self) -> Vec<u64> {
+        let (order, idom) = self.compute_dominators();
+        let root = self.nodes.len();
+        let mut sizes: Vec<u64> = self.nodes.iter().map(|node| node.estimated_size).collect();
+        for &node in order.iter().rev() {
+            if node != root && idom[node] != root {
+                sizes[idom[node]] += sizes[node];
+            }
+        }
+        sizes
+    }
+
+    pub fn shortest_path(&self, target: usize) -> Option<Vec<usize>> {
+        let mut parents: Vec<Option<Option<usize>>> = vec![None; self.nodes.len()];
+        let mut queue = VecDeque::new();
+        for &root in &self.roots {
+            if parents[root].is_none() {
+                parents[root] = Some(None);
+                queue.push_back(root);
+            }
+        }
+        while let Some(node) = queue.pop_front() {
+            if node == target {
+                break;
+            }
+            for &index in &self.outgoing[node] {
+                let to = self.edges[index].to;
+                if parents[to].is_none() {
+                    parents[to] = Some(Some(index));
+                    queue.push_back(to);
+                }
+            }
+        }
+        let mut path = Vec::new();
+        let mut current = target;
+        loop {
+            match parents[current] {
+                Some(Some(index)) => {
+                    path.push(index);
+                    current = self.edges[index].from;
+                }
+                Some(None) => break,
+                None => return None
+            }
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    pub fn describe_path(&self, path: &[usize]) -> String {
+        let mut description = match path.first() {
+            Some(&index) => self.describe_node(self.edges[index].from),
+            None => return String::new()
+        };
+        for &index in path {
+            let edge = &self.edges[index];
+            description.push_str(&format!(" -[{}]-> {}", edge.name, self.describe_node(edge.to)));
+        }
+        description
+    }
+
+    fn describe_node(&self, node: usize) -> String {
+        let node_info = &self.nodes[node];
+        match node_info.name {
+            Some(ref name) => format!("{} {}#{}", node_info.class_name, name, node),
+            None => format!("{}#{}", node_info.class_name, node)
+        }
+    }
+
+    fn compute_dominators(&self) -> (Vec<usize>, Vec<usize>) {
+        let root = self.nodes.len();
+        let count = root + 1;
+        let successors = |node: usize| -> Vec<usize> {
+            if node == root {
+                self.roots.clone()
+            } else {
+                self.outgoing[node].iter().map(|&index| self.edges[index].to).collect()
+            }
+        };
+
+        let mut postorder = Vec::new();
+        let mut visited = vec![false; count];
+        let mut stack = vec![(root, successors(root), 0)];
+        visited[root] = true;
+        while let Some((node, children, next)) = stack.pop() {
+            if next < children.len() {
+                let child = children[next];
+                stack.push((node, children, next + 1));
+                if !visited[child] {
+                    visited[child] = true;
+                    stack.push((child, successors(child), 0));
+                }
+            } else {
+                postorder.push(node);
+            }
+        }
+        let mut position = vec![usize::max_value(); count];
+        for (index, &node) in postorder.iter().enumerate() {
+            position[node] = index;
+        }
+
+        let mut predecessors = vec![Vec::new(); count];
+        for &node in &postorder {
+            for child in successors(node) {
+                predecessors[child].push(node);
+            }
+        }
+
+        let undefined = usize::max_value();
+        let mut idom = vec![undefined; count];
+        idom[root] = root;
+        let order: Vec<usize> = postorder.iter().rev().cloned().collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &node in order.iter().skip(1) {
+                let mut new_idom = undefined;
+                for &predecessor in &predecessors[node] {
+                    if idom[predecessor] == undefined {
+                        continue;
+                    }
+                    new_idom = if new_idom == undefined {
+                        predecessor
+                    } else {
+                        intersect(&idom, &position, predecessor, new_idom)
+                    };
+                }
+                if idom[node] != new_idom {
+                    idom[node] = new_idom;
+                    changed = true;
+                }
+            }
+        }
+        (order, idom)
+    }
+}
+
+fn intersect(idom: &[usize], position: &[usize], mut a: usize, mut b: usize) -> usize {
+    while a != b {
+        while position[a] < position[b] {
+            a = idom[a];
+        }
+        while position[b] < position[a] {
+            b = idom[b];
+        }
+    }
+    a
+}
+
+enum Target<'rt> {
+    Object(Object<'rt>),
+    Environment(Environment<'rt>)
+}
+
+impl<'rt> Target<'rt> {
+    fn get(&self) -> *mut JSObject {
+        match *self {
+            Target::Object(ref object) => object.get(),
+            Target::Environment(ref environment) => environment.get()
+        }
+    }
+
+    fn describe(&self, cx: DebugContext<'rt>) -> Result<'rt, Node> {
+        match *self {
+            Target::Object(ref object) => {
+                let name = if try!(object.get_is_callable(cx)) {
+                    try!(object.get_name(cx))
+                } else {
+                    None
+                };
+                Ok(Node {
+                    kind: "object".to_string(),
+                    class_name: try!(object.get_class(cx)),
+                    name: name,
+                    estimated_size: 0
+                })
+            }
+            Target::Environment(ref environment) => {
+                let class_name = if try!(environment.get_is_inspectable(cx)) {
+                    try!(environment.get_type(cx)).as_str().to_string()
+                } else {
+                    "uninspectable".to_string()
+                };
+                Ok(Node {
+                    kind: "environment".to_string(),
+                    class_name: class_name,
+                    name: None,
+                    estimated_size: 0
+                })
+            }
+        }
+    }
+
+    fn get_references(
+        &self,
+        cx: DebugContext<'rt>
+    ) -> Result<'rt, (u64, Vec<(String, Target<'rt>)>)> {
+        let mut size = 32;
+        let mut references = Vec::new();
+        match *self {
+            Target::Object(ref object) => {
+                if try!(object.get_is_proxy(cx)) {
+                    if let Some(target) = try!(object.get_proxy_target(cx)) {
+                        references.push(("(proxy target)".to_string(), Target::Object(target)));
+                    }
+                    if let Some(handler) = try!(object.get_proxy_handler(cx)) {
+                        references.push(("(proxy handler)".to_string(), Target::Object(handler)));
+                    }
+                    return Ok((size, references));
+                }
+                if let Some(proto) = try!(object.get_proto(cx)) {
+                    references.push(("__proto__".to_string(), Target::Object(proto)));
+                }
+                for name in try!(object.get_own_property_names(cx)) {
+                    let descriptor = try!(object.get_own_property_descriptor(cx, &name));
+                    size += 16;
+                    let values = vec![
+                        (name.clone(), descriptor.value),
+                        (format!("get {}", name), descriptor.get),
+                        (format!("set {}", name), descriptor.set)
+                    ];
+                    for (name, value) in values {
+                        match value {
+                            Some(Value::Object(value)) => {
+                                references.push((name, Target::Object(value)));
+                            }
+                            Some(Value::String(value)) => size += 2 * value.len() as u64,
+                            _ => ()
+                        }
+                    }
+                }
+                if try!(object.get_is_callable(cx)) {
+                    if let Some(environment) = try!(object.get_environment(cx)) {
+                        references.push((
+                            "(environment)".to_string(),
+                            Target::Environment(environment)
+                        ));
+                    }
+                    if let Some(target) = try!(object.get_bound_target_function(cx)) {
+                        references.push(("(bound target)".to_string(), Target::Object(target)));
+                    }
+                    if let Some(Value::Object(this)) = try!(object.get_bound_this(cx)) {
+                        references.push(("(bound this)".to_string(), Target::Object(this)));
+                    }
+                    if let Some(arguments) = try!(object.get_bound_arguments(cx)) {
+                        for (index, argument) in arguments.into_iter().enumerate() {
+                            if let Value::Object(argument) = argument {
+                                let name = format!("(bound argument {})", index);
+                                references.push((name, Target::Object(argument)));
+                            }
+                        }
+                    }
+                }
+            }
+            Target::Environment(ref environment) => {
+                if !try!(environment.get_is_inspectable(cx)) {
+                    return Ok((size, references));
+                }
+                if let Some(parent) = try!(environment.get_parent(cx)) {
+                    references.push(("(parent)".to_string(), Target::Environment(parent)));
+                }
+                match try!(environment.get_type(cx)) {
+                    EnvironmentType::Declarative => {
+                        for name in try!(environment.names(cx)) {
+                            size += 16;
+                            match try!(environment.get_binding(cx, &name)) {
+                                BindingValue::Value(Value::Object(value)) => {
+                                    references.push((name, Target::Object(value)));
+                                }
+                                BindingValue::Value(Value::String(value)) => {
+                                    size += 2 * value.len() as u64;
+                                }
+                                _ => ()
+                            }
+                        }
+                    }
+                    EnvironmentType::Object | EnvironmentType::With => {
+                        let object = try!(environment.get_object(cx));
+                        references.push(("(object)".to_string(), Target::Object(object)));
+                    }
+                    EnvironmentType::Other(_) => ()
+                }
+            }
+        }
+        Ok((size, references))
+    }
+}
+
+struct Capture<'rt> {
+    ids: IdMap<'rt>,
+    nodes: Vec<Node>,
+    queue: VecDeque<(usize, Target<'rt>)>
+}
+
+impl<'rt> Capture<'rt> {
+    fn intern(&mut self, cx: DebugContext<'rt>, target: Target<'rt>) -> Result<'rt, usize> {
+        if let Some(id) = try!(self.ids.lookup(cx, target.get())) {
+            return Ok(id);
+        }
+        let id = self.nodes.len();
+        self.nodes.push(try!(target.describe(cx)));
+        try!(self.ids.insert(cx, target.get(), id));
+        self.queue.push_back((id, target));
+        Ok(id)
+    }
+}
+
+struct IdMap<'rt>(TracedBox<'rt, *mut JSObject>);
+
+impl<'rt> IdMap<'rt> {
+    fn new(cx: DebugContext<'rt>, debugger: &Debugger<'rt>) -> Result<'rt, IdMap<'rt>> {
+        unsafe {
+            let _ac = enter_compartment!(cx, debugger);
+            let cx = cx.as_raw();
+            rooted!(in (cx) let global = jsapi::CurrentGlobalOrNull(cx));
+            rooted!(in (cx) let mut constructor = jsval::UndefinedValue());
+            try_jsapi!(cx, jsapi::JS_GetProperty(
+                cx,
+                global.handle(),
+                c_str!("Map"),
+                constructor.handle_mut()
+            ));
+            rooted!(in (cx) let mut map = ptr::null_mut());
+            try_jsapi!(cx, jsapi::Construct1(
+                cx,
+                constructor.handle(),
+                &HandleValueArray::new(),
+                map.handle_mut()
+            ));
+            Ok(IdMap(TracedBox::new(DebugContext::from_raw(cx), map.get())))
+        }
+    }
+
+    fn lookup(&self, cx: DebugContext<'rt>, key: *mut JSObject) -> Result<'rt, Option<usize>> {
+        method!(cx, self, "get", key).map(|id| {
+            UndefinedOr::<u32>::into_option(id).map(|id| id as usize)
+        })
+    }
+
+    fn insert(&self, cx: DebugContext<'rt>, key: *mut JSObject, id: usize) -> Result<'rt, ()> {
+        method!(cx, self, "set", key, id as u32).map(|_: *mut JSObject| ())
+    }
+}
+
+derive_rooted!(*mut JSObject, IdMap<'rt>);
+
+#[cfg(test)]
+mod tests {
+    use super::{Edge, Node, ReachabilityGraph};
+
+    fn node(size: u64) -> Node {
+        Node {
+            kind: "object".to_string(),
+            class_name: "Object".to_string(),
+            name: None,
+            estimated_size: size
+        }
+    }
+
+    fn edge(from: usize, to: usize, name: &str) -> Edge {
+        Edge {
+            from: from,
+            to: to,
+            name: name.to_string()
+        }
+    }
+
+    fn graph() -> ReachabilityGraph {
+        ReachabilityGraph::new(
+            (0..6).map(|index| node(10 * (index + 1))).collect(),
+            vec![
+                edge(0, 1, "a"),
+                edge(0, 2, "b"),
+                edge(1, 3, "c"),
+                edge(2, 3, "d"),
+                edge(3, 4, "e"),
+                edge(3, 1, "back"),
+                edge(5, 4, "f")
+            ],
+            vec![0, 5]
+        )
+    }
+
+    #[test]
+    fn dominators() {
+        let graph = graph();
+        assert_eq!(graph.dominators(), vec![None, Some(0), Some(0), Some(0), None, None]);
+        assert_eq!(graph.retained_sizes(), vec![100, 20, 30, 40, 50, 60]);
+    }
+
+    #[test]
+    fn shortest_path() {
+        let graph = graph();
+        assert_eq!(graph.shortest_path(0), Some(vec![]));
+        assert_eq!(graph.shortest_path(3), Some(vec![0, 2]));
+        assert_eq!(graph.shortest_path(4), Some(vec![6]));
+        assert_eq!(graph.describe_path(&[0, 2]), "Object#0 -[a]-> Object#1 -[c]-> Object#3");
+        let unreachable = ReachabilityGraph::new(vec![node(1), node(1)], Vec::new(), vec![0]);
+        assert_eq!(unreachable.shortest_path(1), None);
+    }
+
+    #[test]
+    fn round_trip() {
+        let graph = graph();
+        let parsed = ReachabilityGraph::parse(&graph.to_json().to_string()).ok().unwrap();
+        assert_eq!(parsed.get_nodes(), graph.get_nodes());
+        assert_eq!(parsed.get_edges(), graph.get_edges());
+        assert_eq!(parsed.get_roots(), graph.get_roots());
+        assert!(ReachabilityGraph::parse(r#"{ "nodes": [], "edges": [], "roots": [0] }"#).is_err());
+    }
+}
@@ -0,0 +1,323 @@
+use context::DebugContext;
+use convert::{FromJSValue, ToJSValue, UndefinedOr};
+use environment::Environment;
+use exception::Result;
+use frame::Frame;
+use js::jsapi;
+use js::jsapi::JSObject;
+use js::jsval;
+use object::Object;
+use rooted::Rooted;
+#[cfg(feature = "serialize")]
+use serialize::ObjectIds;
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use trace::TracedBox;
+
+pub enum Handle<'rt> {
+    Object(Object<'rt>),
+    Environment(Environment<'rt>),
+    Frame(Frame<'rt>)
+}
+
+impl<'rt> Handle<'rt> {
+    pub fn to_owned_in(&self, cx: DebugContext<'rt>) -> Handle<'rt> {
+        match *self {
+            Handle::Object(ref object) => Handle::Object(Object::new(cx, object.get())),
+            Handle::Environment(ref environment) => {
+                Handle::Environment(Environment::new(cx, environment.get()))
+            }
+            Handle::Frame(ref frame) => Handle::Frame(Frame::new(cx, frame.get()))
+        }
+    }
+
+    fn is_same(&self, other: &Handle<'rt>) -> bool {
+        match (self, other) {
+            (&Handle::Object(_), &Handle::Object(_)) |
+            (&Handle::Environment(_), &Handle::Environment(_)) |
+            (&Handle::Frame(_), &Handle::Frame(_)) => self.get() == other.get(),
+            _ => false
+        }
+    }
+
+    fn get(&self) -> *mut JSObject {
+        match *self {
+            Handle::Object(ref object) => object.get(),
+            Handle::Environment(ref environment) => environment.get(),
+            Handle::Frame(ref frame) => frame.get()
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lifetime {
+    Pause,
+    Thread
+}
+
+struct Entry<'rt> {
+    lifetime: Lifetime,
+    depth: usize,
+    handle: Handle<'rt>
+}
+
+pub struct HandleRegistry<'rt> {
+    entries: RefCell<BTreeMap<u64, Entry<'rt>>>,
+    referents: RefCell<Option<TracedBox<'rt, *mut JSObject>>>,
+    depth: Cell<usize>,
+    next_id: Cell<u64>
+}
+
+impl<'rt> HandleRegistry<'rt> {
+    pub fn new() -> HandleRegistry<'rt> {
+        HandleRegistry {
+            entries: RefCell::new(BTreeMap::new()),
+            referents: RefCell::new(None),
+            depth: Cell::new(0),
+            next_id: Cell::new(1)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.borrow().is_empty()
+    }
+
+    pub fn register(
+        &self,
+        cx: DebugContext<'rt>,
+        handle: Handle<'rt>,
+        lifetime: Lifetime
+    ) -> Result<'rt, u64> {
+        if let Some(id) = try!(self.find(cx, &handle)) {
+            if let Some(entry) = self.entries.borrow_mut().get_mut(&id) {
+                if lifetime == Lifetime::Thread {
+                    entry.lifetime = Lifetime::Thread;
+                }
+                return Ok(id);
+            }
+        }
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        try!(self.index(cx, &handle, id));
+        self.entries.borrow_mut().insert(id, Entry {
+            lifetime: lifetime,
+            depth: self.depth.get(),
+            handle: handle
+        });
+        Ok(id)
+    }
+
+    pub fn register_object(
+        &self,
+        cx: DebugContext<'rt>,
+        object: Object<'rt>,
+        lifetime: Lifetime
+    ) -> Result<'rt, u64> {
+        self.register(cx, Handle::Object(object), lifetime)
+    }
+
+    pub fn register_environment(
+        &self,
+        cx: DebugContext<'rt>,
+        environment: Environment<'rt>,
+        lifetime: Lifetime
+    ) -> Result<'rt, u64> {
+        self.register(cx, Handle::Environment(environment), lifetime)
+    }
+
+    pub fn register_frame(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: Frame<'rt>,
+        lifetime: Lifetime
+    ) -> Result<'rt, u64> {
+        self.register(cx, Handle::Frame(frame), lifetime)
+    }
+
+    pub fn get(&self, cx: DebugContext<'rt>, id: u64) -> Option<Handle<'rt>> {
+        self.entries.borrow().get(&id).map(|entry| entry.handle.to_owned_in(cx))
+    }
+
+    pub fn get_object(&self, cx: DebugContext<'rt>, id: u64) -> Option<Object<'rt>> {
+        match self.get(cx, id) {
+            Some(Handle::Object(object)) => Some(object),
+            _ => None
+        }
+    }
+
+    pub fn get_environment(&self, cx: DebugContext<'rt>, id: u64) -> Option<Environment<'rt>> {
+        match self.get(cx, id) {
+            Some(Handle::Environment(environment)) => Some(environment),
+            _ => None
+        }
+    }
+
+    pub fn get_frame(&self, cx: DebugContext<'rt>, id: u64) -> Option<Frame<'rt>> {
+        match self.get(cx, id) {
+            Some(Handle::Frame(frame)) => Some(frame),
+            _ => None
+        }
+    }
+
+    pub fn get_lifetime(&self, id: u64) -> Option<Lifetime> {
+        self.entries.borrow().get(&id).map(|entry| entry.lifetime)
+    }
+
+    pub fn promote(&self, id: u64) -> bool {
+        match self.entries.borrow_mut().get_mut(&id) {
+            Some(entry) => {
+                entry.lifetime = Lifetime::Thread;
+                true
+            }
+            None => false
+        }
+    }
+
+    pub fn release(&self, id: u64) -> bool {
+        self.entries.borrow_mut().remove(&id).is_some()
+    }
+
+    pub fn enter_pause(&self) {
+        self.depth.set(self.depth.get() + 1);
+    }
+
+    pub fn leave_pause(&self) {
+        let depth = self.depth.get().saturating_sub(1);
+        self.depth.set(depth);
+        self.entries.borrow_mut().retain(|_, entry| {
+            entry.lifetime != Lifetime::Pause || (depth != 0 && entry.depth <= depth)
+        });
+    }
+
+    pub fn release_pool(&self, lifetime: Lifetime) {
+        self.entries.borrow_mut().retain(|_, entry| entry.lifetime != lifetime);
+    }
+
+    pub fn clear(&self) {
+        self.entries.borrow_mut().clear();
+    }
+
+    fn find(&self, cx: DebugContext<'rt>, handle: &Handle<'rt>) -> Result<'rt, Option<u64>> {
+        let referents = self.referents.borrow();
+        let referents = match *referents {
+            Some(ref referents) => referents,
+            None => return Ok(None)
+        };
+        let id = unsafe {
+            let _ac = enter_compartment!(cx, referents);
+            let cx = cx.as_raw();
+            rooted!(in (cx) let key = handle.get());
+            rooted!(in (cx) let mut value = jsval::UndefinedValue());
+            try_jsapi!(cx, jsapi::GetWeakMapEntry(
+                cx,
+                referents.handle(),
+                key.handle(),
+                value.handle_mut()
+            ));
+            let id: UndefinedOr<f64> = try!(FromJSValue::from_js_value(cx, value.handle()));
+            id.into_option()
+        };
+        Ok(id.map(|id| id as u64).and_then(|id| {
+            match self.entries.borrow().get(&id) {
+                Some(entry) if entry.handle.is_same(handle) => Some(id),
+                _ => None
+            }
+        }))
+    }
+
+    fn index(&self, cx: DebugContext<'rt>, handle: &Handle<'rt>, id: u64) -> Result<'rt, ()> {
+        if self.referents.borrow().is_none() {
+            let referents = unsafe {
+                let _ac = jsapi::JSAutoCompartment::new(cx.as_raw(), handle.get());
+                try_jsapi!(cx.as_raw(), jsapi::NewWeakMapObject(cx.as_raw()))
+            };
+            *self.referents.borrow_mut() = Some(TracedBox::new(cx, referents));
+        }
+        let referents = self.referents.borrow();
+        let referents = referents.as_ref().unwrap();
+        unsafe {
+            let _ac = enter_compartment!(cx, referents);
+            let cx = cx.as_raw();
+            rooted!(in (cx) let key = handle.get());
+            rooted!(in (cx) let mut value = jsval::UndefinedValue());
+            try_jsapi!(cx, (id as f64).to_js_value(cx, value.handle_mut()));
+            try_jsapi!(cx, jsapi::SetWeakMapEntry(
+                cx,
+                referents.handle(),
+                key.handle(),
+                value.handle()
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl<'rt> Default for HandleRegistry<'rt> {
+    fn default() -> HandleRegistry<'rt> {
+        HandleRegistry::new()
+    }
+}
+
+#[cfg(feature = "serialize")]
+impl<'rt> ObjectIds<'rt> for HandleRegistry<'rt> {
+    fn get_object_id(&self, cx: DebugContext<'rt>, object: &Object<'rt>) -> Result<'rt, u64> {
+        self.register_object(cx, Object::new(cx, object.get()), Lifetime::Pause)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HandleRegistry, Lifetime};
+    use object::Object;
+    use utils;
+    use value::{CompletionValue, Value};
+
+    #[test]
+    fn lifetimes() {
+        utils::with_debuggee(|cx, debugger, _| {
+            let global = debugger.get_debuggees(cx).unwrap().remove(0);
+            let new_object = || -> Object {
+                match global.execute_in_global(cx, "({})").unwrap() {
+                    CompletionValue::Return(Value::Object(object)) => object,
+                    _ => panic!()
+                }
+            };
+            let registry = HandleRegistry::new();
+            let first = new_object();
+            let second = new_object();
+
+            registry.enter_pause();
+            let paused = registry.register_object(cx, first, Lifetime::Pause).unwrap();
+            let same = registry.get_object(cx, paused).unwrap();
+            assert_eq!(registry.register_object(cx, same, Lifetime::Pause).unwrap(), paused);
+            let thread = registry.register_object(cx, second, Lifetime::Thread).unwrap();
+            assert_ne!(paused, thread);
+
+            registry.enter_pause();
+            let nested = registry.register_object(cx, new_object(), Lifetime::Pause).unwrap();
+            assert_eq!(registry.len(), 3);
+            registry.leave_pause();
+            assert!(registry.get(cx, nested).is_none());
+            assert!(registry.get(cx, paused).is_some());
+
+            let promoted = registry.register_object(cx, new_object(), Lifetime::Pause).unwrap();
+            assert!(registry.promote(promoted));
+            assert_eq!(registry.get_lifetime(promoted), Some(Lifetime::Thread));
+            registry.leave_pause();
+            assert!(registry.get(cx, paused).is_none());
+            assert!(registry.get(cx, promoted).is_some());
+            assert!(registry.get(cx, thread).is_some());
+
+            assert!(registry.release(thread));
+            assert!(!registry.release(thread));
+            assert!(registry.get(cx, thread).is_none());
+            registry.release_pool(Lifetime::Thread);
+            assert!(registry.is_empty());
+            assert!(!registry.promote(promoted));
+        });
+    }
+}
@@ -1,7 +1,8 @@
-use js::jsapi::{Handle, MutableHandle};
+use js::jsapi::{Handle, JSRuntime, MutableHandle};
 
 pub trait Rooted<T> {
     fn get(&self) -> T;
     fn handle(&self) -> Handle<T>;
     fn handle_mut(&mut self) -> MutableHandle<T>;
+    fn runtime(&self) -> *mut JSRuntime;
 }
@@ -0,0 +1,51 @@
+use context::DebugContext;
+use convert::NullOr;
+use exception::Result;
+use ext::HandleValueArrayExt;
+use js::jsapi::JSObject;
+use rooted::Rooted;
+use trace::TracedBox;
+
+pub struct SavedFrame<'rt>(TracedBox<'rt, *mut JSObject>);
+
+impl<'rt> SavedFrame<'rt> {
+    pub fn new(cx: DebugContext<'rt>, frame: *mut JSObject) -> SavedFrame<'rt> {
+        SavedFrame(TracedBox::new(cx, frame))
+    }
+
+    pub fn get_source(&self, cx: DebugContext<'rt>) -> Result<'rt, String> {
+        getter!(cx, self, "source")
+    }
+
+    pub fn get_line(&self, cx: DebugContext<'rt>) -> Result<'rt, usize> {
+        getter!(cx, self, "line").map(|line: u32| line as usize)
+    }
+
+    pub fn get_column(&self, cx: DebugContext<'rt>) -> Result<'rt, usize> {
+        getter!(cx, self, "column").map(|column: u32| column as usize)
+    }
+
+    pub fn get_function_display_name(&self, cx: DebugContext<'rt>) -> Result<'rt, Option<String>> {
+        getter!(cx, self, "functionDisplayName").map(|name| NullOr::<String>::into_option(name))
+    }
+
+    pub fn get_parent(&self, cx: DebugContext<'rt>) -> Result<'rt, Option<SavedFrame<'rt>>> {
+        getter!(cx, self, "parent").map(|frame| NullOr::<SavedFrame<'rt>>::into_option(frame))
+    }
+
+    pub fn get_async_cause(&self, cx: DebugContext<'rt>) -> Result<'rt, Option<String>> {
+        getter!(cx, self, "asyncCause").map(|cause| NullOr::<String>::into_option(cause))
+    }
+
+    pub fn get_async_parent(&self, cx: DebugContext<'rt>) -> Result<'rt, Option<SavedFrame<'rt>>> {
+        getter!(cx, self, "asyncParent").map(|frame| NullOr::<SavedFrame<'rt>>::into_option(frame))
+    }
+
+    pub fn get_stack_string(&self, cx: DebugContext<'rt>) -> Result<'rt, String> {
+        method!(cx, self, "toString")
+    }
+}
+
+derive_rooted!(*mut JSObject, SavedFrame<'rt>);
+
+derive_convert!(SavedFrame<'rt>);
@@ -1,112 +1,293 @@
+use breakpoint::{BreakpointOptions, ConditionalOnHit};
 use call::Call;
-use convert::{FromJSValue, ToJSValue};
+use context::DebugContext;
+use convert::{FromJSValue, NullOr, ToJSValue, UndefinedOr};
 use exception::Result;
 use ext::HandleValueArrayExt;
 use frame::Frame;
-use js::jsapi::{CallArgs, HandleValueArray, JSAutoCompartment, JSContext, JSObject, Value};
+use js::jsapi::{
+    CallArgs,
+    HandleValue,
+    HandleValueArray,
+    JSContext,
+    JSObject,
+    Value
+};
 use js::jsval;
 use object::Object;
 use rooted::Rooted;
-use source::Source;
+use source::{Source, SourceText};
+use std::any::TypeId;
 use std::rc::Rc;
-use trace::TracedBox;
+use trace::{TracedBox, WeakBox};
 use utils;
 use value::ResumptionValue;
 
-pub trait OnHit {
-    fn on_hit(&self, cx: *mut JSContext, frame: &Frame) -> Result<ResumptionValue>;
+pub trait OnHit<'rt> {
+    fn on_hit(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>>;
 }
 
-impl Call for OnHit {
+impl<'rt> Call for OnHit<'rt> + 'rt {
+    fn hook_type() -> TypeId {
+        TypeId::of::<OnHit<'static> + 'static>()
+    }
+
     unsafe fn call(&self, cx: *mut JSContext, argc: u32, vp: *mut Value) -> bool {
         let args = CallArgs::from_vp(vp, argc);
-        match self.on_hit(cx, &Frame::from_js_value(cx, args.get(0)).unwrap()) {
+        let frame = Frame::from_js_value(cx, args.get(0)).unwrap();
+        match self.on_hit(DebugContext::from_raw(cx), &frame) {
             Ok(result) => result.to_js_value(cx, args.rval()),
             Err(exception) => exception.into_pending_exception(cx)
         }
     }
 }
 
-pub struct Breakpoint(TracedBox<*mut JSObject>);
+pub struct OffsetLocation {
+    pub line_number: usize,
+    pub column_number: usize,
+    pub is_entry_point: bool
+}
+
+impl FromJSValue for OffsetLocation {
+    unsafe fn from_js_value<'rt>(cx: *mut JSContext, v: HandleValue) -> Result<'rt, Self> {
+        rooted!(in (cx) let obj = v.to_object());
+        let line_number: u32 = try!(utils::get_property(cx, obj.handle(), "lineNumber"));
+        let column_number: u32 = try!(utils::get_property(cx, obj.handle(), "columnNumber"));
+        Ok(OffsetLocation {
+            line_number: line_number as usize,
+            column_number: column_number as usize,
+            is_entry_point: try!(utils::get_property(cx, obj.handle(), "isEntryPoint"))
+        })
+    }
+}
+
+pub struct ColumnOffset {
+    pub line_number: usize,
+    pub column_number: usize,
+    pub offset: usize
+}
+
+impl FromJSValue for ColumnOffset {
+    unsafe fn from_js_value<'rt>(cx: *mut JSContext, v: HandleValue) -> Result<'rt, Self> {
+        rooted!(in (cx) let obj = v.to_object());
+        let line_number: u32 = try!(utils::get_property(cx, obj.handle(), "lineNumber"));
+        let column_number: u32 = try!(utils::get_property(cx, obj.handle(), "columnNumber"));
+        let offset: u32 = try!(utils::get_property(cx, obj.handle(), "offset"));
+        Ok(ColumnOffset {
+            line_number: line_number as usize,
+            column_number: column_number as usize,
+            offset: offset as usize
+        })
+    }
+}
+
+pub struct OffsetCoverage {
+    pub line_number: usize,
+    pub column_number: usize,
+    pub offset: usize,
+    pub count: u64
+}
+
+impl FromJSValue for OffsetCoverage {
+    unsafe fn from_js_value<'rt>(cx: *mut JSContext, v: HandleValue) -> Result<'rt, Self> {
+        rooted!(in (cx) let obj = v.to_object());
+        let line_number: u32 = try!(utils::get_property(cx, obj.handle(), "lineNumber"));
+        let column_number: u32 = try!(utils::get_property(cx, obj.handle(), "columnNumber"));
+        let offset: u32 = try!(utils::get_property(cx, obj.handle(), "offset"));
+        let count: f64 = try!(utils::get_property(cx, obj.handle(), "count"));
+        Ok(OffsetCoverage {
+            line_number: line_number as usize,
+            column_number: column_number as usize,
+            offset: offset as usize,
+            count: count as u64
+        })
+    }
+}
+
+pub struct Breakpoint<'rt>(TracedBox<'rt, *mut JSObject>);
 
-impl Breakpoint{
-    pub fn new(cx: *mut JSContext, breakpoint: *mut JSObject) -> Breakpoint {
+impl<'rt> Breakpoint<'rt>{
+    pub fn new(cx: DebugContext<'rt>, breakpoint: *mut JSObject) -> Breakpoint<'rt> {
         Breakpoint(TracedBox::new(cx, breakpoint))
     }
 }
 
-derive_rooted!(*mut JSObject, Breakpoint);
+derive_rooted!(*mut JSObject, Breakpoint<'rt>);
 
-derive_convert!(Breakpoint);
+derive_convert!(Breakpoint<'rt>);
 
-pub struct Script(TracedBox<*mut JSObject>);
+pub struct Script<'rt>(TracedBox<'rt, *mut JSObject>);
 
-impl Script {
-    pub fn new(cx: *mut JSContext, script: *mut JSObject) -> Script {
+impl<'rt> Script<'rt> {
+    pub fn new(cx: DebugContext<'rt>, script: *mut JSObject) -> Script<'rt> {
         Script(TracedBox::new(cx, script))
     }
 
-    pub fn get_source(&self, cx: *mut JSContext) -> Result<Source> {
+    pub fn downgrade(&self, cx: DebugContext<'rt>) -> WeakScript<'rt> {
+        WeakScript(WeakBox::new(cx, self.get()))
+    }
+
+    pub fn get_source(&self, cx: DebugContext<'rt>) -> Result<'rt, Source<'rt>> {
         getter!(cx, self, "source")
     }
 
-    pub fn get_url(&self, cx: *mut JSContext) -> Result<String> {
+    pub fn get_source_text(&self, cx: DebugContext<'rt>) -> Result<'rt, Rc<SourceText>> {
+        try!(self.get_source(cx)).get_source_text(cx)
+    }
+
+    pub fn get_url(&self, cx: DebugContext<'rt>) -> Result<'rt, String> {
         getter!(cx, self, "url")
     }
 
-    pub fn get_start_line(&self, cx: *mut JSContext) -> Result<usize> {
+    pub fn get_display_name(&self, cx: DebugContext<'rt>) -> Result<'rt, Option<String>> {
+        getter!(cx, self, "displayName").map(|name| UndefinedOr::<String>::into_option(name))
+    }
+
+    pub fn get_is_function(&self, cx: DebugContext<'rt>) -> Result<'rt, bool> {
+        getter!(cx, self, "parameterNames").map(|names| {
+            UndefinedOr::<Vec<String>>::into_option(names).is_some()
+        })
+    }
+
+    pub fn get_start_line(&self, cx: DebugContext<'rt>) -> Result<'rt, usize> {
         getter!(cx, self, "startLine").map(|start_line: u32| start_line as usize)
     }
 
-    pub fn get_line_count(&self, cx: *mut JSContext) -> Result<usize> {
+    pub fn get_line_count(&self, cx: DebugContext<'rt>) -> Result<'rt, usize> {
         getter!(cx, self, "lineCount").map(|line_count: u32| line_count as usize)
     }
 
-    pub fn get_global(&self, cx: *mut JSContext) -> Result<Object> {
+    pub fn get_global(&self, cx: DebugContext<'rt>) -> Result<'rt, Object<'rt>> {
         getter!(cx, self, "global")
     }
 
-    pub fn get_offsets_for_line(&self, cx: *mut JSContext, line: usize) -> Result<Vec<usize>> {
+    pub fn get_offsets_for_line(
+        &self,
+        cx: DebugContext<'rt>,
+        line: usize
+    ) -> Result<'rt, Vec<usize>> {
         method!(cx, self, "getLineOffsets", line as u32).map(|offsets: Vec<u32>| {
             offsets.iter().map(|offset| *offset as usize).collect()
         })
     }
 
+    pub fn get_all_column_offsets(&self, cx: DebugContext<'rt>) -> Result<'rt, Vec<ColumnOffset>> {
+        method!(cx, self, "getAllColumnOffsets")
+    }
+
+    pub fn get_offsets_coverage(
+        &self,
+        cx: DebugContext<'rt>
+    ) -> Result<'rt, Option<Vec<OffsetCoverage>>> {
+        method!(cx, self, "getOffsetsCoverage").map(|coverage| {
+            NullOr::<Vec<OffsetCoverage>>::into_option(coverage)
+        })
+    }
+
+    pub fn get_offset_location(
+        &self,
+        cx: DebugContext<'rt>,
+        offset: usize
+    ) -> Result<'rt, OffsetLocation> {
+        method!(cx, self, "getOffsetLocation", offset as u32)
+    }
+
     pub fn add_breakpoint(
-        self,
-        cx: *mut JSContext,
-        offset: usize, on_hit: Rc<OnHit>
-    ) -> Result<Breakpoint> {
+        &self,
+        cx: DebugContext<'rt>,
+        offset: usize, on_hit: Rc<OnHit<'rt> + 'rt>
+    ) -> Result<'rt, Breakpoint<'rt>> {
         unsafe {
-            let _ac = JSAutoCompartment::new(cx, self.get());
+            let _ac = enter_compartment!(cx, self);
+            let cx = cx.as_raw();
             rooted!(in (cx) let mut offset_arg = jsval::UndefinedValue());
             try_jsapi!(cx, (offset as u32).to_js_value(cx, offset_arg.handle_mut()));
             rooted!(in (cx) let mut breakpoint_arg = jsval::UndefinedValue());
             try_jsapi!(cx, on_hit.to_js_value(cx, breakpoint_arg.handle_mut()));
-            try!(utils::call_method(
+            try!(utils::call_method::<()>(
                 cx,
                 self.handle(),
                 "setBreakpoint",
                 &HandleValueArray::from_slice(&[offset_arg.get(), breakpoint_arg.get()])
             ));
-            Ok(Breakpoint::new(cx, breakpoint_arg.to_object()))
+            Ok(Breakpoint::new(DebugContext::from_raw(cx), breakpoint_arg.to_object()))
         }
     }
 
+    pub fn add_breakpoint_with_options(
+        &self,
+        cx: DebugContext<'rt>,
+        offset: usize,
+        options: BreakpointOptions<'rt>,
+        on_hit: Rc<OnHit<'rt> + 'rt>
+    ) -> Result<'rt, Breakpoint<'rt>> {
+        self.add_breakpoint(cx, offset, Rc::new(ConditionalOnHit::new(options, on_hit)))
+    }
+
     pub fn remove_breakpoint(
-        self,
-        cx: *mut JSContext,
+        &self,
+        cx: DebugContext<'rt>,
         offset: usize,
-        breakpoint: Breakpoint
-    ) -> Result<()> {
-        method!(cx, self, "clearBreakpoints", breakpoint, offset as u32)
+        breakpoint: Breakpoint<'rt>
+    ) -> Result<'rt, ()> {
+        method!(cx, self, "clearBreakpoint", breakpoint, offset as u32)
     }
 
-    pub fn clear_breakpoints(self, cx: *mut JSContext, offset: usize) -> Result<()> {
+    pub fn clear_breakpoints(&self, cx: DebugContext<'rt>, offset: usize) -> Result<'rt, ()> {
         method!(cx, self, "clearAllBreakpoints", offset as u32)
     }
 }
 
-derive_rooted!(*mut JSObject, Script);
+derive_rooted!(*mut JSObject, Script<'rt>);
 
-derive_convert!(Script);
+derive_convert!(Script<'rt>);
+
+pub struct WeakScript<'rt>(WeakBox<'rt>);
+
+impl<'rt> WeakScript<'rt> {
+    pub fn is_alive(&self) -> bool {
+        self.0.is_alive()
+    }
+
+    pub fn upgrade(&self, cx: DebugContext<'rt>) -> Option<Script<'rt>> {
+        self.0.get().map(|script| Script::new(cx, script))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use debugger::ScriptQuery;
+    use js::jsapi;
+    use utils;
+
+    #[test]
+    fn weak_script() {
+        utils::with_debuggee(|cx, debugger, evaluate| {
+            evaluate("var f = function () {\n    return 1;\n};\n");
+            let script = debugger.find_scripts(cx, &ScriptQuery {
+                url: Some("test".to_string()),
+                line: Some(2)
+            }).unwrap().remove(0);
+            let weak = script.downgrade(cx);
+
+            unsafe {
+                jsapi::JS_GC(cx.runtime());
+            }
+            assert!(weak.is_alive());
+            assert!(weak.upgrade(cx).is_some());
+
+            drop(script);
+            assert!(weak.is_alive());
+            evaluate("f = null;");
+            unsafe {
+                jsapi::JS_GC(cx.runtime());
+            }
+            assert!(!weak.is_alive());
+            assert!(weak.upgrade(cx).is_none());
+        });
+    }
+}
@@ -0,0 +1,103 @@
+use context::DebugContext;
+use exception;
+use js::jsapi::{JSContext, JSObject, JSRuntime};
+use object::Object;
+use rooted::Rooted;
+use serde::{Serialize, Serializer};
+use serde::ser::{Error, SerializeStruct};
+use std::cell::Cell;
+use value::Value;
+
+pub trait ObjectIds<'rt> {
+    fn get_object_id(
+        &self,
+        cx: DebugContext<'rt>,
+        object: &Object<'rt>
+    ) -> exception::Result<'rt, u64>;
+}
+
+#[derive(Clone, Copy)]
+struct Scope {
+    cx: *mut JSContext,
+    runtime: *mut JSRuntime,
+    ids: *const (),
+    get_object_id: unsafe fn(*const (), *mut JSContext, *mut JSObject) -> Option<u64>
+}
+
+thread_local!(static SCOPE: Cell<Option<Scope>> = Cell::new(None));
+
+struct ScopeGuard(Option<Scope>);
+
+impl Drop for ScopeGuard {
+    fn drop(&mut self) {
+        SCOPE.with(|scope| scope.set(self.0));
+    }
+}
+
+unsafe fn get_object_id<'rt, T: ObjectIds<'rt>>(
+    ids: *const (),
+    cx: *mut JSContext,
+    object: *mut JSObject
+) -> Option<u64> {
+    let cx = DebugContext::from_raw(cx);
+    (*(ids as *const T)).get_object_id(cx, &Object::new(cx, object)).ok()
+}
+
+pub fn with_object_ids<'rt, T: ObjectIds<'rt>, F: FnOnce() -> R, R>(
+    cx: DebugContext<'rt>,
+    ids: &T,
+    f: F
+) -> R {
+    let scope = Scope {
+        cx: cx.as_raw(),
+        runtime: cx.runtime(),
+        ids: ids as *const T as *const (),
+        get_object_id: get_object_id::<'rt, T>
+    };
+    let _guard = ScopeGuard(SCOPE.with(|current| current.replace(Some(scope))));
+    f()
+}
+
+impl<'rt> Serialize for Object<'rt> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match SCOPE.with(|scope| scope.get()) {
+            Some(scope) if scope.runtime == self.runtime() => {
+                match unsafe { (scope.get_object_id)(scope.ids, scope.cx, self.get()) } {
+                    Some(id) => serializer.serialize_u64(id),
+                    None => Err(S::Error::custom("Failed to assign an id to an object"))
+                }
+            }
+            Some(_) => Err(S::Error::custom("Object serialized with another runtime's ids")),
+            None => Err(S::Error::custom("Object serialized outside of with_object_ids"))
+        }
+    }
+}
+
+impl<'rt> Serialize for Value<'rt> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            Value::Undefined => {
+                let mut state = try!(serializer.serialize_struct("Value", 1));
+                try!(state.serialize_field("type", "undefined"));
+                state.end()
+            }
+            Value::Null => serializer.serialize_unit(),
+            Value::Boolean(b) => serializer.serialize_bool(b),
+            Value::Int32(i) => serializer.serialize_i32(i),
+            Value::Double(d) if d.is_finite() => serializer.serialize_f64(d),
+            Value::Double(d) => {
+                let mut state = try!(serializer.serialize_struct("Value", 2));
+                try!(state.serialize_field("type", "number"));
+                try!(state.serialize_field("value", &d.to_string()));
+                state.end()
+            }
+            Value::String(ref s) => serializer.serialize_str(s),
+            Value::Object(ref object) => {
+                let mut state = try!(serializer.serialize_struct("Value", 2));
+                try!(state.serialize_field("type", "object"));
+                try!(state.serialize_field("id", object));
+                state.end()
+            }
+        }
+    }
+}
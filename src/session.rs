@@ -0,0 +1,297 @@
+use breakpoint::{BreakpointOptions, ConditionalOnHit, HitCount, LogMessage, OnLog};
+use context::DebugContext;
+use debugger::{Debugger, OnEnterFrame, ScriptQuery};
+use exception::Result;
+use frame::Frame;
+use pause::{EventLoop, PauseController};
+use rooted::Rooted;
+use script::{Breakpoint, OnHit, Script};
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
+use value::ResumptionValue;
+
+pub trait Connection<'rt> {
+    fn is_connected(&self) -> bool;
+
+    fn process_events(
+        &self,
+        cx: DebugContext<'rt>,
+        controller: &PauseController<'rt>
+    ) -> Result<'rt, ()>;
+}
+
+pub struct Dispatcher<T>(pub Weak<T>);
+
+impl<'rt, T: Connection<'rt>> EventLoop<'rt> for Dispatcher<T> {
+    fn process_events(
+        &self,
+        cx: DebugContext<'rt>,
+        controller: &PauseController<'rt>
+    ) -> Result<'rt, ()> {
+        match self.0.upgrade() {
+            Some(ref session) if session.is_connected() => {
+                try!(session.process_events(cx, controller));
+            }
+            _ => {
+                controller.resume(None);
+            }
+        }
+        Ok(())
+    }
+}
+
+pub trait OnPauseRequest<'rt> {
+    fn on_pause_request(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>>;
+}
+
+pub struct PauseRequest<'rt> {
+    debugger: Rc<Debugger<'rt>>,
+    this: RefCell<Weak<PauseRequest<'rt>>>,
+    target: Weak<OnPauseRequest<'rt> + 'rt>,
+    pending: Cell<bool>
+}
+
+impl<'rt> PauseRequest<'rt> {
+    pub fn new(
+        debugger: Rc<Debugger<'rt>>,
+        target: Weak<OnPauseRequest<'rt> + 'rt>
+    ) -> Rc<PauseRequest<'rt>> {
+        let request = Rc::new(PauseRequest {
+            debugger: debugger,
+            this: RefCell::new(Weak::new()),
+            target: target,
+            pending: Cell::new(false)
+        });
+        *request.this.borrow_mut() = Rc::downgrade(&request);
+        request
+    }
+
+    fn this(&self) -> Rc<PauseRequest<'rt>> {
+        self.this.borrow().upgrade().unwrap()
+    }
+
+    pub fn request(&self, cx: DebugContext<'rt>) -> Result<'rt, ()> {
+        if self.pending.get() {
+            return Ok(());
+        }
+        try!(self.debugger.add_on_enter_frame(cx, self.this() as Rc<OnEnterFrame<'rt> + 'rt>));
+        self.pending.set(true);
+        Ok(())
+    }
+
+    pub fn cancel(&self, cx: DebugContext<'rt>) -> Result<'rt, ()> {
+        if !self.pending.get() {
+            return Ok(());
+        }
+        self.pending.set(false);
+        let on_enter_frame = self.this() as Rc<OnEnterFrame<'rt> + 'rt>;
+        self.debugger.remove_on_enter_frame(cx, &on_enter_frame)
+    }
+}
+
+impl<'rt> OnEnterFrame<'rt> for PauseRequest<'rt> {
+    fn on_enter_frame(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        if !self.pending.get() {
+            return Ok(None);
+        }
+        try!(self.cancel(cx));
+        match self.target.upgrade() {
+            Some(target) => target.on_pause_request(cx, frame),
+            None => Ok(None)
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct BreakpointSpec {
+    pub url: String,
+    pub line: usize,
+    pub condition: Option<String>,
+    pub hit_count: Option<HitCount>,
+    pub log_message: Option<String>
+}
+
+pub struct BreakpointLocation<'rt> {
+    pub script: Script<'rt>,
+    pub line: usize,
+    pub column: usize
+}
+
+struct Location<'rt> {
+    script: Script<'rt>,
+    offset: usize,
+    breakpoint: Breakpoint<'rt>,
+    line: usize,
+    column: usize
+}
+
+struct Entry<'rt> {
+    id: u64,
+    spec: BreakpointSpec,
+    on_hit: Rc<OnHit<'rt> + 'rt>,
+    locations: Vec<Location<'rt>>
+}
+
+pub struct Breakpoints<'rt> {
+    debugger: Rc<Debugger<'rt>>,
+    entries: RefCell<Vec<Entry<'rt>>>,
+    next_id: Cell<u64>
+}
+
+impl<'rt> Breakpoints<'rt> {
+    pub fn new(debugger: Rc<Debugger<'rt>>) -> Breakpoints<'rt> {
+        Breakpoints {
+            debugger: debugger,
+            entries: RefCell::new(Vec::new()),
+            next_id: Cell::new(1)
+        }
+    }
+
+    pub fn add(
+        &self,
+        cx: DebugContext<'rt>,
+        spec: BreakpointSpec,
+        on_hit: &Rc<OnHit<'rt> + 'rt>,
+        on_log: Option<&Rc<OnLog<'rt> + 'rt>>
+    ) -> Result<'rt, u64> {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        let log_message = match (spec.log_message.as_ref(), on_log) {
+            (Some(template), Some(on_log)) => Some(LogMessage::new(template, on_log.clone())),
+            _ => None
+        };
+        let options = BreakpointOptions {
+            condition: spec.condition.clone(),
+            hit_count: spec.hit_count,
+            log_message: log_message
+        };
+        let mut entry = Entry {
+            id: id,
+            spec: spec,
+            on_hit: Rc::new(ConditionalOnHit::new(options, on_hit.clone())),
+            locations: Vec::new()
+        };
+        let scripts = try!(self.debugger.find_scripts(cx, &ScriptQuery {
+            url: Some(entry.spec.url.clone()),
+            line: Some(entry.spec.line)
+        }));
+        for script in scripts {
+            try!(set_breakpoint_in_script(cx, &mut entry, &script));
+        }
+        self.entries.borrow_mut().push(entry);
+        Ok(id)
+    }
+
+    pub fn remove(&self, cx: DebugContext<'rt>, id: u64) -> Result<'rt, bool> {
+        let entry = {
+            let mut entries = self.entries.borrow_mut();
+            match entries.iter().position(|entry| entry.id == id) {
+                Some(index) => entries.remove(index),
+                None => return Ok(false)
+            }
+        };
+        try!(clear(cx, entry));
+        Ok(true)
+    }
+
+    pub fn remove_url(&self, cx: DebugContext<'rt>, url: &str) -> Result<'rt, ()> {
+        let removed: Vec<Entry<'rt>> = {
+            let mut entries = self.entries.borrow_mut();
+            let (removed, kept) = entries.drain(..).partition(|entry| entry.spec.url == url);
+            *entries = kept;
+            removed
+        };
+        for entry in removed {
+            try!(clear(cx, entry));
+        }
+        Ok(())
+    }
+
+    pub fn locations(&self, cx: DebugContext<'rt>, id: u64) -> Vec<BreakpointLocation<'rt>> {
+        self.entries.borrow().iter().filter(|entry| entry.id == id).flat_map(|entry| {
+            entry.locations.iter().map(|location| BreakpointLocation {
+                script: Script::new(cx, location.script.get()),
+                line: location.line,
+                column: location.column
+            })
+        }).collect()
+    }
+
+    pub fn get_spec(&self, id: u64) -> Option<BreakpointSpec> {
+        self.entries.borrow().iter().find(|entry| entry.id == id).map(|entry| entry.spec.clone())
+    }
+
+    pub fn hits(&self, cx: DebugContext<'rt>, frame: &Frame<'rt>) -> Result<'rt, Vec<u64>> {
+        let script = match try!(frame.get_script(cx)) {
+            Some(script) => script,
+            None => return Ok(Vec::new())
+        };
+        let offset = try!(frame.get_offset(cx));
+        Ok(self.entries.borrow().iter().filter(|entry| {
+            entry.locations.iter().any(|location| {
+                location.script.get() == script.get() && location.offset == offset
+            })
+        }).map(|entry| entry.id).collect())
+    }
+
+    pub fn resolve(&self, cx: DebugContext<'rt>, script: &Script<'rt>) -> Result<'rt, ()> {
+        let url = try!(script.get_url(cx));
+        let source = try!(script.get_source(cx));
+        let mut entries = self.entries.borrow_mut();
+        for entry in entries.iter_mut().filter(|entry| entry.spec.url == url) {
+            let scripts = try!(self.debugger.find_scripts(cx, &ScriptQuery {
+                url: Some(url.clone()),
+                line: Some(entry.spec.line)
+            }));
+            for nested in scripts {
+                if try!(nested.get_source(cx)).get() == source.get() {
+                    try!(set_breakpoint_in_script(cx, entry, &nested));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn set_breakpoint_in_script<'rt>(
+    cx: DebugContext<'rt>,
+    entry: &mut Entry<'rt>,
+    script: &Script<'rt>
+) -> Result<'rt, ()> {
+    for offset in try!(script.get_offsets_for_line(cx, entry.spec.line)) {
+        if entry.locations.iter().any(|location| {
+            location.script.get() == script.get() && location.offset == offset
+        }) {
+            continue;
+        }
+        let location = try!(script.get_offset_location(cx, offset));
+        let breakpoint = try!(Script::new(cx, script.get()).add_breakpoint(
+            cx,
+            offset,
+            entry.on_hit.clone()
+        ));
+        entry.locations.push(Location {
+            script: Script::new(cx, script.get()),
+            offset: offset,
+            breakpoint: breakpoint,
+            line: location.line_number,
+            column: location.column_number
+        });
+    }
+    Ok(())
+}
+
+fn clear<'rt>(cx: DebugContext<'rt>, entry: Entry<'rt>) -> Result<'rt, ()> {
+    for location in entry.locations {
+        try!(location.script.remove_breakpoint(cx, location.offset, location.breakpoint));
+    }
+    Ok(())
+}
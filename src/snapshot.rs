@@ -0,0 +1,100 @@
+use context::DebugContext;
+use exception::Result;
+use frame::{Frame, FrameImplementation, FrameType};
+use object::Object;
+use script::Script;
+use source::Source;
+use value::Value;
+
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+pub struct SourceSnapshot {
+    pub id: u32,
+    pub url: Option<String>,
+    pub display_url: Option<String>,
+    pub source_map_url: Option<String>,
+    pub introduction_type: Option<String>,
+    pub introduction_offset: Option<usize>,
+    pub text_length: usize
+}
+
+impl SourceSnapshot {
+    pub fn new<'rt>(cx: DebugContext<'rt>, source: &Source<'rt>) -> Result<'rt, SourceSnapshot> {
+        Ok(SourceSnapshot {
+            id: try!(source.get_id(cx)),
+            url: try!(source.get_url(cx)),
+            display_url: try!(source.get_display_url(cx)),
+            source_map_url: try!(source.get_source_map_url(cx)),
+            introduction_type: try!(source.get_introduction_type(cx)).map(|introduction_type| {
+                introduction_type.as_str().to_string()
+            }),
+            introduction_offset: try!(source.get_introduction_offset(cx)),
+            text_length: try!(source.get_source_text(cx)).len()
+        })
+    }
+}
+
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+pub struct ScriptSnapshot {
+    pub url: String,
+    pub start_line: usize,
+    pub line_count: usize,
+    pub source: SourceSnapshot
+}
+
+impl ScriptSnapshot {
+    pub fn new<'rt>(cx: DebugContext<'rt>, script: &Script<'rt>) -> Result<'rt, ScriptSnapshot> {
+        Ok(ScriptSnapshot {
+            url: try!(script.get_url(cx)),
+            start_line: try!(script.get_start_line(cx)),
+            line_count: try!(script.get_line_count(cx)),
+            source: try!(SourceSnapshot::new(cx, &try!(script.get_source(cx))))
+        })
+    }
+}
+
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "camelCase"))]
+pub struct FrameSnapshot<'rt> {
+    #[cfg_attr(feature = "serialize", serde(rename = "type"))]
+    pub frame_type: FrameType,
+    pub implementation: FrameImplementation,
+    pub depth: usize,
+    pub is_constructing: bool,
+    pub offset: usize,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub callee: Option<Object<'rt>>,
+    pub this: Value<'rt>,
+    pub script: Option<ScriptSnapshot>
+}
+
+impl<'rt> FrameSnapshot<'rt> {
+    pub fn new(cx: DebugContext<'rt>, frame: &Frame<'rt>) -> Result<'rt, FrameSnapshot<'rt>> {
+        let offset = try!(frame.get_offset(cx));
+        let (line, column, script) = match try!(frame.get_script(cx)) {
+            Some(script) => {
+                let location = try!(script.get_offset_location(cx, offset));
+                (
+                    Some(location.line_number),
+                    Some(location.column_number),
+                    Some(try!(ScriptSnapshot::new(cx, &script)))
+                )
+            }
+            None => (None, None, None)
+        };
+        Ok(FrameSnapshot {
+            frame_type: try!(frame.get_type(cx)),
+            implementation: try!(frame.get_implementation(cx)),
+            depth: try!(frame.get_depth(cx)),
+            is_constructing: try!(frame.get_is_constructing(cx)),
+            offset: offset,
+            line: line,
+            column: column,
+            callee: try!(frame.get_callee(cx)),
+            this: try!(frame.get_this(cx)),
+            script: script
+        })
+    }
+}
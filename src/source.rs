@@ -1,20 +1,358 @@
+use context::DebugContext;
+use convert;
+use convert::{FromJSValue, NullOr, UndefinedOr};
 use exception::Result;
-use js::jsapi::{JSContext, JSObject};
+use js::jsapi::{HandleValue, JSContext, JSObject, JSRuntime};
 use rooted::Rooted;
-use trace::TracedBox;
+use script::Script;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+use trace;
+use trace::{Teardown, TracedBox, WeakBox};
 
-pub struct Source(TracedBox<*mut JSObject>);
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IntroductionType {
+    Eval,
+    DebuggerEval,
+    Function,
+    GeneratorFunction,
+    AsyncFunction,
+    ScriptElement,
+    InlineScript,
+    EventHandler,
+    ImportedModule,
+    JavascriptUrl,
+    Worker,
+    ImportScripts,
+    Other(String)
+}
+
+impl IntroductionType {
+    pub fn as_str(&self) -> &str {
+        match *self {
+            IntroductionType::Eval => "eval",
+            IntroductionType::DebuggerEval => "debugger eval",
+            IntroductionType::Function => "Function",
+            IntroductionType::GeneratorFunction => "GeneratorFunction",
+            IntroductionType::AsyncFunction => "AsyncFunction",
+            IntroductionType::ScriptElement => "scriptElement",
+            IntroductionType::InlineScript => "inlineScript",
+            IntroductionType::EventHandler => "eventHandler",
+            IntroductionType::ImportedModule => "importedModule",
+            IntroductionType::JavascriptUrl => "javascriptURL",
+            IntroductionType::Worker => "Worker",
+            IntroductionType::ImportScripts => "importScripts",
+            IntroductionType::Other(ref string) => string
+        }
+    }
+}
+
+impl FromJSValue for IntroductionType {
+    unsafe fn from_js_value<'rt>(cx: *mut JSContext, v: HandleValue) -> Result<'rt, Self> {
+        convert::with_str(cx, v, |string| {
+            match string {
+                "eval" => IntroductionType::Eval,
+                "debugger eval" => IntroductionType::DebuggerEval,
+                "Function" => IntroductionType::Function,
+                "GeneratorFunction" => IntroductionType::GeneratorFunction,
+                "AsyncFunction" => IntroductionType::AsyncFunction,
+                "scriptElement" => IntroductionType::ScriptElement,
+                "inlineScript" => IntroductionType::InlineScript,
+                "eventHandler" => IntroductionType::EventHandler,
+                "importedModule" => IntroductionType::ImportedModule,
+                "javascriptURL" => IntroductionType::JavascriptUrl,
+                "Worker" => IntroductionType::Worker,
+                "importScripts" => IntroductionType::ImportScripts,
+                _ => IntroductionType::Other(string.to_string())
+            }
+        })
+    }
+}
+
+pub struct Source<'rt>(TracedBox<'rt, *mut JSObject>);
 
-impl Source {
-    pub fn new(cx: *mut JSContext, source: *mut JSObject) -> Source {
+impl<'rt> Source<'rt> {
+    pub fn new(cx: DebugContext<'rt>, source: *mut JSObject) -> Source<'rt> {
         Source(TracedBox::new(cx, source))
     }
 
-    pub fn get_text(&self, cx: *mut JSContext) -> Result<String> {
+    pub fn get_id(&self, cx: DebugContext<'rt>) -> Result<'rt, u32> {
+        getter!(cx, self, "id")
+    }
+
+    pub fn get_text(&self, cx: DebugContext<'rt>) -> Result<'rt, String> {
         getter!(cx, self, "text")
     }
+
+    pub fn get_source_text(&self, cx: DebugContext<'rt>) -> Result<'rt, Rc<SourceText>> {
+        let texts = SourceTexts::get(cx);
+        let id = try!(self.get_id(cx));
+        if let Some(text) = texts.find(id) {
+            return Ok(text);
+        }
+        let text = Rc::new(SourceText::new(try!(self.get_text(cx))));
+        texts.insert(cx, id, self, text.clone());
+        Ok(text)
+    }
+
+    pub fn get_url(&self, cx: DebugContext<'rt>) -> Result<'rt, Option<String>> {
+        getter!(cx, self, "url").map(|url| NullOr::<String>::into_option(url))
+    }
+
+    pub fn get_display_url(&self, cx: DebugContext<'rt>) -> Result<'rt, Option<String>> {
+        getter!(cx, self, "displayURL").map(|url| NullOr::<String>::into_option(url))
+    }
+
+    pub fn get_source_map_url(&self, cx: DebugContext<'rt>) -> Result<'rt, Option<String>> {
+        getter!(cx, self, "sourceMapURL").map(|url| NullOr::<String>::into_option(url))
+    }
+
+    pub fn set_source_map_url(&self, cx: DebugContext<'rt>, url: &str) -> Result<'rt, ()> {
+        setter!(cx, self, "sourceMapURL", url.to_string())
+    }
+
+    pub fn get_introduction_type(
+        &self,
+        cx: DebugContext<'rt>
+    ) -> Result<'rt, Option<IntroductionType>> {
+        getter!(cx, self, "introductionType").map(|introduction_type| {
+            UndefinedOr::<IntroductionType>::into_option(introduction_type)
+        })
+    }
+
+    pub fn get_introduction_script(
+        &self,
+        cx: DebugContext<'rt>
+    ) -> Result<'rt, Option<Script<'rt>>> {
+        getter!(cx, self, "introductionScript").map(|script| {
+            UndefinedOr::<Script<'rt>>::into_option(script)
+        })
+    }
+
+    pub fn get_introduction_offset(&self, cx: DebugContext<'rt>) -> Result<'rt, Option<usize>> {
+        getter!(cx, self, "introductionOffset").map(|offset| {
+            UndefinedOr::<u32>::into_option(offset).map(|offset| offset as usize)
+        })
+    }
+
+    pub fn get_element_attribute_name(&self, cx: DebugContext<'rt>) -> Result<'rt, Option<String>> {
+        getter!(cx, self, "elementAttributeName").map(|name| {
+            UndefinedOr::<String>::into_option(name)
+        })
+    }
 }
 
-derive_rooted!(*mut JSObject, Source);
+derive_rooted!(*mut JSObject, Source<'rt>);
 
-derive_convert!(Source);
+derive_convert!(Source<'rt>);
+
+struct SourceTexts {
+    runtime: *mut JSRuntime,
+    texts: RefCell<HashMap<u32, (WeakBox<'static>, Rc<SourceText>)>>
+}
+
+thread_local!(
+    static TEXTS: RefCell<Vec<Rc<SourceTexts>>> = RefCell::new(Vec::new())
+);
+
+impl SourceTexts {
+    fn get(cx: DebugContext) -> Rc<SourceTexts> {
+        let runtime = cx.runtime();
+        if let Some(texts) = TEXTS.with(|texts| {
+            texts.borrow().iter().find(|texts| texts.runtime == runtime).cloned()
+        }) {
+            return texts;
+        }
+        let texts = Rc::new(SourceTexts {
+            runtime: runtime,
+            texts: RefCell::new(HashMap::new())
+        });
+        TEXTS.with(|known| known.borrow_mut().push(texts.clone()));
+        trace::on_teardown(cx.as_raw(), Rc::downgrade(&texts) as Weak<Teardown>);
+        texts
+    }
+
+    fn find(&self, id: u32) -> Option<Rc<SourceText>> {
+        match self.texts.borrow().get(&id) {
+            Some(&(ref source, ref text)) if source.is_alive() => Some(text.clone()),
+            _ => None
+        }
+    }
+
+    fn insert(&self, cx: DebugContext, id: u32, source: &Source, text: Rc<SourceText>) {
+        let mut texts = self.texts.borrow_mut();
+        if texts.len() == texts.capacity() {
+            texts.retain(|_, &mut (ref source, _)| source.is_alive());
+        }
+        let cx = unsafe { DebugContext::from_raw(cx.as_raw()) };
+        texts.insert(id, (WeakBox::new(cx, source.get()), text));
+    }
+}
+
+impl Teardown for SourceTexts {
+    fn teardown(&self) {
+        self.texts.borrow_mut().clear();
+        let runtime = self.runtime;
+        TEXTS.with(|texts| texts.borrow_mut().retain(|texts| texts.runtime != runtime));
+    }
+}
+
+const CHECKPOINT_INTERVAL: usize = 64;
+
+pub struct SourceText {
+    text: String,
+    lines: Vec<(usize, usize)>,
+    length: usize,
+    checkpoints: Vec<(usize, usize)>
+}
+
+impl SourceText {
+    pub fn new(text: String) -> SourceText {
+        let mut lines = Vec::new();
+        let mut start = 0;
+        let mut chars = text.char_indices().peekable();
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '\r' => {
+                    lines.push((start, index));
+                    start = match chars.peek() {
+                        Some(&(next, '\n')) => {
+                            chars.next();
+                            next + 1
+                        }
+                        _ => index + 1
+                    };
+                }
+                '\n' | '\u{2028}' | '\u{2029}' => {
+                    lines.push((start, index));
+                    start = index + c.len_utf8();
+                }
+                _ => ()
+            }
+        }
+        lines.push((start, text.len()));
+        let mut length = text.len();
+        let mut checkpoints = Vec::new();
+        if !text.is_ascii() {
+            length = 0;
+            for (count, (index, c)) in text.char_indices().enumerate() {
+                if count % CHECKPOINT_INTERVAL == 0 {
+                    checkpoints.push((length, index));
+                }
+                length += c.len_utf16();
+            }
+        }
+        SourceText {
+            text: text,
+            lines: lines,
+            length: length,
+            checkpoints: checkpoints
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn line(&self, line: usize) -> Option<&str> {
+        if line == 0 {
+            return None;
+        }
+        self.lines.get(line - 1).map(|&(start, end)| &self.text[start..end])
+    }
+
+    pub fn range(&self, start: usize, length: usize) -> Option<&str> {
+        if start > self.length {
+            return None;
+        }
+        let end = if length > self.length - start { self.length } else { start + length };
+        Some(&self.text[self.byte_offset(start)..self.byte_offset(end)])
+    }
+
+    pub fn context(&self, line: usize, radius: usize) -> Vec<(usize, &str)> {
+        if line == 0 || line > self.lines.len() {
+            return Vec::new();
+        }
+        let first = if line > radius { line - radius } else { 1 };
+        let last = if line + radius < self.lines.len() { line + radius } else { self.lines.len() };
+        (first..last + 1).map(|line| (line, self.line(line).unwrap())).collect()
+    }
+
+    fn byte_offset(&self, offset: usize) -> usize {
+        if self.checkpoints.is_empty() {
+            return offset;
+        }
+        let index = match self.checkpoints.binary_search_by_key(&offset, |&(units, _)| units) {
+            Ok(index) => index,
+            Err(index) => index - 1
+        };
+        let (mut units, start) = self.checkpoints[index];
+        for (index, c) in self.text[start..].char_indices() {
+            if units >= offset {
+                return start + index;
+            }
+            units += c.len_utf16();
+        }
+        self.text.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SourceText;
+
+    #[test]
+    fn lines() {
+        let text = SourceText::new("a\nbb\r\nccc\rdd\u{2028}e".to_string());
+        assert_eq!(text.line_count(), 5);
+        assert_eq!(text.line(0), None);
+        assert_eq!(text.line(1), Some("a"));
+        assert_eq!(text.line(2), Some("bb"));
+        assert_eq!(text.line(3), Some("ccc"));
+        assert_eq!(text.line(4), Some("dd"));
+        assert_eq!(text.line(5), Some("e"));
+        assert_eq!(text.line(6), None);
+        assert_eq!(SourceText::new("a\n".to_string()).line(2), Some(""));
+    }
+
+    #[test]
+    fn range() {
+        let text = SourceText::new("let s = \"h\u{e9}llo\";".to_string());
+        assert_eq!(text.len(), 16);
+        assert_eq!(text.range(9, 5), Some("h\u{e9}llo"));
+        assert_eq!(text.range(14, 10), Some("\";"));
+        assert_eq!(text.range(16, 1), Some(""));
+        assert_eq!(text.range(17, 1), None);
+
+        let text = SourceText::new("a\u{1f600}b".to_string());
+        assert_eq!(text.len(), 4);
+        assert_eq!(text.range(1, 2), Some("\u{1f600}"));
+        assert_eq!(text.range(3, 1), Some("b"));
+
+        let long = format!("{}\u{e9}x", "\u{e9}".repeat(200));
+        let text = SourceText::new(long);
+        assert_eq!(text.range(199, 3), Some("\u{e9}\u{e9}x"));
+    }
+
+    #[test]
+    fn context() {
+        let text = SourceText::new("1\n2\n3\n4\n5".to_string());
+        assert_eq!(text.context(1, 1), vec![(1, "1"), (2, "2")]);
+        assert_eq!(text.context(3, 1), vec![(2, "2"), (3, "3"), (4, "4")]);
+        assert_eq!(text.context(5, 2), vec![(3, "3"), (4, "4"), (5, "5")]);
+        assert!(text.context(6, 2).is_empty());
+    }
+}
@@ -0,0 +1,951 @@
+use context::DebugContext;
+use debugger::{Debugger, OnNewScript, ScriptQuery};
+use exception::{Exception, Result};
+use frame::Frame;
+use rooted::Rooted;
+use script::{Breakpoint, OnHit, Script};
+use serde_json;
+use serde_json::Value as Json;
+use source::Source;
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::rc::{Rc, Weak};
+use std::result;
+use stepper::{OnStepComplete, StepKind, Stepper};
+use value::ResumptionValue;
+
+pub enum Error<'rt> {
+    Exception(Exception<'rt>),
+    Io(io::Error),
+    Json(serde_json::Error),
+    InvalidMap(String)
+}
+
+impl<'rt> From<Exception<'rt>> for Error<'rt> {
+    fn from(exception: Exception<'rt>) -> Error<'rt> {
+        Error::Exception(exception)
+    }
+}
+
+impl<'rt> From<io::Error> for Error<'rt> {
+    fn from(error: io::Error) -> Error<'rt> {
+        Error::Io(error)
+    }
+}
+
+impl<'rt> From<serde_json::Error> for Error<'rt> {
+    fn from(error: serde_json::Error) -> Error<'rt> {
+        Error::Json(error)
+    }
+}
+
+impl<'rt> fmt::Debug for Error<'rt> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl<'rt> fmt::Display for Error<'rt> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Exception(_) => write!(f, "uncaught exception in debugger"),
+            Error::Io(ref error) => write!(f, "{}", error),
+            Error::Json(ref error) => write!(f, "{}", error),
+            Error::InvalidMap(ref message) => write!(f, "invalid source map: {}", message)
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OriginalPosition {
+    pub source: String,
+    pub line: usize,
+    pub column: usize,
+    pub name: Option<String>
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct GeneratedPosition {
+    pub line: usize,
+    pub column: usize
+}
+
+#[derive(Clone, Copy)]
+struct Original {
+    source: usize,
+    line: usize,
+    column: usize,
+    name: Option<usize>
+}
+
+#[derive(Clone, Copy)]
+struct Mapping {
+    generated_line: usize,
+    generated_column: usize,
+    original: Option<Original>
+}
+
+pub struct SourceMap {
+    file: Option<String>,
+    sources: Vec<String>,
+    sources_content: Vec<Option<String>>,
+    names: Vec<String>,
+    mappings: Vec<Mapping>
+}
+
+impl SourceMap {
+    pub fn parse<'rt>(text: &str) -> result::Result<SourceMap, Error<'rt>> {
+        let text = if text.starts_with(")]}'") {
+            match text.find('\n') {
+                Some(index) => &text[index + 1..],
+                None => ""
+            }
+        } else {
+            text
+        };
+        let json: Json = try!(serde_json::from_str(text));
+        if json["version"].as_u64() != Some(3) {
+            return Err(Error::InvalidMap("unsupported version".to_string()));
+        }
+        if !json["sections"].is_null() {
+            return Err(Error::InvalidMap("index maps are not supported".to_string()));
+        }
+        let root = json["sourceRoot"].as_str().unwrap_or("");
+        let sources: Vec<String> = match json["sources"].as_array() {
+            Some(sources) => sources.iter().map(|source| {
+                join_source_root(root, source.as_str().unwrap_or(""))
+            }).collect(),
+            None => return Err(Error::InvalidMap("missing sources".to_string()))
+        };
+        let sources_content = match json["sourcesContent"].as_array() {
+            Some(contents) => contents.iter().map(|content| {
+                content.as_str().map(|content| content.to_string())
+            }).collect(),
+            None => vec![None; sources.len()]
+        };
+        let names: Vec<String> = match json["names"].as_array() {
+            Some(names) => names.iter().map(|name| {
+                name.as_str().unwrap_or("").to_string()
+            }).collect(),
+            None => Vec::new()
+        };
+        let mappings = match json["mappings"].as_str() {
+            Some(mappings) => try!(parse_mappings(mappings, sources.len(), names.len())),
+            None => return Err(Error::InvalidMap("missing mappings".to_string()))
+        };
+        Ok(SourceMap {
+            file: json["file"].as_str().map(|file| file.to_string()),
+            sources: sources,
+            sources_content: sources_content,
+            names: names,
+            mappings: mappings
+        })
+    }
+
+    pub fn resolve_sources(&mut self, map_url: &str) {
+        for source in self.sources.iter_mut() {
+            *source = resolve_url(map_url, source);
+        }
+    }
+
+    pub fn get_file(&self) -> Option<&str> {
+        self.file.as_ref().map(|file| file.as_str())
+    }
+
+    pub fn get_sources(&self) -> &[String] {
+        &self.sources
+    }
+
+    pub fn get_source_content(&self, source: &str) -> Option<&str> {
+        self.source_index(source).and_then(|index| {
+            match self.sources_content.get(index) {
+                Some(&Some(ref content)) => Some(content.as_str()),
+                _ => None
+            }
+        })
+    }
+
+    pub fn original_position_for(&self, line: usize, column: usize) -> Option<OriginalPosition> {
+        if line == 0 {
+            return None;
+        }
+        let key = (line - 1, column);
+        let mut low = 0;
+        let mut high = self.mappings.len();
+        while low < high {
+            let middle = (low + high) / 2;
+            let mapping = &self.mappings[middle];
+            if (mapping.generated_line, mapping.generated_column) <= key {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        if low == 0 {
+            return None;
+        }
+        let mapping = &self.mappings[low - 1];
+        if mapping.generated_line != line - 1 {
+            return None;
+        }
+        mapping.original.map(|original| {
+            OriginalPosition {
+                source: self.sources[original.source].clone(),
+                line: original.line + 1,
+                column: original.column,
+                name: original.name.map(|name| self.names[name].clone())
+            }
+        })
+    }
+
+    pub fn generated_positions_for(&self, source: &str, line: usize) -> Vec<GeneratedPosition> {
+        let index = match self.source_index(source) {
+            Some(index) => index,
+            None => return Vec::new()
+        };
+        let mut positions: Vec<GeneratedPosition> = self.mappings.iter().filter(|mapping| {
+            match mapping.original {
+                Some(original) => original.source == index && original.line + 1 == line,
+                None => false
+            }
+        }).map(|mapping| {
+            GeneratedPosition {
+                line: mapping.generated_line + 1,
+                column: mapping.generated_column
+            }
+        }).collect();
+        positions.sort();
+        positions.dedup();
+        positions
+    }
+
+    pub fn generated_position_for(
+        &self,
+        source: &str,
+        line: usize,
+        column: usize
+    ) -> Option<GeneratedPosition> {
+        let index = match self.source_index(source) {
+            Some(index) => index,
+            None => return None
+        };
+        self.mappings.iter().filter_map(|mapping| {
+            match mapping.original {
+                Some(original) if original.source == index &&
+                                  original.line + 1 == line &&
+                                  original.column >= column => {
+                    Some((original.column, GeneratedPosition {
+                        line: mapping.generated_line + 1,
+                        column: mapping.generated_column
+                    }))
+                }
+                _ => None
+            }
+        }).min().map(|(_, position)| position)
+    }
+
+    fn source_index(&self, source: &str) -> Option<usize> {
+        self.sources.iter().position(|known| known == source)
+    }
+}
+
+pub trait SourceMapResolver {
+    fn resolve(&self, source_url: &str, source_map_url: &str) -> io::Result<(String, String)>;
+}
+
+pub struct FileResolver;
+
+impl SourceMapResolver for FileResolver {
+    fn resolve(&self, source_url: &str, source_map_url: &str) -> io::Result<(String, String)> {
+        if source_map_url.starts_with("data:") {
+            return decode_data_url(source_map_url).map(|text| (source_url.to_string(), text));
+        }
+        let map_url = resolve_url(source_url, source_map_url);
+        let path = if map_url.starts_with("file://") {
+            &map_url["file://".len()..]
+        } else {
+            &map_url[..]
+        };
+        if path.contains("://") {
+            return Err(io::Error::new(io::ErrorKind::Other, "unsupported URL scheme"));
+        }
+        let mut text = String::new();
+        try!(try!(File::open(path)).read_to_string(&mut text));
+        Ok((map_url.clone(), text))
+    }
+}
+
+pub struct SourceMaps {
+    resolver: Box<SourceMapResolver>,
+    maps: RefCell<Vec<(String, Rc<SourceMap>)>>
+}
+
+impl SourceMaps {
+    pub fn new(resolver: Box<SourceMapResolver>) -> SourceMaps {
+        SourceMaps {
+            resolver: resolver,
+            maps: RefCell::new(Vec::new())
+        }
+    }
+
+    pub fn get(&self, url: &str) -> Option<Rc<SourceMap>> {
+        self.maps.borrow().iter().find(|&&(ref known, _)| known == url).map(|entry| {
+            entry.1.clone()
+        })
+    }
+
+    pub fn insert(&self, url: &str, map: SourceMap) -> Rc<SourceMap> {
+        let map = Rc::new(map);
+        let mut maps = self.maps.borrow_mut();
+        maps.retain(|&(ref known, _)| known != url);
+        maps.push((url.to_string(), map.clone()));
+        map
+    }
+
+    pub fn load<'rt>(
+        &self,
+        cx: DebugContext<'rt>,
+        source: &Source<'rt>
+    ) -> result::Result<Option<Rc<SourceMap>>, Error<'rt>> {
+        let url = match try!(source.get_url(cx)) {
+            Some(url) => url,
+            None => return Ok(None)
+        };
+        if let Some(map) = self.get(&url) {
+            return Ok(Some(map));
+        }
+        let source_map_url = match try!(source.get_source_map_url(cx)) {
+            Some(source_map_url) => source_map_url,
+            None => return Ok(None)
+        };
+        let (map_url, text) = try!(self.resolver.resolve(&url, &source_map_url));
+        let mut map = try!(SourceMap::parse(&text));
+        map.resolve_sources(&map_url);
+        Ok(Some(self.insert(&url, map)))
+    }
+
+    pub fn original_location<'rt>(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, Option<OriginalPosition>> {
+        self.lookup(cx, frame).map(|(_, position)| position)
+    }
+
+    pub fn set_breakpoint<'rt>(
+        &self,
+        cx: DebugContext<'rt>,
+        debugger: &Debugger<'rt>,
+        source: &str,
+        line: usize,
+        column: Option<usize>,
+        on_hit: Rc<OnHit<'rt> + 'rt>
+    ) -> Result<'rt, Vec<Breakpoint<'rt>>> {
+        let maps: Vec<(String, Rc<SourceMap>)> = self.maps.borrow().iter().map(|entry| {
+            (entry.0.clone(), entry.1.clone())
+        }).collect();
+        let mut breakpoints = Vec::new();
+        for (url, map) in maps {
+            for (script, offset) in try!(generated_offsets(
+                cx,
+                debugger,
+                &url,
+                &map,
+                source,
+                line,
+                column
+            )) {
+                breakpoints.push(try!(script.add_breakpoint(cx, offset, on_hit.clone())));
+            }
+        }
+        Ok(breakpoints)
+    }
+
+    fn lookup<'rt>(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, (bool, Option<OriginalPosition>)> {
+        let script = match try!(frame.get_script(cx)) {
+            Some(script) => script,
+            None => return Ok((false, None))
+        };
+        let map = match self.get(&try!(script.get_url(cx))) {
+            Some(map) => map,
+            None => return Ok((false, None))
+        };
+        let location = try!(script.get_offset_location(cx, try!(frame.get_offset(cx))));
+        Ok((true, map.original_position_for(location.line_number, location.column_number)))
+    }
+}
+
+struct Location<'rt> {
+    script: Script<'rt>,
+    offset: usize,
+    breakpoint: Breakpoint<'rt>
+}
+
+struct Pending<'rt> {
+    id: u64,
+    source: String,
+    line: usize,
+    column: Option<usize>,
+    on_hit: Rc<OnHit<'rt> + 'rt>,
+    locations: Vec<Location<'rt>>
+}
+
+pub struct MappedBreakpoints<'rt> {
+    debugger: Rc<Debugger<'rt>>,
+    maps: Rc<SourceMaps>,
+    entries: RefCell<Vec<Pending<'rt>>>,
+    next_id: Cell<u64>
+}
+
+impl<'rt> MappedBreakpoints<'rt> {
+    pub fn new(debugger: Rc<Debugger<'rt>>, maps: Rc<SourceMaps>) -> MappedBreakpoints<'rt> {
+        MappedBreakpoints {
+            debugger: debugger,
+            maps: maps,
+            entries: RefCell::new(Vec::new()),
+            next_id: Cell::new(1)
+        }
+    }
+
+    pub fn add(
+        &self,
+        cx: DebugContext<'rt>,
+        source: &str,
+        line: usize,
+        column: Option<usize>,
+        on_hit: Rc<OnHit<'rt> + 'rt>
+    ) -> Result<'rt, u64> {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        let mut entry = Pending {
+            id: id,
+            source: source.to_string(),
+            line: line,
+            column: column,
+            on_hit: on_hit,
+            locations: Vec::new()
+        };
+        let maps: Vec<(String, Rc<SourceMap>)> = self.maps.maps.borrow().iter().map(|entry| {
+            (entry.0.clone(), entry.1.clone())
+        }).collect();
+        for (url, map) in maps {
+            try!(self.resolve_entry(cx, &mut entry, &url, &map));
+        }
+        self.entries.borrow_mut().push(entry);
+        Ok(id)
+    }
+
+    pub fn remove(&self, cx: DebugContext<'rt>, id: u64) -> Result<'rt, bool> {
+        let entry = {
+            let mut entries = self.entries.borrow_mut();
+            match entries.iter().position(|entry| entry.id == id) {
+                Some(index) => entries.remove(index),
+                None => return Ok(false)
+            }
+        };
+        for location in entry.locations {
+            try!(location.script.remove_breakpoint(cx, location.offset, location.breakpoint));
+        }
+        Ok(true)
+    }
+
+    pub fn is_resolved(&self, id: u64) -> bool {
+        self.entries.borrow().iter().any(|entry| entry.id == id && !entry.locations.is_empty())
+    }
+
+    fn resolve_entry(
+        &self,
+        cx: DebugContext<'rt>,
+        entry: &mut Pending<'rt>,
+        url: &str,
+        map: &SourceMap
+    ) -> Result<'rt, ()> {
+        for (script, offset) in try!(generated_offsets(
+            cx,
+            &self.debugger,
+            url,
+            map,
+            &entry.source,
+            entry.line,
+            entry.column
+        )) {
+            if entry.locations.iter().any(|location| {
+                location.script.get() == script.get() && location.offset == offset
+            }) {
+                continue;
+            }
+            let breakpoint = try!(script.add_breakpoint(cx, offset, entry.on_hit.clone()));
+            entry.locations.push(Location {
+                script: script,
+                offset: offset,
+                breakpoint: breakpoint
+            });
+        }
+        Ok(())
+    }
+}
+
+impl<'rt> OnNewScript<'rt> for MappedBreakpoints<'rt> {
+    fn on_new_script(&self, cx: DebugContext<'rt>, script: &Script<'rt>) -> Result<'rt, ()> {
+        let source = try!(script.get_source(cx));
+        let map = match self.maps.load(cx, &source) {
+            Ok(Some(map)) => map,
+            Ok(None) => return Ok(()),
+            Err(Error::Exception(exception)) => return Err(exception),
+            Err(_) => return Ok(())
+        };
+        let url = try!(script.get_url(cx));
+        let mut entries = self.entries.borrow_mut();
+        for entry in entries.iter_mut() {
+            try!(self.resolve_entry(cx, entry, &url, &map));
+        }
+        Ok(())
+    }
+}
+
+pub struct MappedStepper<'rt> {
+    maps: Rc<SourceMaps>,
+    stepper: Rc<Stepper<'rt>>
+}
+
+impl<'rt> MappedStepper<'rt> {
+    pub fn new(debugger: Rc<Debugger<'rt>>, maps: Rc<SourceMaps>) -> MappedStepper<'rt> {
+        MappedStepper {
+            maps: maps,
+            stepper: Rc::new(Stepper::new(debugger))
+        }
+    }
+
+    pub fn is_stepping(&self) -> bool {
+        self.stepper.is_stepping()
+    }
+
+    pub fn step(
+        &self,
+        cx: DebugContext<'rt>,
+        kind: StepKind,
+        frame: &Frame<'rt>,
+        on_complete: Rc<OnStepComplete<'rt> + 'rt>
+    ) -> Result<'rt, ()> {
+        let step = Rc::new(MappedStep {
+            this: RefCell::new(Weak::new()),
+            maps: self.maps.clone(),
+            stepper: self.stepper.clone(),
+            kind: kind,
+            start: try!(self.maps.original_location(cx, frame)),
+            on_complete: on_complete
+        });
+        *step.this.borrow_mut() = Rc::downgrade(&step);
+        self.stepper.step(cx, kind, frame, step as Rc<OnStepComplete<'rt> + 'rt>)
+    }
+
+    pub fn cancel(&self, cx: DebugContext<'rt>) -> Result<'rt, ()> {
+        self.stepper.cancel(cx)
+    }
+}
+
+struct MappedStep<'rt> {
+    this: RefCell<Weak<MappedStep<'rt>>>,
+    maps: Rc<SourceMaps>,
+    stepper: Rc<Stepper<'rt>>,
+    kind: StepKind,
+    start: Option<OriginalPosition>,
+    on_complete: Rc<OnStepComplete<'rt> + 'rt>
+}
+
+impl<'rt> MappedStep<'rt> {
+    fn this(&self) -> Rc<MappedStep<'rt>> {
+        self.this.borrow().upgrade().unwrap()
+    }
+}
+
+impl<'rt> OnStepComplete<'rt> for MappedStep<'rt> {
+    fn on_step_complete(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        let (mapped, position) = try!(self.maps.lookup(cx, frame));
+        let unchanged = match (&self.start, &position) {
+            (&Some(ref start), &Some(ref position)) => {
+                start.source == position.source && start.line == position.line
+            }
+            (_, &None) => mapped,
+            _ => false
+        };
+        if unchanged && self.kind != StepKind::Out {
+            try!(self.stepper.step(
+                cx,
+                self.kind,
+                frame,
+                self.this() as Rc<OnStepComplete<'rt> + 'rt>
+            ));
+            return Ok(None);
+        }
+        self.on_complete.on_step_complete(cx, frame)
+    }
+}
+
+fn generated_offsets<'rt>(
+    cx: DebugContext<'rt>,
+    debugger: &Debugger<'rt>,
+    url: &str,
+    map: &SourceMap,
+    source: &str,
+    line: usize,
+    column: Option<usize>
+) -> Result<'rt, Vec<(Script<'rt>, usize)>> {
+    let positions: Vec<GeneratedPosition> = match column {
+        Some(column) => map.generated_position_for(source, line, column).into_iter().collect(),
+        None => map.generated_positions_for(source, line)
+    };
+    let mut first_positions: Vec<GeneratedPosition> = Vec::new();
+    for position in positions {
+        if !first_positions.iter().any(|known| known.line == position.line) {
+            first_positions.push(position);
+        }
+    }
+    let mut result = Vec::new();
+    for position in first_positions {
+        let query = ScriptQuery {
+            url: Some(url.to_string()),
+            line: Some(position.line)
+        };
+        for script in try!(debugger.find_scripts(cx, &query)) {
+            let candidates: Vec<_> = try!(script.get_all_column_offsets(cx))
+                .into_iter()
+                .filter(|offset| {
+                    offset.line_number == position.line &&
+                    offset.column_number >= position.column
+                })
+                .collect();
+            if let Some(column) = candidates.iter().map(|offset| offset.column_number).min() {
+                for offset in candidates.iter().filter(|offset| offset.column_number == column) {
+                    result.push((Script::new(cx, script.get()), offset.offset));
+                }
+            }
+        }
+    }
+    Ok(result)
+}
+
+pub fn resolve_url(base: &str, relative: &str) -> String {
+    if relative.contains("://") || relative.starts_with("data:") {
+        return relative.to_string();
+    }
+    let (prefix, base_path) = match base.find("://") {
+        Some(index) => {
+            let rest = &base[index + 3..];
+            match rest.find('/') {
+                Some(slash) => (&base[..index + 3 + slash], &rest[slash..]),
+                None => (base, "")
+            }
+        }
+        None => ("", base)
+    };
+    let path = if relative.starts_with('/') {
+        relative.to_string()
+    } else {
+        match base_path.rfind('/') {
+            Some(slash) => format!("{}{}", &base_path[..slash + 1], relative),
+            None => relative.to_string()
+        }
+    };
+    format!("{}{}", prefix, normalize_path(&path))
+}
+
+fn normalize_path(path: &str) -> String {
+    let absolute = path.starts_with('/');
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                if segments.last().map_or(false, |last| *last != "..") {
+                    segments.pop();
+                } else if !absolute {
+                    segments.push("..");
+                }
+            }
+            _ => segments.push(segment)
+        }
+    }
+    let joined = segments.join("/");
+    if absolute {
+        format!("/{}", joined)
+    } else {
+        joined
+    }
+}
+
+fn join_source_root(root: &str, source: &str) -> String {
+    if root.is_empty() || source.contains("://") || source.starts_with('/') {
+        source.to_string()
+    } else if root.ends_with('/') {
+        format!("{}{}", root, source)
+    } else {
+        format!("{}/{}", root, source)
+    }
+}
+
+fn decode_data_url(url: &str) -> io::Result<String> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid data URL");
+    let comma = try!(url.find(',').ok_or_else(&invalid));
+    let (header, data) = (&url[..comma], &url[comma + 1..]);
+    if !header.ends_with(";base64") {
+        return Ok(data.to_string());
+    }
+    let mut bytes = Vec::with_capacity(data.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for byte in data.bytes().filter(|byte| *byte != b'=') {
+        buffer = buffer << 6 | try!(base64_value(byte).ok_or_else(&invalid));
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| invalid())
+}
+
+fn base64_value(byte: u8) -> Option<u32> {
+    if byte >= b'A' && byte <= b'Z' {
+        Some((byte - b'A') as u32)
+    } else if byte >= b'a' && byte <= b'z' {
+        Some((byte - b'a') as u32 + 26)
+    } else if byte >= b'0' && byte <= b'9' {
+        Some((byte - b'0') as u32 + 52)
+    } else if byte == b'+' {
+        Some(62)
+    } else if byte == b'/' {
+        Some(63)
+    } else {
+        None
+    }
+}
+
+fn decode_vlq<'rt>(bytes: &[u8], position: &mut usize) -> result::Result<i64, Error<'rt>> {
+    let mut value = 0i64;
+    let mut shift = 0;
+    loop {
+        let byte = match bytes.get(*position) {
+            Some(byte) => *byte,
+            None => return Err(Error::InvalidMap("unterminated VLQ".to_string()))
+        };
+        *position += 1;
+        let digit = match base64_value(byte) {
+            Some(digit) => digit as i64,
+            None => return Err(Error::InvalidMap(format!("invalid character {:?}", byte as char)))
+        };
+        if shift > 60 || ((digit & 31) << shift) >> shift != digit & 31 {
+            return Err(Error::InvalidMap("VLQ overflow".to_string()));
+        }
+        let bits = (digit & 31) << shift;
+        value = try!(value.checked_add(bits).ok_or_else(|| {
+            Error::InvalidMap("VLQ overflow".to_string())
+        }));
+        shift += 5;
+        if digit & 32 == 0 {
+            break;
+        }
+    }
+    Ok(if value & 1 == 1 { -(value >> 1) } else { value >> 1 })
+}
+
+fn parse_mappings<'rt>(
+    mappings: &str,
+    source_count: usize,
+    name_count: usize
+) -> result::Result<Vec<Mapping>, Error<'rt>> {
+    let invalid = |message: &str| Error::InvalidMap(message.to_string());
+    let add = |value: i64, delta: i64| {
+        value.checked_add(delta).ok_or_else(|| invalid("mapping overflow"))
+    };
+    let mut result = Vec::new();
+    let mut source = 0i64;
+    let mut original_line = 0i64;
+    let mut original_column = 0i64;
+    let mut name = 0i64;
+    for (generated_line, group) in mappings.split(';').enumerate() {
+        let mut generated_column = 0i64;
+        for segment in group.split(',').filter(|segment| !segment.is_empty()) {
+            let bytes = segment.as_bytes();
+            let mut position = 0;
+            let mut fields = Vec::with_capacity(5);
+            while position < bytes.len() {
+                fields.push(try!(decode_vlq(bytes, &mut position)));
+            }
+            if fields.len() != 1 && fields.len() != 4 && fields.len() != 5 {
+                return Err(invalid("segment has an invalid number of fields"));
+            }
+            generated_column = try!(add(generated_column, fields[0]));
+            if generated_column < 0 {
+                return Err(invalid("negative generated column"));
+            }
+            let original = if fields.len() >= 4 {
+                source = try!(add(source, fields[1]));
+                original_line = try!(add(original_line, fields[2]));
+                original_column = try!(add(original_column, fields[3]));
+                if source < 0 || source as usize >= source_count {
+                    return Err(invalid("source index out of range"));
+                }
+                if original_line < 0 || original_column < 0 {
+                    return Err(invalid("negative original position"));
+                }
+                let name_index = if fields.len() == 5 {
+                    name = try!(add(name, fields[4]));
+                    if name < 0 || name as usize >= name_count {
+                        return Err(invalid("name index out of range"));
+                    }
+                    Some(name as usize)
+                } else {
+                    None
+                };
+                Some(Original {
+                    source: source as usize,
+                    line: original_line as usize,
+                    column: original_column as usize,
+                    name: name_index
+                })
+            } else {
+                None
+            };
+            result.push(Mapping {
+                generated_line: generated_line,
+                generated_column: generated_column as usize,
+                original: original
+            });
+        }
+    }
+    result.sort_by_key(|mapping| (mapping.generated_line, mapping.generated_column));
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        FileResolver,
+        GeneratedPosition,
+        OriginalPosition,
+        SourceMap,
+        SourceMapResolver,
+        decode_vlq,
+        resolve_url
+    };
+    use std::env;
+    use std::fs::File;
+    use std::io::Write;
+
+    const MAP: &'static str = r#"{
+        "version": 3,
+        "file": "bundle.js",
+        "sourceRoot": "src",
+        "sources": ["main.ts"],
+        "sourcesContent": ["let x = 1;\nfunction f() {}\n"],
+        "names": ["f"],
+        "mappings": "AAAA;AACA,IAAIA"
+    }"#;
+
+    #[test]
+    fn vlq() {
+        let decode = |text: &str| {
+            let mut position = 0;
+            decode_vlq(text.as_bytes(), &mut position).unwrap()
+        };
+        assert_eq!(decode("A"), 0);
+        assert_eq!(decode("C"), 1);
+        assert_eq!(decode("D"), -1);
+        assert_eq!(decode("gB"), 16);
+        assert_eq!(decode("hB"), -16);
+        assert!(decode_vlq(b"//////////////B", &mut 0).is_err());
+    }
+
+    #[test]
+    fn original_position() {
+        let map = SourceMap::parse(MAP).unwrap();
+        assert_eq!(map.get_file(), Some("bundle.js"));
+        assert_eq!(map.get_source_content("src/main.ts"), Some("let x = 1;\nfunction f() {}\n"));
+        assert_eq!(map.original_position_for(1, 10), Some(OriginalPosition {
+            source: "src/main.ts".to_string(),
+            line: 1,
+            column: 0,
+            name: None
+        }));
+        assert_eq!(map.original_position_for(2, 6), Some(OriginalPosition {
+            source: "src/main.ts".to_string(),
+            line: 2,
+            column: 4,
+            name: Some("f".to_string())
+        }));
+        assert_eq!(map.original_position_for(3, 0), None);
+    }
+
+    #[test]
+    fn generated_position() {
+        let map = SourceMap::parse(MAP).unwrap();
+        assert_eq!(map.generated_positions_for("src/main.ts", 2), vec![
+            GeneratedPosition { line: 2, column: 0 },
+            GeneratedPosition { line: 2, column: 4 }
+        ]);
+        assert_eq!(
+            map.generated_position_for("src/main.ts", 2, 1),
+            Some(GeneratedPosition { line: 2, column: 4 })
+        );
+        assert!(map.generated_positions_for("src/other.ts", 1).is_empty());
+    }
+
+    #[test]
+    fn invalid() {
+        let maps = [
+            r#"{ "version": 2, "sources": [], "mappings": "" }"#,
+            r#"{ "version": 3, "sources": [], "mappings": "AAAA" }"#,
+            r#"{ "version": 3, "sources": ["a"], "mappings": "AA" }"#,
+            r#"{"version":3,"sources":[],"mappings":"+///////////H,+///////////H,+///////////H"}"#
+        ];
+        for map in maps.iter() {
+            assert!(SourceMap::parse(map).is_err());
+        }
+    }
+
+    #[test]
+    fn urls() {
+        assert_eq!(resolve_url("http://host/a/b.js", "b.js.map"), "http://host/a/b.js.map");
+        assert_eq!(resolve_url("http://host/a/b.js", "../c/d.ts"), "http://host/c/d.ts");
+        assert_eq!(resolve_url("http://host/a/b.js", "/e.ts"), "http://host/e.ts");
+        assert_eq!(resolve_url("/tmp/out/b.js", "./src/../f.ts"), "/tmp/out/f.ts");
+        assert_eq!(resolve_url("b.js", "g.ts"), "g.ts");
+        assert_eq!(resolve_url("a/b.js", "webpack://app/h.ts"), "webpack://app/h.ts");
+    }
+
+    #[test]
+    fn file_resolver() {
+        let directory = env::temp_dir();
+        let path = directory.join("debugger-source-map-test.js.map");
+        File::create(&path).unwrap().write_all(MAP.as_bytes()).unwrap();
+        let source_url = directory.join("debugger-source-map-test.js");
+        let (map_url, text) = FileResolver.resolve(
+            source_url.to_str().unwrap(),
+            "debugger-source-map-test.js.map"
+        ).unwrap();
+        assert_eq!(map_url, path.to_str().unwrap());
+        let mut map = SourceMap::parse(&text).unwrap();
+        map.resolve_sources(&map_url);
+        let expected = directory.join("src/main.ts");
+        assert_eq!(map.get_sources(), &[expected.to_str().unwrap().to_string()][..]);
+
+        let (_, text) = FileResolver.resolve(
+            "bundle.js",
+            "data:application/json;charset=utf-8;base64,eyJ2ZXJzaW9uIjozfQ=="
+        ).unwrap();
+        assert_eq!(text, r#"{"version":3}"#);
+    }
+}
@@ -0,0 +1,352 @@
+use context::DebugContext;
+use debugger::{Debugger, OnEnterFrame};
+use exception::Result;
+use frame::{Frame, OnPop, OnStep};
+use rooted::Rooted;
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
+use value::{CompletionValue, ResumptionValue};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StepKind {
+    Over,
+    In,
+    Out
+}
+
+pub trait OnStepComplete<'rt> {
+    fn on_step_complete(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>>;
+}
+
+pub struct Stepper<'rt> {
+    debugger: Rc<Debugger<'rt>>,
+    current: RefCell<Option<Rc<Step<'rt>>>>
+}
+
+impl<'rt> Stepper<'rt> {
+    pub fn new(debugger: Rc<Debugger<'rt>>) -> Stepper<'rt> {
+        Stepper {
+            debugger: debugger,
+            current: RefCell::new(None)
+        }
+    }
+
+    pub fn is_stepping(&self) -> bool {
+        match *self.current.borrow() {
+            Some(ref step) => !step.done.get(),
+            None => false
+        }
+    }
+
+    pub fn step_over(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>,
+        on_complete: Rc<OnStepComplete<'rt> + 'rt>
+    ) -> Result<'rt, ()> {
+        self.step(cx, StepKind::Over, frame, on_complete)
+    }
+
+    pub fn step_in(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>,
+        on_complete: Rc<OnStepComplete<'rt> + 'rt>
+    ) -> Result<'rt, ()> {
+        self.step(cx, StepKind::In, frame, on_complete)
+    }
+
+    pub fn step_out(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>,
+        on_complete: Rc<OnStepComplete<'rt> + 'rt>
+    ) -> Result<'rt, ()> {
+        self.step(cx, StepKind::Out, frame, on_complete)
+    }
+
+    pub fn step(
+        &self,
+        cx: DebugContext<'rt>,
+        kind: StepKind,
+        frame: &Frame<'rt>,
+        on_complete: Rc<OnStepComplete<'rt> + 'rt>
+    ) -> Result<'rt, ()> {
+        try!(self.cancel(cx));
+        let step = Rc::new(Step {
+            kind: kind,
+            debugger: self.debugger.clone(),
+            on_complete: on_complete,
+            this: RefCell::new(Weak::new()),
+            start_frame: Frame::new(cx, frame.get()),
+            start_line: Cell::new(None),
+            frames: RefCell::new(Vec::new()),
+            done: Cell::new(false)
+        });
+        *step.this.borrow_mut() = Rc::downgrade(&step);
+        *self.current.borrow_mut() = Some(step.clone());
+        step.start(cx)
+    }
+
+    pub fn cancel(&self, cx: DebugContext<'rt>) -> Result<'rt, ()> {
+        match self.current.borrow_mut().take() {
+            Some(step) => step.finish(cx),
+            None => Ok(())
+        }
+    }
+}
+
+struct Step<'rt> {
+    kind: StepKind,
+    debugger: Rc<Debugger<'rt>>,
+    on_complete: Rc<OnStepComplete<'rt> + 'rt>,
+    this: RefCell<Weak<Step<'rt>>>,
+    start_frame: Frame<'rt>,
+    start_line: Cell<Option<usize>>,
+    frames: RefCell<Vec<(Frame<'rt>, bool)>>,
+    done: Cell<bool>
+}
+
+impl<'rt> Step<'rt> {
+    fn start(&self, cx: DebugContext<'rt>) -> Result<'rt, ()> {
+        let start_line = try!(get_entry_point_line(cx, &self.start_frame)).map(|(line, _)| line);
+        self.start_line.set(start_line);
+        match self.kind {
+            StepKind::Over => try!(self.hook(cx, &self.start_frame, true)),
+            StepKind::In => {
+                try!(self.hook(cx, &self.start_frame, true));
+                try!(self.debugger.add_on_enter_frame(
+                    cx,
+                    self.this() as Rc<OnEnterFrame<'rt> + 'rt>
+                ));
+            }
+            StepKind::Out => try!(self.hook(cx, &self.start_frame, false))
+        }
+        Ok(())
+    }
+
+    fn this(&self) -> Rc<Step<'rt>> {
+        self.this.borrow().upgrade().unwrap()
+    }
+
+    fn hook(&self, cx: DebugContext<'rt>, frame: &Frame<'rt>, on_step: bool) -> Result<'rt, ()> {
+        let hooked = self.frames.borrow().iter().position(|&(ref hooked, _)| {
+            hooked.get() == frame.get()
+        });
+        if let Some(index) = hooked {
+            if on_step && !self.frames.borrow()[index].1 {
+                try!(self.debugger.add_on_step(cx, frame, self.this() as Rc<OnStep<'rt> + 'rt>));
+                self.frames.borrow_mut()[index].1 = true;
+            }
+            return Ok(());
+        }
+        if on_step {
+            try!(self.debugger.add_on_step(cx, frame, self.this() as Rc<OnStep<'rt> + 'rt>));
+        }
+        try!(self.debugger.add_on_pop(cx, frame, self.this() as Rc<OnPop<'rt> + 'rt>));
+        self.frames.borrow_mut().push((Frame::new(cx, frame.get()), on_step));
+        Ok(())
+    }
+
+    fn finish(&self, cx: DebugContext<'rt>) -> Result<'rt, ()> {
+        if self.done.get() {
+            return Ok(());
+        }
+        self.done.set(true);
+        let on_step_hook = self.this() as Rc<OnStep<'rt> + 'rt>;
+        let on_pop_hook = self.this() as Rc<OnPop<'rt> + 'rt>;
+        let frames: Vec<_> = self.frames.borrow_mut().drain(..).collect();
+        for (frame, on_step) in frames {
+            if on_step {
+                try!(self.debugger.remove_on_step(cx, &frame, &on_step_hook));
+            }
+            try!(self.debugger.remove_on_pop(cx, &frame, &on_pop_hook));
+        }
+        if self.kind == StepKind::In {
+            let on_enter_frame = self.this() as Rc<OnEnterFrame<'rt> + 'rt>;
+            try!(self.debugger.remove_on_enter_frame(cx, &on_enter_frame));
+        }
+        Ok(())
+    }
+
+    fn complete(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        try!(self.finish(cx));
+        self.on_complete.on_step_complete(cx, frame)
+    }
+}
+
+impl<'rt> OnStep<'rt> for Step<'rt> {
+    fn on_step(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        if self.done.get() {
+            return Ok(None);
+        }
+        match try!(get_entry_point_line(cx, frame)) {
+            Some((line, true)) => {
+                if frame.get() == self.start_frame.get() && Some(line) == self.start_line.get() {
+                    return Ok(None);
+                }
+                self.complete(cx, frame)
+            }
+            _ => Ok(None)
+        }
+    }
+}
+
+impl<'rt> OnPop<'rt> for Step<'rt> {
+    fn on_pop(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>,
+        _value: &CompletionValue<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        if self.done.get() {
+            return Ok(None);
+        }
+        self.frames.borrow_mut().retain(|&(ref hooked, _)| hooked.get() != frame.get());
+        match try!(frame.get_older(cx)) {
+            Some(older) => try!(self.hook(cx, &older, true)),
+            None => try!(self.finish(cx))
+        }
+        Ok(None)
+    }
+}
+
+impl<'rt> OnEnterFrame<'rt> for Step<'rt> {
+    fn on_enter_frame(
+        &self,
+        cx: DebugContext<'rt>,
+        frame: &Frame<'rt>
+    ) -> Result<'rt, ResumptionValue<'rt>> {
+        if !self.done.get() {
+            try!(self.hook(cx, frame, true));
+        }
+        Ok(None)
+    }
+}
+
+fn get_entry_point_line<'rt>(
+    cx: DebugContext<'rt>,
+    frame: &Frame<'rt>
+) -> Result<'rt, Option<(usize, bool)>> {
+    let script = match try!(frame.get_script(cx)) {
+        Some(script) => script,
+        None => return Ok(None)
+    };
+    let location = try!(script.get_offset_location(cx, try!(frame.get_offset(cx))));
+    Ok(Some((location.line_number, location.is_entry_point)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OnStepComplete, StepKind, Stepper, get_entry_point_line};
+    use context::DebugContext;
+    use debugger::OnDebuggerStatement;
+    use exception::Result;
+    use frame::Frame;
+    use std::cell::{Cell, RefCell};
+    use std::rc::{Rc, Weak};
+    use utils;
+    use value::ResumptionValue;
+
+    struct Recorder<'rt> {
+        stepper: Stepper<'rt>,
+        kind: StepKind,
+        remaining: Cell<usize>,
+        lines: RefCell<Vec<usize>>,
+        this: RefCell<Weak<Recorder<'rt>>>
+    }
+
+    impl<'rt> Recorder<'rt> {
+        fn step(&self, cx: DebugContext<'rt>, frame: &Frame<'rt>) -> Result<'rt, ()> {
+            if self.remaining.get() == 0 {
+                return Ok(());
+            }
+            self.remaining.set(self.remaining.get() - 1);
+            let this = self.this.borrow().upgrade().unwrap();
+            self.stepper.step(cx, self.kind, frame, this)
+        }
+    }
+
+    impl<'rt> OnDebuggerStatement<'rt> for Recorder<'rt> {
+        fn on_debugger_statement(
+            &self,
+            cx: DebugContext<'rt>,
+            frame: &Frame<'rt>
+        ) -> Result<'rt, ResumptionValue<'rt>> {
+            try!(self.step(cx, frame));
+            Ok(None)
+        }
+    }
+
+    impl<'rt> OnStepComplete<'rt> for Recorder<'rt> {
+        fn on_step_complete(
+            &self,
+            cx: DebugContext<'rt>,
+            frame: &Frame<'rt>
+        ) -> Result<'rt, ResumptionValue<'rt>> {
+            let (line, _) = try!(get_entry_point_line(cx, frame)).unwrap();
+            self.lines.borrow_mut().push(line);
+            try!(self.step(cx, frame));
+            Ok(None)
+        }
+    }
+
+    fn steps(kind: StepKind, count: usize, source: &str) -> Vec<usize> {
+        let mut lines = Vec::new();
+        utils::with_debuggee(|cx, debugger, evaluate| {
+            let recorder = Rc::new(Recorder {
+                stepper: Stepper::new(debugger.clone()),
+                kind: kind,
+                remaining: Cell::new(count),
+                lines: RefCell::new(Vec::new()),
+                this: RefCell::new(Weak::new())
+            });
+            *recorder.this.borrow_mut() = Rc::downgrade(&recorder);
+            debugger.set_on_debugger_statement(cx, Some(recorder.clone())).unwrap();
+            evaluate(source);
+            assert!(!recorder.stepper.is_stepping());
+            debugger.set_on_debugger_statement(cx, None).unwrap();
+            lines = recorder.lines.borrow().clone();
+        });
+        lines
+    }
+
+    const SOURCE: &'static str = "function g() {
+    return 1;
+}
+function f() {
+    debugger;
+    var a = g();
+    return a + g();
+}
+f();
+";
+
+    #[test]
+    fn step_over() {
+        assert_eq!(steps(StepKind::Over, 2, SOURCE), vec![6, 7]);
+    }
+
+    #[test]
+    fn step_in() {
+        assert_eq!(steps(StepKind::In, 4, SOURCE), vec![6, 2, 7, 2]);
+    }
+
+    #[test]
+    fn step_out() {
+        let source = SOURCE.replace("return 1;", "debugger;\n    return 1;");
+        assert_eq!(steps(StepKind::Out, 1, &source), vec![8]);
+    }
+}
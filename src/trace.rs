@@ -1,3 +1,4 @@
+use context::DebugContext;
 use ext::HeapExt;
 use js::{glue, jsapi};
 use js::jsapi::{
@@ -6,6 +7,7 @@ use js::jsapi::{
     JSContext,
     JSFunction,
     JSObject,
+    JSRuntime,
     JSScript,
     JSString,
     JSTracer,
@@ -13,9 +15,13 @@ use js::jsapi::{
     Value,
     jsid,
 };
-use js::rust::GCMethods;
+use js::rust::{ExposeObjectToActiveJS, GCMethods};
 use rooted::Rooted;
+use std::cell::{Cell, RefCell};
+use std::marker::PhantomData;
+use std::mem;
 use std::os::raw::c_void;
+use std::rc::{Rc, Weak};
 
 pub unsafe trait Trace {
     unsafe fn trace(&self, trc: *mut JSTracer);
@@ -57,45 +63,212 @@ unsafe impl Trace for Heap<jsid> {
     }
 }
 
-pub struct TracedBox<T: Copy + GCMethods<T>> where Heap<T>: HeapExt<T> + Trace {
-    cx: *mut JSContext,
-    ptr: Box<Heap<T>>
+pub trait Teardown {
+    fn teardown(&self);
 }
 
-impl<T: Copy + GCMethods<T>> TracedBox<T> where Heap<T>: HeapExt<T> + Trace {
-    pub fn new(cx: *mut JSContext, ptr: T) -> TracedBox<T> {
-        let result = TracedBox {
-            cx: cx,
-            ptr: Box::new(Heap::new(ptr))
-        };
+struct RootArena {
+    runtime: *mut JSRuntime,
+    roots: RefCell<Vec<Option<*const Trace>>>,
+    free: RefCell<Vec<usize>>,
+    weak: RefCell<Vec<Option<*mut Heap<*mut JSObject>>>>,
+    weak_free: RefCell<Vec<usize>>,
+    teardowns: RefCell<Vec<Weak<Teardown>>>
+}
+
+thread_local!(
+    static ARENAS: RefCell<Vec<(*mut JSRuntime, Rc<RootArena>)>> = RefCell::new(Vec::new())
+);
+
+impl RootArena {
+    fn register(cx: *mut JSContext) -> Rc<RootArena> {
+        let rt = unsafe { jsapi::JS_GetRuntime(cx) };
+        ARENAS.with(|arenas| {
+            let mut arenas = arenas.borrow_mut();
+            assert!(
+                arenas.iter().all(|&(known, _)| known != rt),
+                "runtime is already being debugged"
+            );
+            let arena = Rc::new(RootArena {
+                runtime: rt,
+                roots: RefCell::new(Vec::new()),
+                free: RefCell::new(Vec::new()),
+                weak: RefCell::new(Vec::new()),
+                weak_free: RefCell::new(Vec::new()),
+                teardowns: RefCell::new(Vec::new())
+            });
+            unsafe {
+                assert!(jsapi::JS_AddExtraGCRootsTracer(
+                    rt,
+                    Some(RootArena::trace),
+                    &*arena as *const RootArena as *mut _
+                ));
+                assert!(jsapi::JS_AddWeakPointerZoneGroupCallback(
+                    rt,
+                    Some(RootArena::update_weak),
+                    &*arena as *const RootArena as *mut _
+                ));
+            }
+            arenas.push((rt, arena.clone()));
+            arena
+        })
+    }
+
+    fn unregister(&self) {
+        loop {
+            let teardowns = mem::replace(&mut *self.teardowns.borrow_mut(), Vec::new());
+            if teardowns.is_empty() {
+                break;
+            }
+            for teardown in teardowns {
+                if let Some(teardown) = teardown.upgrade() {
+                    teardown.teardown();
+                }
+            }
+        }
         unsafe {
-            assert!(jsapi::JS_AddExtraGCRootsTracer(
-                jsapi::JS_GetRuntime(result.cx),
-                Some(TracedBox::trace),
-                result.ptr.as_ref() as *const Heap<T> as *mut _)
+            jsapi::JS_RemoveExtraGCRootsTracer(
+                self.runtime,
+                Some(RootArena::trace),
+                self as *const RootArena as *mut _
             );
+            jsapi::JS_RemoveWeakPointerZoneGroupCallback(
+                self.runtime,
+                Some(RootArena::update_weak)
+            );
+        }
+        let this = self as *const RootArena;
+        ARENAS.with(|arenas| {
+            arenas.borrow_mut().retain(|&(_, ref arena)| &**arena as *const RootArena != this);
+        });
+    }
+
+    fn get(cx: *mut JSContext) -> Rc<RootArena> {
+        let rt = unsafe { jsapi::JS_GetRuntime(cx) };
+        ARENAS.with(|arenas| {
+            arenas.borrow().iter().find(|&&(known, _)| known == rt).map(|&(_, ref arena)| {
+                arena.clone()
+            }).expect("runtime is not being debugged")
+        })
+    }
+
+    fn add_teardown(&self, teardown: Weak<Teardown>) {
+        let mut teardowns = self.teardowns.borrow_mut();
+        if teardowns.len() == teardowns.capacity() {
+            teardowns.retain(|teardown| teardown.upgrade().is_some());
+        }
+        teardowns.push(teardown);
+    }
+
+    fn add(&self, root: *const Trace) -> usize {
+        let mut roots = self.roots.borrow_mut();
+        match self.free.borrow_mut().pop() {
+            Some(index) => {
+                roots[index] = Some(root);
+                index
+            }
+            None => {
+                roots.push(Some(root));
+                roots.len() - 1
+            }
+        }
+    }
+
+    fn remove(&self, index: usize) {
+        self.roots.borrow_mut()[index] = None;
+        self.free.borrow_mut().push(index);
+    }
+
+    fn add_weak(&self, ptr: *mut Heap<*mut JSObject>) -> usize {
+        let mut weak = self.weak.borrow_mut();
+        match self.weak_free.borrow_mut().pop() {
+            Some(index) => {
+                weak[index] = Some(ptr);
+                index
+            }
+            None => {
+                weak.push(Some(ptr));
+                weak.len() - 1
+            }
+        }
+    }
+
+    fn remove_weak(&self, index: usize) {
+        self.weak.borrow_mut()[index] = None;
+        self.weak_free.borrow_mut().push(index);
+    }
+
+    unsafe extern "C" fn update_weak(_: *mut JSRuntime, data: *mut c_void) {
+        let arena = &*(data as *const RootArena);
+        for ptr in arena.weak.borrow().iter() {
+            if let Some(ptr) = *ptr {
+                jsapi::JS_UpdateWeakPointerAfterGC(ptr);
+            }
         }
-        result
     }
 
     unsafe extern "C" fn trace(trc: *mut JSTracer, data: *mut c_void) {
-        (&*(data as *mut _ as *const Heap<T>)).trace(trc);
+        let arena = &*(data as *const RootArena);
+        for root in arena.roots.borrow().iter() {
+            if let Some(root) = *root {
+                (*root).trace(trc);
+            }
+        }
+    }
+}
+
+pub struct ArenaGuard(Rc<RootArena>);
+
+impl ArenaGuard {
+    pub fn new(cx: *mut JSContext) -> ArenaGuard {
+        ArenaGuard(RootArena::register(cx))
     }
 }
 
-impl<T: Copy + GCMethods<T>> Drop for TracedBox<T> where Heap<T>: HeapExt<T> + Trace {
+pub fn on_teardown(cx: *mut JSContext, teardown: Weak<Teardown>) {
+    RootArena::get(cx).add_teardown(teardown);
+}
+
+impl Drop for ArenaGuard {
     fn drop(&mut self) {
-        unsafe {
-            jsapi::JS_RemoveExtraGCRootsTracer(
-                jsapi::JS_GetRuntime(self.cx),
-                Some(TracedBox::trace),
-                &*self.ptr as *const Heap<T> as *mut _
-            )
+        self.0.unregister();
+    }
+}
+
+pub struct TracedBox<'rt, T: Copy + GCMethods<T>> where Heap<T>: HeapExt<T> + Trace {
+    arena: Rc<RootArena>,
+    index: usize,
+    ptr: Box<Heap<T>>,
+    marker: PhantomData<Cell<&'rt ()>>
+}
+
+impl<'rt, T: Copy + GCMethods<T>> TracedBox<'rt, T>
+    where Heap<T>: HeapExt<T> + Trace + 'static
+{
+    pub fn new(cx: DebugContext<'rt>, ptr: T) -> TracedBox<'rt, T> {
+        let arena = RootArena::get(cx.as_raw());
+        let ptr = Box::new(Heap::new(ptr));
+        let index = arena.add(&*ptr as &Trace as *const Trace);
+        TracedBox {
+            arena: arena,
+            index: index,
+            ptr: ptr,
+            marker: PhantomData
         }
     }
 }
 
-impl<T: Copy + GCMethods<T>> Rooted<T> for TracedBox<T> where Heap<T>: HeapExt<T> + Trace {
+impl<'rt, T: Copy + GCMethods<T>> Drop for TracedBox<'rt, T>
+    where Heap<T>: HeapExt<T> + Trace
+{
+    fn drop(&mut self) {
+        self.arena.remove(self.index);
+    }
+}
+
+impl<'rt, T: Copy + GCMethods<T>> Rooted<T> for TracedBox<'rt, T>
+    where Heap<T>: HeapExt<T> + Trace
+{
     fn get(&self) -> T {
         unsafe { *self.ptr.ptr.get() }
     }
@@ -107,4 +280,50 @@ impl<T: Copy + GCMethods<T>> Rooted<T> for TracedBox<T> where Heap<T>: HeapExt<T
     fn handle_mut(&mut self) -> MutableHandle<T> {
         unsafe { MutableHandle::from_marked_location(self.ptr.ptr.get()) }
     }
+
+    fn runtime(&self) -> *mut JSRuntime {
+        self.arena.runtime
+    }
+}
+
+pub struct WeakBox<'rt> {
+    arena: Rc<RootArena>,
+    index: usize,
+    ptr: Box<Heap<*mut JSObject>>,
+    marker: PhantomData<Cell<&'rt ()>>
+}
+
+impl<'rt> WeakBox<'rt> {
+    pub fn new(cx: DebugContext<'rt>, ptr: *mut JSObject) -> WeakBox<'rt> {
+        let arena = RootArena::get(cx.as_raw());
+        let mut ptr = Box::new(Heap::new(ptr));
+        let index = arena.add_weak(&mut *ptr as *mut Heap<*mut JSObject>);
+        WeakBox {
+            arena: arena,
+            index: index,
+            ptr: ptr,
+            marker: PhantomData
+        }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        unsafe { !(*self.ptr.ptr.get()).is_null() }
+    }
+
+    pub fn get(&self) -> Option<*mut JSObject> {
+        let ptr = unsafe { *self.ptr.ptr.get() };
+        if ptr.is_null() {
+            return None;
+        }
+        unsafe {
+            ExposeObjectToActiveJS(ptr);
+        }
+        Some(ptr)
+    }
+}
+
+impl<'rt> Drop for WeakBox<'rt> {
+    fn drop(&mut self) {
+        self.arena.remove_weak(self.index);
+    }
 }
@@ -1,4 +1,8 @@
+#[cfg(test)]
+use context::DebugContext;
 use convert::{FromJSValue, ToJSValue};
+#[cfg(test)]
+use debugger::Debugger;
 use exception::Result;
 use js::{JSCLASS_GLOBAL_SLOT_COUNT, JSCLASS_IS_GLOBAL, JSCLASS_RESERVED_SLOTS_MASK, jsapi};
 use js::jsapi::{
@@ -13,9 +17,13 @@ use js::jsapi::{
     OnNewGlobalHookOption,
 };
 use js::jsval;
+#[cfg(test)]
+use js::rust::Runtime;
 use std::ffi::CString;
 use std::os::raw::c_char;
 use std::ptr;
+#[cfg(test)]
+use std::rc::Rc;
 
 pub unsafe fn new_global_object(cx: *mut JSContext, rval: MutableHandleObject) -> bool {
     static CLASS: JSClass = JSClass {
@@ -74,17 +82,21 @@ pub unsafe fn define_property<T: ToJSValue>(
     )
 }
 
-pub unsafe fn has_property(cx: *mut JSContext, obj: HandleObject, name: &str) -> Result<bool> {
+pub unsafe fn has_property<'rt>(
+    cx: *mut JSContext,
+    obj: HandleObject,
+    name: &str
+) -> Result<'rt, bool> {
     let mut found = false;
     try_jsapi!(cx, jsapi::JS_HasProperty(cx, obj, CString::new(name).unwrap().as_ptr(), &mut found));
     Ok(found)
 }
 
-pub unsafe fn get_property<T: FromJSValue>(
+pub unsafe fn get_property<'rt, T: FromJSValue>(
     cx: *mut JSContext,
     obj: HandleObject,
     name: &str
-) -> Result<T> {
+) -> Result<'rt, T> {
     rooted!(in (cx) let mut rval = jsval::UndefinedValue());
     try_jsapi!(cx, jsapi::JS_GetProperty(
         cx,
@@ -112,11 +124,11 @@ pub unsafe fn set_property<T: ToJSValue>(
     true
 }
 
-pub unsafe fn get_element<T: FromJSValue>(
+pub unsafe fn get_element<'rt, T: FromJSValue>(
     cx: *mut JSContext,
     obj: HandleObject,
     index: usize
-) -> Result<T> {
+) -> Result<'rt, T> {
     rooted!(in (cx) let mut rval = jsval::UndefinedValue());
     try_jsapi!(cx, jsapi::JS_GetElement(
         cx,
@@ -144,12 +156,12 @@ pub unsafe fn set_element<T: ToJSValue>(
     true
 }
 
-pub unsafe fn call_method<T: FromJSValue>(
+pub unsafe fn call_method<'rt, T: FromJSValue>(
     cx: *mut JSContext,
     obj: HandleObject,
     name: &str,
     args: &HandleValueArray
-) -> Result<T> {
+) -> Result<'rt, T> {
     rooted!(in (cx) let mut rval = jsval::UndefinedValue());
     try_jsapi!(cx, jsapi::JS_CallFunctionName(
         cx,
@@ -160,3 +172,22 @@ pub unsafe fn call_method<T: FromJSValue>(
     ));
     FromJSValue::from_js_value(cx, rval.handle())
 }
+
+#[cfg(test)]
+pub fn with_debuggee<F>(f: F)
+    where F: for<'rt> FnOnce(DebugContext<'rt>, &Rc<Debugger<'rt>>, &Fn(&str))
+{
+    let runtime = Runtime::new();
+    DebugContext::with(&runtime, |cx| {
+        rooted!(in (runtime.cx()) let mut global = ptr::null_mut());
+        unsafe {
+            assert!(new_global_object(runtime.cx(), global.handle_mut()));
+        }
+        let debugger = Rc::new(Debugger::new(cx));
+        debugger.add_debuggee(cx, global.handle()).unwrap();
+        f(cx, &debugger, &|source| {
+            rooted!(in (runtime.cx()) let mut rval = jsval::UndefinedValue());
+            runtime.evaluate_script(global.handle(), source, "test", 1, rval.handle_mut()).unwrap();
+        });
+    });
+}
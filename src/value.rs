@@ -6,18 +6,18 @@ use object::Object;
 use std::ptr;
 use utils;
 
-pub enum Value {
+pub enum Value<'rt> {
     Undefined,
     Boolean(bool),
     Int32(i32),
     Double(f64),
     String(String),
-    Object(Object),
+    Object(Object<'rt>),
     Null,
 }
 
-impl FromJSValue for Value {
-    unsafe fn from_js_value(cx: *mut JSContext, v: HandleValue) -> Result<Self> {
+impl<'rt> FromJSValue for Value<'rt> {
+    unsafe fn from_js_value<'a>(cx: *mut JSContext, v: HandleValue) -> Result<'a, Self> {
         if v.is_undefined() {
             FromJSValue::from_js_value(cx, v).map(|()| Value::Undefined)
         } else if v.is_boolean() {
@@ -38,7 +38,7 @@ impl FromJSValue for Value {
     }
 }
 
-impl ToJSValue for Value {
+impl<'rt> ToJSValue for Value<'rt> {
     unsafe fn to_js_value(&self, cx: *mut JSContext, rval: MutableHandleValue) -> bool {
         match self {
             &Value::Undefined => ().to_js_value(cx, rval),
@@ -52,14 +52,16 @@ impl ToJSValue for Value {
     }
 }
 
-pub enum CompletionValue {
-    Return(Value),
-    Throw(Value),
+#[cfg_attr(feature = "serialize", derive(Serialize))]
+#[cfg_attr(feature = "serialize", serde(rename_all = "lowercase"))]
+pub enum CompletionValue<'rt> {
+    Return(Value<'rt>),
+    Throw(Value<'rt>),
     Terminate,
 }
 
-impl FromJSValue for CompletionValue {
-    unsafe fn from_js_value(cx: *mut JSContext, v: HandleValue) -> Result<Self> {
+impl<'rt> FromJSValue for CompletionValue<'rt> {
+    unsafe fn from_js_value<'a>(cx: *mut JSContext, v: HandleValue) -> Result<'a, Self> {
         NullOr::<*mut JSObject>::from_js_value(cx, v).and_then(|obj| {
             match obj.into_option() {
                 Some(obj) => {
@@ -69,7 +71,7 @@ impl FromJSValue for CompletionValue {
                         Ok(CompletionValue::Return(try!(utils::get_property(
                             cx,
                             obj.handle(),
-                            "result"
+                            "return"
                         ))))
                     } else {
                         assert!(try!(utils::has_property(cx, obj.handle(), "throw")));
@@ -87,12 +89,12 @@ impl FromJSValue for CompletionValue {
     }
 }
 
-impl ToJSValue for CompletionValue {
+impl<'rt> ToJSValue for CompletionValue<'rt> {
     unsafe fn to_js_value(&self, cx: *mut JSContext, rval: MutableHandleValue) -> bool {
         match self {
             &CompletionValue::Return(ref value) => {
                 rooted!(in (cx) let obj = try_jsapi!(jsapi::JS_NewObject(cx, ptr::null_mut())));
-                try_jsapi!(utils::define_property(cx, obj.handle(), "result", value));
+                try_jsapi!(utils::define_property(cx, obj.handle(), "return", value));
                 obj.to_js_value(cx, rval)
             },
             &CompletionValue::Throw(ref value) => {
@@ -107,9 +109,9 @@ impl ToJSValue for CompletionValue {
     }
 }
 
-pub type ResumptionValue = Option<CompletionValue>;
+pub type ResumptionValue<'rt> = Option<CompletionValue<'rt>>;
 
-impl ToJSValue for ResumptionValue {
+impl<'rt> ToJSValue for ResumptionValue<'rt> {
     unsafe fn to_js_value(&self, cx: *mut JSContext, rval: MutableHandleValue) -> bool {
         match self {
             &Some(ref value) => value.to_js_value(cx, rval),